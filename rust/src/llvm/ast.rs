@@ -1,42 +1,638 @@
+use std::collections::HashMap;
+
+use crate::types::integer::Integer;
+
 pub type Ast = Vec<Stmt>;
 
+/// Where a node starts in the source. Stored as the length of the input left at that point,
+/// so parsers can record it without access to the whole source. Spans never affect equality.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    remaining: usize,
+    file: usize, // index of the module file the node was parsed from, 0 for the root file
+}
+
+impl Span {
+    pub fn new(remaining_input: &str) -> Self {
+        Span { remaining: remaining_input.len(), file: 0 }
+    }
+
+    /// The same position in the module file at index `file`
+    pub fn in_file(self, file: usize) -> Self {
+        Span { file, ..self }
+    }
+
+    pub fn file(&self) -> usize {
+        self.file
+    }
+
+    /// 1-based line and column of the span within `source`
+    pub fn location(&self, source: &str) -> (usize, usize) {
+        let offset = source.len().saturating_sub(self.remaining);
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        (line, column)
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
-    Num(i32),
+    Num(Integer), // unsuffixed literal, takes the integer type of the operand it is combined with
+    Float(f64),   // unsuffixed literal is `f64` unless combined with an `f32`
     Call(String, Vec<Expr>), // should this be a box?
     Str(String),
     Ident(String),
     QualifiedIdent(Vec<String>),
     Char(char),
+    Bool(bool),
     Infix(Box<Expr>, BinaryOp, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    /// `expr as T`, also produced for suffixed literals like `10u8`
+    Cast(Box<Expr>, Type),
+    /// `if` used as a value, e.g. `let x = if a < b { a } else { b };`. Each branch yields its trailing expression
+    If {
+        condition: Box<Expr>,
+        then_branch: Vec<Stmt>,
+        else_branch: Vec<Stmt>,
+    },
 
     Array(Vec<Expr>),             // Represents an array literal, e.g., [1, "hello", 'c']
     ArrayIndexing(Box<Expr>, Box<Expr>), // Represents array indexing, e.g., arr[2]
+    /// `arr[1..3]` or `s[..5]`, a view of part of an array, `Vec`, slice or string. Either bound can be left out.
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `Point { x: 1, y: 2 }`, fields in the order they are written
+    StructLiteral(String, Vec<(String, Expr)>),
+    FieldAccess(Box<Expr>, String), // `point.x`
+    /// `Shape::Rect(1, 2)`, or `Shape::Empty` for a variant without a payload
+    EnumVariant {
+        enum_name: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    /// Arms are tried in order, the first one whose pattern matches and whose guard holds runs
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    /// `|x, y: i32| x + y`, an expression body is a block holding just that expression.
+    /// Parameter and return types default to the type expected where the closure is used, then to `i32`.
+    Closure {
+        params: Vec<Param>,
+        return_type: Option<Type>,
+        body: Vec<Stmt>,
+    },
+    /// `(a, b)`, or `(a,)` for a single element
+    Tuple(Vec<Expr>),
+    /// `v.push(1)` or `a.len()`, a method of a builtin type
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    /// `println!("x = {}", x)` or `format!("{}", x)`, a builtin macro named without its `!`.
+    /// The first argument is the format string.
+    MacroCall(String, Vec<Expr>),
+}
+
+/// A piece of the format string of `println!` or `format!`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPiece {
+    Text(String),
+    /// `{}`, replaced by the next argument
+    Placeholder,
+}
+
+/// Splits a format string into its text and `{}` placeholders, `{{` and `}}` are literal braces
+pub fn parse_format_string(format: &str) -> Result<Vec<FormatPiece>, String> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                text.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(FormatPiece::Placeholder);
+            }
+            ('{', _) => return Err("only `{}` placeholders are supported, write `{{` for a literal `{`".to_string()),
+            ('}', _) => return Err("unmatched `}`, write `}}` for a literal `}`".to_string()),
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+    Ok(pieces)
+}
 
+impl Expr {
+    /// `Vec::new()`, which parses like a variant of an enum called `Vec`
+    pub fn is_vec_new(&self) -> bool {
+        matches!(self, Expr::EnumVariant { enum_name, variant, args } if enum_name == "Vec" && variant == "new" && args.is_empty())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     FunctionDeclaration {
         ident: String,
-        params: Vec<String>,
+        type_params: Vec<String>, // `fn max<T>`, instantiated for the types of each call's arguments
+        params: Vec<Param>,
+        return_type: Option<Type>, // `-> T`, unannotated functions return i32
         body: Vec<Stmt>,
+        public: bool, // `pub fn`, callable from outside its module
+        span: Span,
     },
+    /// `let` / `let mut` binding
     Assignment {
         ident: String,
+        mutable: bool,
+        ty: Option<Type>, // `let x: u8 = 3;`
+        expr: Expr,
+        span: Span,
+    },
+    /// `const LIMIT: i32 = 10;` or `static mut COUNTER: i32 = 0;`, a top-level item every function can use.
+    /// The value has to be known at compile time.
+    Global {
+        ident: String,
+        kind: GlobalKind,
+        ty: Type,
+        expr: Expr,
+        public: bool,
+        span: Span,
+    },
+    /// `let (q, r) = divmod(a, b);`, the pattern has to match any value and binds immutable variables
+    Destructure {
+        pattern: Pattern,
+        ty: Option<Type>,
+        expr: Expr,
+        span: Span,
+    },
+    /// `x = expr`, or a compound assignment like `x += expr` when `op` is set
+    Reassignment {
+        ident: String,
+        op: Option<BinaryOp>,
         expr: Expr,
+        span: Span,
     },
-    Expression(Expr),
-    Return(Expr),
+    /// `struct Point { x: i32, y: i32 }`
+    StructDeclaration {
+        ident: String,
+        fields: Vec<Field>,
+        public: bool,
+        span: Span,
+    },
+    /// `enum Shape { Circle(i32), Rect(i32, i32), Empty }`
+    EnumDeclaration {
+        ident: String,
+        variants: Vec<Variant>,
+        public: bool,
+        span: Span,
+    },
+    /// `mod shapes;`, whose items are in `shapes.ak` next to the declaring file, or `mod shapes { ... }`.
+    /// Modules are flattened away by `parsers::modules` before a program is checked or run.
+    Module {
+        ident: String,
+        body: Option<Vec<Stmt>>, // `None` until the module's file is loaded
+        public: bool,
+        span: Span,
+    },
+    /// `impl Point { ... }`, functions of a declared struct or enum. One whose first parameter is `self`
+    /// is a method called as `p.len()`, the others are called as `Point::new(1, 2)`.
+    /// `parsers::modules` turns them into functions named `Point::len` and `Point::new`.
+    Impl {
+        ty: String,
+        functions: Vec<Stmt>, // `Stmt::FunctionDeclaration`s
+        span: Span,
+    },
+    /// `use shapes::area;` makes the item at the end of the path available by its last name
+    Use {
+        path: Vec<String>,
+        public: bool, // `pub use` re-exports the item
+        span: Span,
+    },
+    /// `p.x = expr` or `p.a.b += expr` on a field of a `let mut` struct
+    FieldAssignment {
+        ident: String,
+        fields: Vec<String>, // path from the variable to the assigned field
+        op: Option<BinaryOp>,
+        expr: Expr,
+        span: Span,
+    },
+    /// `a[i] = expr` or `grid[i][j] += expr` on an element of a `let mut` array or `Vec`
+    IndexAssignment {
+        ident: String,
+        indices: Vec<Expr>, // one index per level of nesting
+        op: Option<BinaryOp>,
+        expr: Expr,
+        span: Span,
+    },
+    Expression(Expr, Span),
+    Return(Expr, Span),
+    /// `else if` chains nest as an `else_branch` holding a single `Stmt::If`
+    If {
+        condition: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+    },
+    While {
+        label: Option<String>, // `'outer: while ...`
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    /// `for ident in start..end`, `end` is exclusive and evaluated once
+    For {
+        label: Option<String>,
+        ident: String,
+        start: Expr,
+        end: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    Break(Option<String>, Span),    // optional loop label
+    Continue(Option<String>, Span), // optional loop label
 }
 
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::FunctionDeclaration { span, .. }
+            | Stmt::Assignment { span, .. }
+            | Stmt::Global { span, .. }
+            | Stmt::Destructure { span, .. }
+            | Stmt::Reassignment { span, .. }
+            | Stmt::StructDeclaration { span, .. }
+            | Stmt::EnumDeclaration { span, .. }
+            | Stmt::Module { span, .. }
+            | Stmt::Impl { span, .. }
+            | Stmt::Use { span, .. }
+            | Stmt::FieldAssignment { span, .. }
+            | Stmt::IndexAssignment { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::For { span, .. }
+            | Stmt::Expression(_, span)
+            | Stmt::Return(_, span)
+            | Stmt::Break(_, span)
+            | Stmt::Continue(_, span) => *span,
+        }
+    }
+}
+
+
+
+/// What a `Stmt::Global` declares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlobalKind {
+    Const,     // `const`, its value is used in place of its name
+    Static,    // `static`, a global variable that can't be assigned
+    StaticMut, // `static mut`
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub ident: String,
+    pub ty: Option<Type>, // unannotated parameters are i32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub ident: String,
+    pub ty: Type,
+}
+
+/// An enum variant and the types of its payload, empty for a variant like `Empty`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub ident: String,
+    pub fields: Vec<Type>,
+}
 
+/// `pattern if guard => body`, an expression body is a block holding just that expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Stmt>,
+}
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Wildcard,        // `_`
+    Binding(String), // matches anything and binds it to the name
+    Literal(Expr),   // `3`, `-1`, `'a'`, `true` or `"s"`
+    /// `Shape::Rect(w, _)`, with a pattern for every payload field
+    Variant {
+        enum_name: String,
+        variant: String,
+        fields: Vec<Pattern>,
+    },
+    Tuple(Vec<Pattern>), // `(a, _)`, a pattern for every element
+}
+
+/// A type written in an annotation
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Int { bits: u32, signed: bool }, // `i8`..`i128`, `u8`..`u64`
+    Float { bits: u32 },             // `f32`, `f64`
+    Bool,
+    Char,
+    Str,
+    Ptr(Box<Type>), // `*T`
+    Unit,           // `()`
+    Named(String),  // a declared struct or enum
+    /// `fn(i32, f64) -> i32`, a function or closure. Without `->` the result is `i32`, as for a declaration.
+    Function(Vec<Type>, Box<Type>),
+    Tuple(Vec<Type>), // `(i32, bool)`, or `(i32,)` for a single element
+    Array(Box<Type>, usize), // `[i32; 3]`, a fixed number of elements
+    Vec(Box<Type>),          // `Vec<i32>`, a growable array on the heap
+    Slice(Box<Type>),        // `&[i32]`, a pointer to some elements and their number
+}
+
+impl Type {
+    pub const I32: Type = Type::Int {
+        bits: 32,
+        signed: true,
+    };
+    pub const F64: Type = Type::Float { bits: 64 };
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Int { bits, signed: true } => write!(f, "i{}", bits),
+            Type::Int { bits, signed: false } => write!(f, "u{}", bits),
+            Type::Float { bits } => write!(f, "f{}", bits),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Str => write!(f, "str"),
+            Type::Ptr(ty) => write!(f, "*{}", ty),
+            Type::Unit => write!(f, "()"),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Function(params, return_type) => {
+                let params = params.iter().map(Type::to_string).collect::<Vec<_>>();
+                write!(f, "fn({}) -> {}", params.join(", "), return_type)
+            }
+            Type::Tuple(types) if types.len() == 1 => write!(f, "({},)", types[0]),
+            Type::Tuple(types) => {
+                let types = types.iter().map(Type::to_string).collect::<Vec<_>>();
+                write!(f, "({})", types.join(", "))
+            }
+            Type::Array(ty, len) => write!(f, "[{}; {}]", ty, len),
+            Type::Vec(ty) => write!(f, "Vec<{}>", ty),
+            Type::Slice(ty) => write!(f, "&[{}]", ty),
+        }
+    }
+}
+
+impl Type {
+    /// Replaces the type parameters bound in `bindings`, e.g. `T` in `fn(T) -> T`
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Named(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Ptr(ty) => Type::Ptr(Box::new(ty.substitute(bindings))),
+            Type::Function(params, return_type) => Type::Function(
+                params.iter().map(|param| param.substitute(bindings)).collect(),
+                Box::new(return_type.substitute(bindings)),
+            ),
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| ty.substitute(bindings)).collect()),
+            Type::Array(ty, len) => Type::Array(Box::new(ty.substitute(bindings)), *len),
+            Type::Vec(ty) => Type::Vec(Box::new(ty.substitute(bindings))),
+            Type::Slice(ty) => Type::Slice(Box::new(ty.substitute(bindings))),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl Param {
+    /// Replaces the type parameters bound in `bindings` in the parameter's annotation
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Param {
+        Param {
+            ident: self.ident.clone(),
+            ty: self.ty.as_ref().map(|ty| ty.substitute(bindings)),
+        }
+    }
+}
+
+/// Name of a generic function's instance for `type_args`, e.g. `max<u8>`
+pub fn instance_name(name: &str, type_args: &[Type]) -> String {
+    let type_args = type_args.iter().map(Type::to_string).collect::<Vec<_>>();
+    format!("{}<{}>", name, type_args.join(", "))
+}
+
+/// The `const` and `static` items of `stmts` ordered so that each comes after the `const`s its
+/// value refers to, the order they can be evaluated in. A cycle is cut where it was entered, the
+/// checker then reports a value that isn't known at compile time.
+pub fn globals_in_dependency_order(stmts: &[Stmt]) -> Vec<&Stmt> {
+    let globals = stmts.iter().filter(|stmt| matches!(stmt, Stmt::Global { .. })).collect::<Vec<_>>();
+    let mut visited = vec![false; globals.len()];
+    let mut ordered = Vec::with_capacity(globals.len());
+    for i in 0..globals.len() {
+        visit_global(i, &globals, &mut visited, &mut ordered);
+    }
+    ordered
+}
+
+fn visit_global<'a>(i: usize, globals: &[&'a Stmt], visited: &mut [bool], ordered: &mut Vec<&'a Stmt>) {
+    if std::mem::replace(&mut visited[i], true) {
+        return;
+    }
+    let Stmt::Global { expr, .. } = globals[i] else { unreachable!("only globals are ordered") };
+    let mut names = Vec::new();
+    constant_names(expr, &mut names);
+    for name in names {
+        let dependency = globals.iter().position(|global| {
+            matches!(global, Stmt::Global { ident, kind: GlobalKind::Const, .. } if ident == name)
+        });
+        if let Some(dependency) = dependency {
+            visit_global(dependency, globals, visited, ordered);
+        }
+    }
+    ordered.push(globals[i]);
+}
+
+/// Names in the parts of an expression that can be constant, see `TypeChecker::is_constant`
+fn constant_names<'a>(expr: &'a Expr, names: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Ident(name) => names.push(name),
+        Expr::Unary(_, operand) | Expr::Cast(operand, _) => constant_names(operand, names),
+        Expr::Infix(left, _, right) => {
+            constant_names(left, names);
+            constant_names(right, names);
+        }
+        _ => {}
+    }
+}
+
+/// Binds the type parameters in `param` to the matching parts of the argument type `arg`,
+/// the first argument to bind a type parameter decides it
+pub fn bind_type_params(
+    param: &Type,
+    arg: &Type,
+    type_params: &[String],
+    bindings: &mut HashMap<String, Type>,
+) {
+    match (param, arg) {
+        (Type::Named(name), _) if type_params.contains(name) => {
+            bindings.entry(name.clone()).or_insert_with(|| arg.clone());
+        }
+        (Type::Ptr(param), Type::Ptr(arg))
+        | (Type::Array(param, _), Type::Array(arg, _))
+        | (Type::Vec(param), Type::Vec(arg))
+        | (Type::Slice(param), Type::Slice(arg)) => bind_type_params(param, arg, type_params, bindings),
+        (Type::Function(params, param_return), Type::Function(args, arg_return))
+            if params.len() == args.len() =>
+        {
+            for (param, arg) in params.iter().zip(args) {
+                bind_type_params(param, arg, type_params, bindings);
+            }
+            bind_type_params(param_return, arg_return, type_params, bindings);
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the type parameters bound in `bindings` in every annotation of `stmts`,
+/// turning the body of a generic function into the body of one of its instances
+pub fn substitute_types(stmts: &mut [Stmt], bindings: &HashMap<String, Type>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::FunctionDeclaration { params, return_type, body, .. } => {
+                substitute_params(params, return_type, bindings);
+                substitute_types(body, bindings);
+            }
+            Stmt::Assignment { ty, expr, .. } | Stmt::Destructure { ty, expr, .. } => {
+                if let Some(ty) = ty {
+                    *ty = ty.substitute(bindings);
+                }
+                substitute_expr_types(expr, bindings);
+            }
+            Stmt::Global { ty, expr, .. } => {
+                *ty = ty.substitute(bindings);
+                substitute_expr_types(expr, bindings);
+            }
+            Stmt::StructDeclaration { fields, .. } => {
+                fields.iter_mut().for_each(|field| field.ty = field.ty.substitute(bindings))
+            }
+            Stmt::EnumDeclaration { variants, .. } => {
+                for ty in variants.iter_mut().flat_map(|variant| &mut variant.fields) {
+                    *ty = ty.substitute(bindings);
+                }
+            }
+            Stmt::Module { body, .. } => body.iter_mut().for_each(|body| substitute_types(body, bindings)),
+            Stmt::Impl { functions, .. } => substitute_types(functions, bindings),
+            Stmt::Reassignment { expr, .. }
+            | Stmt::FieldAssignment { expr, .. }
+            | Stmt::Expression(expr, _)
+            | Stmt::Return(expr, _) => substitute_expr_types(expr, bindings),
+            Stmt::IndexAssignment { indices, expr, .. } => {
+                indices.iter_mut().for_each(|index| substitute_expr_types(index, bindings));
+                substitute_expr_types(expr, bindings);
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                substitute_expr_types(condition, bindings);
+                substitute_types(then_branch, bindings);
+                else_branch.iter_mut().for_each(|branch| substitute_types(branch, bindings));
+            }
+            Stmt::While { condition, body, .. } => {
+                substitute_expr_types(condition, bindings);
+                substitute_types(body, bindings);
+            }
+            Stmt::For { start, end, body, .. } => {
+                substitute_expr_types(start, bindings);
+                substitute_expr_types(end, bindings);
+                substitute_types(body, bindings);
+            }
+            Stmt::Use { .. } | Stmt::Break(..) | Stmt::Continue(..) => {}
+        }
+    }
+}
+
+fn substitute_params(params: &mut [Param], return_type: &mut Option<Type>, bindings: &HashMap<String, Type>) {
+    for ty in params.iter_mut().filter_map(|param| param.ty.as_mut()).chain(return_type.as_mut()) {
+        *ty = ty.substitute(bindings);
+    }
+}
+
+fn substitute_expr_types(expr: &mut Expr, bindings: &HashMap<String, Type>) {
+    match expr {
+        Expr::Cast(operand, ty) => {
+            substitute_expr_types(operand, bindings);
+            *ty = ty.substitute(bindings);
+        }
+        Expr::Closure { params, return_type, body } => {
+            substitute_params(params, return_type, bindings);
+            substitute_types(body, bindings);
+        }
+        Expr::Call(_, args) | Expr::MacroCall(_, args) | Expr::Array(args) | Expr::Tuple(args) | Expr::EnumVariant { args, .. } => {
+            args.iter_mut().for_each(|arg| substitute_expr_types(arg, bindings))
+        }
+        Expr::StructLiteral(_, fields) => {
+            fields.iter_mut().for_each(|(_, expr)| substitute_expr_types(expr, bindings))
+        }
+        Expr::Infix(left, _, right) | Expr::ArrayIndexing(left, right) => {
+            substitute_expr_types(left, bindings);
+            substitute_expr_types(right, bindings);
+        }
+        Expr::Unary(_, operand) | Expr::FieldAccess(operand, _) => substitute_expr_types(operand, bindings),
+        Expr::Slice(array, start, end) => {
+            substitute_expr_types(array, bindings);
+            start.iter_mut().chain(end).for_each(|bound| substitute_expr_types(bound, bindings));
+        }
+        Expr::MethodCall { receiver, args, .. } => {
+            substitute_expr_types(receiver, bindings);
+            args.iter_mut().for_each(|arg| substitute_expr_types(arg, bindings));
+        }
+        Expr::If { condition, then_branch, else_branch } => {
+            substitute_expr_types(condition, bindings);
+            substitute_types(then_branch, bindings);
+            substitute_types(else_branch, bindings);
+        }
+        Expr::Match { scrutinee, arms } => {
+            substitute_expr_types(scrutinee, bindings);
+            for arm in arms {
+                arm.guard.iter_mut().for_each(|guard| substitute_expr_types(guard, bindings));
+                substitute_types(&mut arm.body, bindings);
+            }
+        }
+        Expr::Num(_)
+        | Expr::Float(_)
+        | Expr::Str(_)
+        | Expr::Ident(_)
+        | Expr::QualifiedIdent(_)
+        | Expr::Char(_)
+        | Expr::Bool(_) => {}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum VariableValue<'ctx> {
     Int(inkwell::values::IntValue<'ctx>),
+    Float(inkwell::values::FloatValue<'ctx>),
     Str(String),
     Ptr(inkwell::values::PointerValue<'ctx>),
+    Struct(inkwell::values::StructValue<'ctx>),
+    Array(inkwell::values::ArrayValue<'ctx>),
+    /// `let mut` binding living in an `alloca`'d stack slot
+    Mutable(
+        inkwell::values::PointerValue<'ctx>,
+        inkwell::types::BasicTypeEnum<'ctx>,
+    ),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -45,6 +641,56 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessOrEqual,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum UnaryOp {
+    Not,
+    Negate,
+}
+
+impl BinaryOp {
+    /// Binding power used by the precedence-climbing expression parser, higher binds tighter
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::LessThan
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterOrEqual => 3,
+            BinaryOp::Add | BinaryOp::Subtract => 4,
+            BinaryOp::Multiply | BinaryOp::Divide => 5,
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand when the left one doesn't decide the result
+    pub fn is_logical(&self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+
+    /// Comparison operators evaluate to a boolean rather than the type of their operands
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Equal
+                | BinaryOp::NotEqual
+                | BinaryOp::LessThan
+                | BinaryOp::GreaterThan
+                | BinaryOp::LessOrEqual
+                | BinaryOp::GreaterOrEqual
+        )
+    }
 }
 
 impl Into<BinaryOp> for &str {
@@ -54,6 +700,14 @@ impl Into<BinaryOp> for &str {
             "-" => BinaryOp::Subtract,
             "*" => BinaryOp::Multiply,
             "/" => BinaryOp::Divide,
+            "==" => BinaryOp::Equal,
+            "!=" => BinaryOp::NotEqual,
+            "<" => BinaryOp::LessThan,
+            ">" => BinaryOp::GreaterThan,
+            "<=" => BinaryOp::LessOrEqual,
+            ">=" => BinaryOp::GreaterOrEqual,
+            "&&" => BinaryOp::And,
+            "||" => BinaryOp::Or,
             _ => panic!("Unknown binary operator: {}", self),
         }
     }
@@ -66,6 +720,23 @@ impl std::fmt::Display for BinaryOp {
             BinaryOp::Subtract => write!(f, "-"),
             BinaryOp::Multiply => write!(f, "*"),
             BinaryOp::Divide => write!(f, "/"),
+            BinaryOp::Equal => write!(f, "=="),
+            BinaryOp::NotEqual => write!(f, "!="),
+            BinaryOp::LessThan => write!(f, "<"),
+            BinaryOp::GreaterThan => write!(f, ">"),
+            BinaryOp::LessOrEqual => write!(f, "<="),
+            BinaryOp::GreaterOrEqual => write!(f, ">="),
+            BinaryOp::And => write!(f, "&&"),
+            BinaryOp::Or => write!(f, "||"),
+        }
+    }
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnaryOp::Not => write!(f, "!"),
+            UnaryOp::Negate => write!(f, "-"),
         }
     }
 }
@@ -2,8 +2,7 @@
 use inkwell::context::Context;
 use inkwell::support::LLVMString;
 use crate::llvm::compiler::Compiler;
-
-use super::ast::Ast;
+use crate::parsers::modules::Program;
 
 #[no_mangle]
 pub extern "C" fn printd(x: i32) -> i32 {
@@ -11,9 +10,11 @@ pub extern "C" fn printd(x: i32) -> i32 {
     x
 }
 
-pub fn emit_from_statements(ast: Ast) -> anyhow::Result<LLVMString> {
+pub fn emit_from_program(program: Program, bounds_checks: bool) -> anyhow::Result<LLVMString> {
     let context = Context::create();
-    let mut compiler = Compiler::new(&context);
-    compiler.compile_ir(ast)?;
+    let mut compiler = Compiler::new(&context)
+        .with_sources(program.files)
+        .with_bounds_checks(bounds_checks);
+    compiler.compile_ir(program.ast)?;
     Ok(compiler.module.print_to_string())
 }
\ No newline at end of file
@@ -1,23 +1,234 @@
 use anyhow::anyhow;
 use inkwell::{
+    basic_block::BasicBlock,
     context::Context,
     execution_engine::ExecutionEngine,
-    values::{AnyValue, AnyValueEnum, BasicValue, BasicValueEnum},
-    AddressSpace, OptimizationLevel,
+    types::{
+        AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FloatType, FunctionType, IntType,
+        StructType,
+    },
+    values::{
+        AnyValueEnum, ArrayValue, BasicMetadataValueEnum, BasicValue, BasicValueEnum,
+        CallSiteValue, FloatValue, FunctionValue, IntValue, PointerValue, StructValue,
+    },
+    AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel,
 };
 
 use super::{
-    ast::{BinaryOp, Expr, Stmt, VariableValue},
+    ast::{
+        bind_type_params, globals_in_dependency_order, instance_name, parse_format_string,
+        substitute_types, BinaryOp, Expr, Field, FormatPiece, GlobalKind, MatchArm, Param, Pattern,
+        Span, Stmt, Type, UnaryOp, VariableValue, Variant,
+    },
     GLOBAL_ENTRY, USER_DEFINED_ENTRY,
 };
+use crate::{parsers::modules::SourceFile, types::integer::Integer};
+
+/// Integer predicate for a comparison operator on signed or unsigned operands
+fn int_predicate(op: &BinaryOp, signed: bool) -> IntPredicate {
+    match (op, signed) {
+        (BinaryOp::Equal, _) => IntPredicate::EQ,
+        (BinaryOp::NotEqual, _) => IntPredicate::NE,
+        (BinaryOp::LessThan, true) => IntPredicate::SLT,
+        (BinaryOp::GreaterThan, true) => IntPredicate::SGT,
+        (BinaryOp::LessOrEqual, true) => IntPredicate::SLE,
+        (BinaryOp::GreaterOrEqual, true) => IntPredicate::SGE,
+        (BinaryOp::LessThan, false) => IntPredicate::ULT,
+        (BinaryOp::GreaterThan, false) => IntPredicate::UGT,
+        (BinaryOp::LessOrEqual, false) => IntPredicate::ULE,
+        (BinaryOp::GreaterOrEqual, false) => IntPredicate::UGE,
+        _ => panic!("Not a comparison operator: {}", op),
+    }
+}
+
+fn is_unsigned_type(ty: Option<&Type>) -> bool {
+    matches!(ty, Some(Type::Int { signed: false, .. }))
+}
+
+/// Names a closure body refers to, the variables among them are captured when it's created
+fn referenced_names(stmts: &[Stmt], names: &mut std::collections::BTreeSet<String>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assignment { expr, .. }
+            | Stmt::Global { expr, .. }
+            | Stmt::Destructure { expr, .. }
+            | Stmt::Return(expr, _)
+            | Stmt::Expression(expr, _) => expr_names(expr, names),
+            Stmt::Reassignment { ident, expr, .. } | Stmt::FieldAssignment { ident, expr, .. } => {
+                names.insert(ident.clone());
+                expr_names(expr, names);
+            }
+            Stmt::IndexAssignment {
+                ident,
+                indices,
+                expr,
+                ..
+            } => {
+                names.insert(ident.clone());
+                indices.iter().for_each(|index| expr_names(index, names));
+                expr_names(expr, names);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                expr_names(condition, names);
+                referenced_names(then_branch, names);
+                referenced_names(else_branch.as_deref().unwrap_or_default(), names);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                expr_names(condition, names);
+                referenced_names(body, names);
+            }
+            Stmt::For {
+                start, end, body, ..
+            } => {
+                expr_names(start, names);
+                expr_names(end, names);
+                referenced_names(body, names);
+            }
+            Stmt::FunctionDeclaration { .. }
+            | Stmt::StructDeclaration { .. }
+            | Stmt::EnumDeclaration { .. }
+            | Stmt::Module { .. }
+            | Stmt::Impl { .. }
+            | Stmt::Use { .. }
+            | Stmt::Break(..)
+            | Stmt::Continue(..) => {}
+        }
+    }
+}
+
+fn expr_names(expr: &Expr, names: &mut std::collections::BTreeSet<String>) {
+    match expr {
+        Expr::Ident(name) => {
+            names.insert(name.clone());
+        }
+        Expr::Call(name, args) => {
+            names.insert(name.clone());
+            args.iter().for_each(|arg| expr_names(arg, names));
+        }
+        Expr::Infix(left, _, right) | Expr::ArrayIndexing(left, right) => {
+            expr_names(left, names);
+            expr_names(right, names);
+        }
+        Expr::Unary(_, operand) | Expr::Cast(operand, _) | Expr::FieldAccess(operand, _) => {
+            expr_names(operand, names)
+        }
+        Expr::Slice(array, start, end) => {
+            expr_names(array, names);
+            start
+                .iter()
+                .chain(end)
+                .for_each(|bound| expr_names(bound, names));
+        }
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            expr_names(condition, names);
+            referenced_names(then_branch, names);
+            referenced_names(else_branch, names);
+        }
+        Expr::Array(elements)
+        | Expr::Tuple(elements)
+        | Expr::MacroCall(_, elements)
+        | Expr::EnumVariant { args: elements, .. } => elements
+            .iter()
+            .for_each(|element| expr_names(element, names)),
+        Expr::StructLiteral(_, fields) => {
+            fields.iter().for_each(|(_, expr)| expr_names(expr, names))
+        }
+        Expr::Match { scrutinee, arms } => {
+            expr_names(scrutinee, names);
+            for arm in arms {
+                arm.guard.iter().for_each(|guard| expr_names(guard, names));
+                referenced_names(&arm.body, names);
+            }
+        }
+        Expr::Closure { body, .. } => referenced_names(body, names),
+        Expr::MethodCall { receiver, args, .. } => {
+            expr_names(receiver, names);
+            args.iter().for_each(|arg| expr_names(arg, names));
+        }
+        Expr::Num(_)
+        | Expr::Float(_)
+        | Expr::Str(_)
+        | Expr::Char(_)
+        | Expr::Bool(_)
+        | Expr::QualifiedIdent(_) => {}
+    }
+}
+
+/// LLVM layout of a declared enum, a tagged union
+#[derive(Clone)]
+struct EnumLayout<'ctx> {
+    /// `{ i32 tag, [n x i64] payload }`, with room in the payload for the largest variant
+    ty: StructType<'ctx>,
+    /// Variants in declaration order, the index is the tag, with the struct type their payload is stored as
+    variants: Vec<(Variant, StructType<'ctx>)>,
+}
+
+/// A name bound by a pattern, its value and its type when the declaration says it
+type PatternBinding<'ctx> = (String, BasicValueEnum<'ctx>, Option<Type>);
+
+/// Blocks of a `match` that is currently being compiled
+struct MatchContext<'ctx> {
+    /// Body of every arm, compiled the first time a tag block reaches it
+    body_blocks: Vec<Option<BasicBlock<'ctx>>>,
+    /// Values of the arm bodies that fall through to `merge_block`
+    incoming: Vec<(Option<AnyValueEnum<'ctx>>, BasicBlock<'ctx>)>,
+    merge_block: BasicBlock<'ctx>,
+}
+
+/// A generic function, compiled into one instance per set of type arguments it's called with
+#[derive(Clone)]
+struct GenericFunction {
+    type_params: Vec<String>,
+    params: Vec<Param>,
+    return_type: Option<Type>,
+    body: Vec<Stmt>,
+}
+
+/// Jump targets of a loop that is currently being compiled
+struct LoopContext<'ctx> {
+    label: Option<String>,
+    continue_block: BasicBlock<'ctx>,
+    break_block: BasicBlock<'ctx>,
+}
 
 pub struct Compiler<'ctx> {
     pub context: &'ctx Context,
     pub builder: inkwell::builder::Builder<'ctx>,
     pub module: inkwell::module::Module<'ctx>,
-    #[allow(dead_code)]
     execution_engine: ExecutionEngine<'ctx>,
     variables: std::collections::HashMap<String, VariableValue<'ctx>>,
+    /// The `const`s and `static`s bound by `declare_globals`, closures use them without capturing
+    globals: std::collections::HashMap<String, VariableValue<'ctx>>,
+    /// Language types of the variables in scope, LLVM integers don't record signedness
+    variable_types: std::collections::HashMap<String, Type>,
+    return_types: std::collections::HashMap<String, Type>,
+    param_types: std::collections::HashMap<String, Vec<Type>>,
+    /// Named LLVM struct types of the declared structs, with their fields in declaration order
+    struct_types: std::collections::HashMap<String, (StructType<'ctx>, Vec<Field>)>,
+    enum_types: std::collections::HashMap<String, EnumLayout<'ctx>>,
+    generic_functions: std::collections::HashMap<String, GenericFunction>,
+    loops: Vec<LoopContext<'ctx>>,
+    /// `{ data, len, cap }`, the header of every `Vec`. Its elements live on the heap behind `data`.
+    vec_type: StructType<'ctx>,
+    /// `{ data, len }`, a slice of the elements of an array, `Vec` or string
+    slice_type: StructType<'ctx>,
+    /// The source files of the program, indexed by `Span::file`, which panics point into
+    files: Vec<SourceFile>,
+    /// The statement being compiled, where a failed runtime check reports the program panicked
+    span: Option<Span>,
+    /// Whether indexing checks the index against the length, off for `--no-bounds-checks`
+    bounds_checks: bool,
 }
 
 impl<'ctx> Compiler<'ctx> {
@@ -28,15 +239,56 @@ impl<'ctx> Compiler<'ctx> {
             .create_jit_execution_engine(OptimizationLevel::None)
             .unwrap();
         let variables = std::collections::HashMap::new();
+        let vec_type = ctx.opaque_struct_type("Vec");
+        vec_type.set_body(
+            &[
+                ctx.i8_type().ptr_type(AddressSpace::default()).into(),
+                ctx.i64_type().into(),
+                ctx.i64_type().into(),
+            ],
+            false,
+        );
+        let slice_type = ctx.opaque_struct_type("Slice");
+        slice_type.set_body(
+            &[
+                ctx.i8_type().ptr_type(AddressSpace::default()).into(),
+                ctx.i64_type().into(),
+            ],
+            false,
+        );
         Compiler {
             context: &ctx,
             builder,
             module,
             execution_engine,
             variables,
+            globals: std::collections::HashMap::new(),
+            variable_types: std::collections::HashMap::new(),
+            return_types: std::collections::HashMap::new(),
+            param_types: std::collections::HashMap::new(),
+            struct_types: std::collections::HashMap::new(),
+            enum_types: std::collections::HashMap::new(),
+            generic_functions: std::collections::HashMap::new(),
+            loops: Vec::new(),
+            vec_type,
+            slice_type,
+            files: Vec::new(),
+            span: None,
+            bounds_checks: true,
         }
     }
 
+    /// The source files a panic reports its location in
+    pub fn with_sources(mut self, files: Vec<SourceFile>) -> Self {
+        self.files = files;
+        self
+    }
+
+    pub fn with_bounds_checks(mut self, bounds_checks: bool) -> Self {
+        self.bounds_checks = bounds_checks;
+        self
+    }
+
     /// Compile the AST to LLVM IR
     pub fn compile_ir(&mut self, ast: Vec<Stmt>) -> anyhow::Result<()> {
         // add the standard library to the compiler
@@ -52,10 +304,14 @@ impl<'ctx> Compiler<'ctx> {
 
     // implementations for some stdlib functions we're going to make available
     fn add_stdlib(&self) {
-        // TODO: lets make a safer wrapper around printf
         self.add_printf();
         self.add_print_string_fn();
         self.add_printd();
+        self.add_printf64();
+        self.add_strcmp();
+        self.add_realloc();
+        self.add_vec_grow();
+        self.add_panic();
     }
 
     fn add_print_string_fn(&self) {
@@ -107,6 +363,196 @@ impl<'ctx> Compiler<'ctx> {
         );
 
         self.module.add_function("printf", printf_type, None);
+
+        // `format!` measures and then fills its string with snprintf
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let snprintf_type = self.context.i32_type().fn_type(
+            &[
+                i8_ptr_type.into(),
+                self.context.i64_type().into(),
+                i8_ptr_type.into(),
+            ],
+            true,
+        );
+        self.module.add_function("snprintf", snprintf_type, None);
+    }
+
+    // used to match string patterns
+    fn add_strcmp(&self) {
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let strcmp_type = self
+            .context
+            .i32_type()
+            .fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
+        self.module.add_function("strcmp", strcmp_type, None);
+        // used to slice strings
+        let strlen_type = self
+            .context
+            .i64_type()
+            .fn_type(&[i8_ptr_type.into()], false);
+        self.module.add_function("strlen", strlen_type, None);
+    }
+
+    /// `realloc` backs the elements of every `Vec`
+    fn add_realloc(&self) {
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let realloc_type =
+            i8_ptr_type.fn_type(&[i8_ptr_type.into(), self.context.i64_type().into()], false);
+        self.module.add_function("realloc", realloc_type, None);
+    }
+
+    /// The routines a failed runtime check calls. They print where the program panicked and the
+    /// source line to stderr, then exit with code 101. Their `__akame_` names can't clash with
+    /// user functions:
+    /// `panic(location: *i8, message: *i8, line: *i8)` and
+    /// `panic_bounds(location: *i8, len: i64, index: i64, line: *i8)` for an index out of bounds and
+    /// `panic_slice(location: *i8, start: i64, end: i64, len: i64, line: *i8)` for a slice out of bounds
+    fn add_panic(&self) {
+        let i32_type = self.context.i32_type();
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let dprintf_type = i32_type.fn_type(&[i32_type.into(), i8_ptr_type.into()], true);
+        self.module.add_function("dprintf", dprintf_type, None);
+        let exit_type = self.context.void_type().fn_type(&[i32_type.into()], false);
+        self.module.add_function("exit", exit_type, None);
+
+        self.add_panic_routine(
+            "__akame_panic",
+            &[i8_ptr_type.into(), i8_ptr_type.into(), i8_ptr_type.into()],
+            "panicked at %s: %s\n%s\n",
+        );
+        let i64_type = self.context.i64_type();
+        self.add_panic_routine(
+            "__akame_panic_bounds",
+            &[
+                i8_ptr_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+                i8_ptr_type.into(),
+            ],
+            "panicked at %s: index out of bounds: the len is %llu but the index is %lld\n%s\n",
+        );
+        self.add_panic_routine(
+            "__akame_panic_slice",
+            &[
+                i8_ptr_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+                i8_ptr_type.into(),
+            ],
+            "panicked at %s: range %lld..%lld is out of bounds for length %llu\n%s\n",
+        );
+    }
+
+    /// Defines a routine that prints its parameters with `format` to stderr and exits with code 101
+    fn add_panic_routine(&self, name: &str, params: &[BasicMetadataTypeEnum<'ctx>], format: &str) {
+        let i32_type = self.context.i32_type();
+        let fn_type = self.context.void_type().fn_type(params, false);
+        let function = self.module.add_function(name, fn_type, None);
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        let format = self
+            .builder
+            .build_global_string_ptr(format, &format!("{}_format", name));
+        let mut args: Vec<BasicMetadataValueEnum> = vec![
+            i32_type.const_int(2, false).into(),
+            format.as_pointer_value().into(),
+        ];
+        args.extend(
+            function
+                .get_params()
+                .into_iter()
+                .map(BasicMetadataValueEnum::from),
+        );
+        let dprintf = self
+            .module
+            .get_function("dprintf")
+            .expect("dprintf function not found");
+        self.builder.build_call(dprintf, &args, "dprintf");
+        let exit = self
+            .module
+            .get_function("exit")
+            .expect("exit function not found");
+        self.builder
+            .build_call(exit, &[i32_type.const_int(101, false).into()], "exit");
+        self.builder.build_unreachable();
+    }
+
+    /// `vec_grow(v: *Vec, element_size: i64)` makes room for one more element in `v`,
+    /// doubling the capacity of a full `Vec` with `realloc`
+    fn add_vec_grow(&self) {
+        let i64_type = self.context.i64_type();
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let fn_type = self.context.void_type().fn_type(
+            &[
+                self.vec_type.ptr_type(AddressSpace::default()).into(),
+                i64_type.into(),
+            ],
+            false,
+        );
+        let function = self.module.add_function("__akame_vec_grow", fn_type, None);
+        let entry = self.context.append_basic_block(function, "entry");
+        let grow = self.context.append_basic_block(function, "grow");
+        let done = self.context.append_basic_block(function, "done");
+
+        self.builder.position_at_end(entry);
+        let vec = function.get_nth_param(0).unwrap().into_pointer_value();
+        let element_size = function.get_nth_param(1).unwrap().into_int_value();
+        let len = self
+            .builder
+            .build_load(i64_type, self.vec_field(vec, 1), "len")
+            .into_int_value();
+        let cap_slot = self.vec_field(vec, 2);
+        let cap = self
+            .builder
+            .build_load(i64_type, cap_slot, "cap")
+            .into_int_value();
+        let full = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, len, cap, "full");
+        self.builder.build_conditional_branch(full, grow, done);
+
+        self.builder.position_at_end(grow);
+        let unallocated = self.builder.build_int_compare(
+            IntPredicate::EQ,
+            cap,
+            i64_type.const_zero(),
+            "unallocated",
+        );
+        let doubled = self
+            .builder
+            .build_int_mul(cap, i64_type.const_int(2, false), "doubled");
+        let new_cap = self
+            .builder
+            .build_select(unallocated, i64_type.const_int(4, false), doubled, "newcap")
+            .into_int_value();
+        let bytes = self.builder.build_int_mul(new_cap, element_size, "bytes");
+        let data_slot = self.vec_field(vec, 0);
+        let data = self.builder.build_load(i8_ptr_type, data_slot, "data");
+        let realloc = self
+            .module
+            .get_function("realloc")
+            .expect("realloc function not found");
+        let new_data = self
+            .builder
+            .build_call(realloc, &[data.into(), bytes.into()], "newdata")
+            .try_as_basic_value()
+            .left()
+            .expect("realloc returns a pointer");
+        self.builder.build_store(data_slot, new_data);
+        self.builder.build_store(cap_slot, new_cap);
+        self.builder.build_unconditional_branch(done);
+
+        self.builder.position_at_end(done);
+        self.builder.build_return(None);
+    }
+
+    /// Pointer to the `data` (0), `len` (1) or `cap` (2) field of the `Vec` header at `vec`
+    fn vec_field(&self, vec: PointerValue<'ctx>, index: u32) -> PointerValue<'ctx> {
+        let name = ["data", "len", "cap"][index as usize];
+        self.builder
+            .build_struct_gep(self.vec_type, vec, index, name)
+            .expect("a Vec header has a data, len and cap field")
     }
 
     fn add_printd(&self) {
@@ -137,6 +583,32 @@ impl<'ctx> Compiler<'ctx> {
         self.builder.build_return(Some(&param));
     }
 
+    /// `printf64(x: f64) -> f64`, the float counterpart of `printd`
+    fn add_printf64(&self) {
+        let f64_type = self.context.f64_type();
+        let fn_type = f64_type.fn_type(&[f64_type.into()], false);
+        let printf64_fn = self.module.add_function("printf64", fn_type, None);
+
+        let basic_block = self.context.append_basic_block(printf64_fn, "entry");
+        self.builder.position_at_end(basic_block);
+        let param = printf64_fn.get_nth_param(0).unwrap().into_float_value();
+
+        let format_str = self
+            .builder
+            .build_global_string_ptr("%f\n\00", "format_str_f");
+        self.builder.build_call(
+            self.module.get_function("printf").unwrap(),
+            &[
+                inkwell::values::BasicMetadataValueEnum::PointerValue(
+                    format_str.as_pointer_value(),
+                ),
+                param.into(),
+            ],
+            "calltmp",
+        );
+        self.builder.build_return(Some(&param));
+    }
+
     fn add_string_format_global(&self) -> inkwell::values::GlobalValue<'ctx> {
         let str_format = self.builder.build_global_string_ptr("%s\n", "str_format");
         str_format
@@ -216,10 +688,12 @@ impl<'ctx> Compiler<'ctx> {
         }
     }
 
-    fn compile_expr(&self, expr: &Expr) -> AnyValueEnum<'ctx> {
-        match expr {
+    fn compile_expr(&mut self, expr: &Expr) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        Ok(match expr {
             Expr::Str(s) => {
-                let string_val = self.context.const_string(s.as_bytes(), false);
+                // `s` already holds the decoded escapes; terminate it for the C functions it's passed to
+                let bytes = [s.as_bytes(), b"\0"].concat();
+                let string_val = self.context.const_string(&bytes, false);
                 let global_str = self.module.add_global(
                     string_val.get_type(),
                     Some(AddressSpace::default()),
@@ -228,108 +702,89 @@ impl<'ctx> Compiler<'ctx> {
                 global_str.set_initializer(&string_val);
                 global_str.as_pointer_value().into()
             }
-            Expr::Array(array) => {
-                // Here, we're assuming all elements of your array are of the same type.
-                // We'll compile the first expression to get its type,
-                // then use it to create the LLVM array type.
-                let element = self.compile_expr(&array[0]);
-                let array_type = element
-                    .get_type()
-                    .into_array_type()
-                    .array_type(array.len() as u32);
-
-                //let array_vals: Vec<_> = array.iter().map(|e| self.compile_expr(e)).collect();
-
-                let array_vals: Vec<_> = array
-                    .iter()
-                    .map(|e| self.compile_expr(e).into_array_value())
-                    .collect();
-
-                // Create a constant array with the given values.
-                // let const_array = array_type.const_array(&array_values);
-
-                // Create a global instance of the array and set its initializer.
-                let global_array = self.module.add_global(
-                    array_type,
-                    Some(AddressSpace::default()),
-                    "global_array",
-                );
-                global_array.set_initializer(&array_vals[0]);
-
-                // Return the global array's pointer.
-                global_array.as_pointer_value().into()
-            }
             Expr::ArrayIndexing(array, index) => {
-                let array_val_pointer = self.compile_expr(array).into_pointer_value();
-                let index_val = self.compile_expr(index).into_int_value();
-
-                // You might have a function like this:
-                let element_type = self.context.i32_type().ptr_type(AddressSpace::default()); // This function returns the BasicType of the element.
-
-                let gep = unsafe {
-                    self.builder.build_gep(
-                        element_type,
-                        array_val_pointer,
-                        &[index_val],
-                        "array_indexing",
-                    )
-                };
+                let (element, element_type) = self.element_pointer(array, index)?;
                 self.builder
-                    .build_load(element_type, gep, "array_indexing_load")
+                    .build_load(element_type, element, "array_indexing_load")
                     .into()
             }
+            Expr::Slice(array, start, end) => self
+                .compile_slice(array, start.as_deref(), end.as_deref())?
+                .into(),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.compile_method_call(receiver, method, args)?,
+            Expr::MacroCall(name, args) => self.compile_macro_call(name, args)?,
+            Expr::StructLiteral(name, fields) => self.compile_struct_literal(name, fields)?.into(),
+            Expr::FieldAccess(operand, field) => self.compile_field_access(operand, field)?.into(),
+            // the element type of an empty `Vec` doesn't matter until something is pushed
+            Expr::EnumVariant { .. } if expr.is_vec_new() => self.vec_type.const_zero().into(),
+            Expr::EnumVariant {
+                enum_name,
+                variant,
+                args,
+            } => self.compile_enum_variant(enum_name, variant, args)?.into(),
+            Expr::Closure { .. } | Expr::Tuple(_) | Expr::Array(_) => {
+                self.compile_expr_expecting(expr, None)?
+            }
+            Expr::Match { scrutinee, arms } => match self.compile_match(scrutinee, arms)? {
+                Some(value) => value,
+                // the arms have no common value, e.g. a `match` used as a statement
+                None => AnyValueEnum::PointerValue(
+                    self.context
+                        .i32_type()
+                        .ptr_type(AddressSpace::default())
+                        .const_zero(),
+                ),
+            },
             Expr::QualifiedIdent(idents) => {
                 // Assuming idents is a Vec<String> or similar
                 if let Some(first_ident) = idents.first() {
                     match first_ident.as_str() {
-                        "std" => self
-                            .stdlib_call(idents)
-                            .expect("Unable to link to stdlib")
-                            .into(),
-                        _ => panic!("Unknown qualified identifier: {}", first_ident),
+                        "std" => self.stdlib_call(idents)?.into(),
+                        _ => return Err(anyhow!("Unknown qualified identifier: {}", first_ident)),
                     }
                 } else {
-                    panic!("Empty qualified identifier");
+                    return Err(anyhow!("Empty qualified identifier"));
                 }
             }
-            Expr::Num(n) => self.context.i32_type().const_int(*n as u64, false).into(),
+            Expr::Num(n) => self.compile_int_literal(n).into(),
+            Expr::Float(n) => self.context.f64_type().const_float(*n).into(),
+            // a variable holding a closure shadows a function of the same name
+            Expr::Call(func_name, args) if self.variables.contains_key(func_name) => {
+                self.compile_closure_call(func_name, args)?
+            }
+            Expr::Call(func_name, args) if self.generic_functions.contains_key(func_name) => {
+                self.compile_generic_call(func_name, args)?
+            }
             Expr::Call(func_name, arg) => {
-                let function = match self.module.get_function(func_name) {
-                    Some(func) => func,
-                    None => {
-                        panic!("Undefined function call: {func_name}");
-                    }
-                };
-                let args = arg
+                let expected_types = self.param_types.get(func_name).cloned().unwrap_or_default();
+                let function = self
+                    .module
+                    .get_function(func_name)
+                    .ok_or_else(|| anyhow!("Undefined function: `{}`", func_name))?;
+                let values = arg
                     .iter()
-                    .map(|arg| {
-                        self.compile_expr(arg)
-                            .try_into()
-                            .expect("Unable to try into basic BasicMetadataValueEnum for call")
-                    })
-                    .collect::<Vec<inkwell::values::BasicMetadataValueEnum>>();
+                    .enumerate()
+                    .map(|(i, arg)| self.compile_expr_expecting(arg, expected_types.get(i)))
+                    .collect::<anyhow::Result<_>>()?;
+                let args = self.call_args(function, arg, values);
                 let result = self
                     .builder
                     .build_call(function, args.as_slice(), "calltmp");
-                //let ret_type = function.get_type()
-                //   .get_return_type().expect("No return type found");
-                match result.try_as_basic_value().left() {
-                    Some(value) => value.into(),
-                    None => {
-                        // it's a void return so we shouldn't return anything
-
-                        AnyValueEnum::PointerValue(
-                            self.context
-                                .i32_type()
-                                .ptr_type(AddressSpace::default())
-                                .const_zero(),
-                        )
-                    }
-                }
+                self.call_result(result)
             }
             Expr::Ident(var_name) => match self.variables.get(var_name) {
                 Some(VariableValue::Int(value)) => AnyValueEnum::IntValue(*value),
+                Some(VariableValue::Float(value)) => AnyValueEnum::FloatValue(*value),
                 Some(VariableValue::Ptr(value)) => AnyValueEnum::PointerValue(*value),
+                Some(VariableValue::Struct(value)) => AnyValueEnum::StructValue(*value),
+                Some(VariableValue::Array(value)) => AnyValueEnum::ArrayValue(*value),
+                Some(VariableValue::Mutable(ptr, ty)) => {
+                    self.builder.build_load(*ty, *ptr, var_name).into()
+                }
                 Some(VariableValue::Str(referenced_var)) => {
                     match self.variables.get(referenced_var) {
                         Some(VariableValue::Int(referenced_value)) => {
@@ -357,9 +812,18 @@ impl<'ctx> Compiler<'ctx> {
                                 )
                             }
                         }
-                        _ => panic!("Dereferencing non-int variable or undefined variable"),
+                        _ => {
+                            return Err(anyhow!(
+                                "Dereferencing non-int variable or undefined variable"
+                            ))
+                        }
                     }
                 }
+                // a declared function used as a value
+                None if self.module.get_function(var_name).is_some() => {
+                    let function = self.module.get_function(var_name).unwrap();
+                    self.function_closure(function).into()
+                }
                 None => {
                     /*
                        TODO: if there's undefined function call within another function, it will try to look it up from the symbol table. Which results in this being a pretty inaccurate error being thrown.
@@ -373,178 +837,3455 @@ impl<'ctx> Compiler<'ctx> {
 
                     */
 
-                    panic!("Variable: {var_name} not found in symbol table. ");
+                    return Err(anyhow!("Variable: {var_name} not found in symbol table. "));
                 }
             },
+            // a `char` is its code point, like in the interpreter
             Expr::Char(c) => {
-                let char_val = self.context.i8_type().const_int(*c as u64, false);
+                let char_val = self.context.i32_type().const_int(*c as u64, false);
                 char_val.into()
             }
-            Expr::Infix(left, op, right) => {
-                let left_value = self.compile_expr(left).into_int_value();
-                let right_value = self.compile_expr(right).into_int_value();
-                match op {
-                    BinaryOp::Add => self
-                        .builder
-                        .build_int_add(left_value, right_value, "addtmp")
-                        .into(),
-                    BinaryOp::Subtract => self
-                        .builder
-                        .build_int_sub(left_value, right_value, "subtmp")
-                        .into(),
-                    BinaryOp::Multiply => self
-                        .builder
-                        .build_int_mul(left_value, right_value, "multmp")
-                        .into(),
-                    BinaryOp::Divide => self
-                        .builder
-                        .build_int_signed_div(left_value, right_value, "divtmp")
-                        .into(),
+            Expr::Bool(b) => self.context.bool_type().const_int(*b as u64, false).into(),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match self.compile_if(condition, then_branch, Some(else_branch))? {
+                Some(value) => value,
+                None => {
+                    return Err(anyhow!(
+                        "`if` expression branches must produce values of the same type: {:?}",
+                        expr
+                    ))
                 }
+            },
+            Expr::Infix(left, op, right) if op.is_logical() => {
+                self.compile_logical(op, left, right)?.into()
             }
-        }
-    }
-
-    pub fn compile(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
-        for stmt in stmts {
-            match stmt {
-                Stmt::Return(expr) => {
-                    let value = self.compile_expr(expr);
-                    if value.get_type().is_void_type() {
-                        self.builder.build_return(None);
-                    } else {
-                        match value {
-                            AnyValueEnum::IntValue(i) => {
-                                self.builder.build_return(Some(&i));
-                            }
-                            AnyValueEnum::PointerValue(p) => {
-                                self.builder.build_return(Some(&p));
+            Expr::Unary(UnaryOp::Negate, expr) => match self.compile_expr(expr)? {
+                AnyValueEnum::IntValue(value) if value.get_type().get_bit_width() != 1 => {
+                    self.builder.build_int_neg(value, "negtmp").into()
+                }
+                AnyValueEnum::FloatValue(value) => {
+                    self.builder.build_float_neg(value, "fnegtmp").into()
+                }
+                value => return Err(anyhow!("Cannot negate: {:?}", value)),
+            },
+            Expr::Unary(UnaryOp::Not, expr) => {
+                let value = self.compile_expr(expr)?;
+                let value = self
+                    .build_condition(value)
+                    .map_err(|err| anyhow!("Cannot apply `!`: {}", err))?;
+                self.builder.build_not(value, "nottmp").into()
+            }
+            Expr::Infix(left, op, right) => {
+                let signed = !self.is_unsigned(left) && !self.is_unsigned(right);
+                let left_value = self.compile_expr(left)?;
+                let right_value = self.compile_expr(right)?;
+                match (left_value, right_value) {
+                    (AnyValueEnum::IntValue(left_value), AnyValueEnum::IntValue(right_value)) => {
+                        let (left_value, right_value) = match (&**left, &**right) {
+                            (Expr::Num(_), Expr::Num(_)) => {
+                                self.unify_int_widths(left_value, right_value, signed)
                             }
-                            _ => panic!("Unknown return type: {:#?}", value),
-                        }
-                    };
+                            // an unsuffixed literal takes the type of the other operand
+                            (Expr::Num(_), _) => (
+                                self.coerce_int(left_value, right_value.get_type(), true),
+                                right_value,
+                            ),
+                            (_, Expr::Num(_)) => (
+                                left_value,
+                                self.coerce_int(right_value, left_value.get_type(), true),
+                            ),
+                            // operands of nested expressions can come back at different widths (e.g. a char and a call result)
+                            _ => self.unify_int_widths(left_value, right_value, signed),
+                        };
+                        self.build_int_binary_op(op, left_value, right_value, signed)
+                            .into()
+                    }
+                    (
+                        AnyValueEnum::FloatValue(left_value),
+                        AnyValueEnum::FloatValue(right_value),
+                    ) => {
+                        // an `f64` literal becomes an `f32` next to one
+                        let (left_value, right_value) = match &**left {
+                            Expr::Float(_) => (
+                                self.coerce_float(left_value, right_value.get_type()),
+                                right_value,
+                            ),
+                            _ => (
+                                left_value,
+                                self.coerce_float(right_value, left_value.get_type()),
+                            ),
+                        };
+                        self.build_float_binary_op(op, left_value, right_value)
+                    }
+                    (left_value, right_value) => {
+                        return Err(anyhow!(
+                            "Mismatched operands for `{}`: {:?} and {:?}",
+                            op,
+                            left_value,
+                            right_value
+                        ))
+                    }
                 }
-                Stmt::FunctionDeclaration {
-                    ident: name,
-                    params,
-                    body,
-                } => {
-                    let param_types: Vec<inkwell::types::BasicMetadataTypeEnum<'_>> =
-                        vec![self.context.i32_type().into(); params.len()];
-                    let fn_type = self.context.i32_type().fn_type(&param_types, false);
-                    let function = self.module.add_function(name, fn_type, None);
-                    let entry = self.context.append_basic_block(function, "entry");
-                    self.builder.position_at_end(entry);
-
-                    for (i, param) in params.iter().enumerate() {
-                        let value = function.get_nth_param(i as u32).unwrap().into_int_value();
-                        self.variables
-                            .insert(param.clone(), VariableValue::Int(value));
+            }
+            Expr::Cast(operand, ty) => {
+                let signed = !self.is_unsigned(operand);
+                let target_signed = !is_unsigned_type(Some(ty));
+                match (self.compile_expr(operand)?, self.llvm_type(ty)) {
+                    (AnyValueEnum::IntValue(value), Some(BasicTypeEnum::IntType(int_type))) => {
+                        self.coerce_int(value, int_type, signed).into()
                     }
-
-                    let mut _last_value = None;
-
-                    for (i, stmt) in body.iter().enumerate() {
-                        let is_last_expr = i == body.len() - 1;
-
-                        match stmt {
-                            Stmt::Assignment {
-                                ident: var_name,
-                                expr,
-                            } => {
-                                let value = self.compile_expr(expr);
-                                match value {
-                                    AnyValueEnum::IntValue(int_val) => {
-                                        self.variables
-                                            .insert(var_name.clone(), VariableValue::Int(int_val));
-                                    }
-                                    AnyValueEnum::PointerValue(ptr_val) => {
-                                        // If you want to refine further, you might check the type of the pointer
-                                        // but for now, we'll assume any pointer is a string
-                                        self.variables
-                                            .insert(var_name.clone(), VariableValue::Ptr(ptr_val));
-                                    }
-                                    // Add other types as necessary
-                                    _ => {
-                                        return Err(anyhow!(
-                                            "Unsupported assignment type for variable {}",
-                                            var_name
-                                        ))
-                                    }
-                                }
-                            }
-                            Stmt::Return(expr) => {
-                                let value = self.compile_expr(expr);
-                                if value.get_type().is_void_type() {
-                                    self.builder.build_return(None);
-                                } else {
-                                    match value {
-                                        AnyValueEnum::IntValue(i) => {
-                                            self.builder.build_return(Some(&i));
-                                        }
-                                        AnyValueEnum::PointerValue(p) => {
-                                            self.builder.build_return(Some(&p));
-                                        }
-                                        _ => {
-                                            return Err(anyhow!(
-                                                "Unknown return type: {:#?} | context: {:?}",
-                                                value,
-                                                expr
-                                            ))
-                                        }
-                                    }
-                                };
-                            }
-                            x => {
-                                if let Stmt::Expression(expr) = x {
-                                    let value = self.compile_expr(expr);
-                                    if is_last_expr {
-                                        _last_value = Some(value.as_any_value_enum());
-                                    }
-                                }
-                            }
+                    (AnyValueEnum::IntValue(value), Some(BasicTypeEnum::FloatType(float_type))) => {
+                        if signed && value.get_type().get_bit_width() != 1 {
+                            self.builder
+                                .build_signed_int_to_float(value, float_type, "sitofp")
+                                .into()
+                        } else {
+                            self.builder
+                                .build_unsigned_int_to_float(value, float_type, "uitofp")
+                                .into()
                         }
                     }
-
-                    // check if there's a return value already, if not, return 0
-                    if body.iter().any(|stmt| {
-                        if let Stmt::Return(_) = stmt {
-                            true
+                    (AnyValueEnum::FloatValue(value), Some(BasicTypeEnum::IntType(int_type))) => {
+                        if target_signed {
+                            self.builder
+                                .build_float_to_signed_int(value, int_type, "fptosi")
+                                .into()
                         } else {
-                            false
+                            self.builder
+                                .build_float_to_unsigned_int(value, int_type, "fptoui")
+                                .into()
                         }
-                    }) {
-                    } else {
-                        // Alternatively, you can have a default return value or handle this case differently
-                        self.builder
-                            .build_return(Some(&self.context.i32_type().const_int(0, false)));
                     }
+                    (
+                        AnyValueEnum::FloatValue(value),
+                        Some(BasicTypeEnum::FloatType(float_type)),
+                    ) => self.coerce_float(value, float_type).into(),
+                    (value, _) => return Err(anyhow!("Cannot cast {:?} as {}", value, ty)),
                 }
-                Stmt::Assignment {
-                    ident: var_name,
-                    expr,
-                } => {
-                    match expr {
-                        Expr::Ident(string_value) => {
-                            // If it's a Var expression, assume it's a string value
-                            self.variables
-                                .insert(var_name.clone(), VariableValue::Str(string_value.clone()));
-                        }
-                        _ => {
-                            let value = self.compile_expr(expr);
-                            self.variables.insert(
-                                var_name.clone(),
-                                VariableValue::Int(value.into_int_value()),
-                            );
-                        }
+            }
+        })
+    }
+
+    /// Like `compile_expr`, but a closure without annotations takes its parameter and return types
+    /// from the function type `expected` where it's used, and the elements of a tuple or array literal
+    /// are converted to the element types of `expected`
+    fn compile_expr_expecting(
+        &mut self,
+        expr: &Expr,
+        expected: Option<&Type>,
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        Ok(match expr {
+            Expr::Closure {
+                params,
+                return_type,
+                body,
+            } => self
+                .compile_closure(params, return_type.as_ref(), body, expected)?
+                .into(),
+            Expr::Tuple(elements) => self.compile_tuple(elements, expected)?.into(),
+            Expr::Array(elements) => self.compile_array(elements, expected)?.into(),
+            _ => self.compile_expr(expr)?,
+        })
+    }
+
+    /// Converts the compiled arguments of a call to the parameter types of `function`
+    fn call_args(
+        &self,
+        function: FunctionValue<'ctx>,
+        args: &[Expr],
+        values: Vec<AnyValueEnum<'ctx>>,
+    ) -> Vec<BasicMetadataValueEnum<'ctx>> {
+        let param_types = function.get_type().get_param_types();
+        args.iter()
+            .zip(values)
+            .enumerate()
+            .map(|(i, (arg, value))| {
+                // widen booleans and chars passed to integer parameters, e.g. `printd(a < b)`
+                match (value, param_types.get(i)) {
+                    (AnyValueEnum::IntValue(int_val), Some(BasicTypeEnum::IntType(ty))) => {
+                        let signed = !self.is_unsigned(arg);
+                        self.coerce_int(int_val, *ty, signed).into()
                     }
+                    (AnyValueEnum::FloatValue(float_val), Some(BasicTypeEnum::FloatType(ty))) => {
+                        self.coerce_float(float_val, *ty).into()
+                    }
+                    // C variadic arguments pass floats as doubles, e.g. `printf("%f", x)` with an f32 x
+                    (AnyValueEnum::FloatValue(float_val), None) => {
+                        self.coerce_float(float_val, self.context.f64_type()).into()
+                    }
+                    _ => value
+                        .try_into()
+                        .expect("Unable to try into basic BasicMetadataValueEnum for call"),
                 }
-                Stmt::Expression(expr) => {
-                    self.compile_expr(expr);
-                }
+            })
+            .collect()
+    }
+
+    /// The value a call evaluates to, a function without a return value gives a null placeholder
+    fn call_result(&self, result: CallSiteValue<'ctx>) -> AnyValueEnum<'ctx> {
+        match result.try_as_basic_value().left() {
+            Some(value) => value.into(),
+            None => self.unit_value(),
+        }
+    }
+
+    /// The null placeholder an expression of type `()` evaluates to
+    fn unit_value(&self) -> AnyValueEnum<'ctx> {
+        AnyValueEnum::PointerValue(
+            self.context
+                .i32_type()
+                .ptr_type(AddressSpace::default())
+                .const_zero(),
+        )
+    }
+
+    /// Calls the instance of a generic function for the types of `args`. Unsuffixed literals
+    /// and closures are compiled after the other arguments and take the types those decide,
+    /// so `max(x, 1)` calls `max<u8>` when `x` is a `u8`.
+    fn compile_generic_call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        let generic = self.generic_functions[name].clone();
+        if generic.params.len() != args.len() {
+            return Err(anyhow!(
+                "`{}` takes {} argument(s) but {} were given",
+                name,
+                generic.params.len(),
+                args.len()
+            ));
+        }
+        let param_types = generic
+            .params
+            .iter()
+            .map(|param| param.ty.clone().unwrap_or(Type::I32))
+            .collect::<Vec<_>>();
+        let (late, early): (Vec<_>, Vec<_>) = (0..args.len()).partition(|&i| {
+            matches!(
+                args[i],
+                Expr::Num(_) | Expr::Float(_) | Expr::Closure { .. }
+            )
+        });
+        let mut bindings = std::collections::HashMap::new();
+        let mut values = vec![None; args.len()];
+        for i in early.into_iter().chain(late) {
+            let expected = param_types[i].substitute(&bindings);
+            let value = self.compile_expr_expecting(&args[i], Some(&expected))?;
+            if let Some(ty) = self.value_type(value, &args[i]) {
+                bind_type_params(&param_types[i], &ty, &generic.type_params, &mut bindings);
             }
+            values[i] = Some(value);
+        }
+        let type_args = generic
+            .type_params
+            .iter()
+            .map(|type_param| {
+                bindings.get(type_param).cloned().ok_or_else(|| {
+                    anyhow!("Cannot infer type parameter `{}` of `{}`", type_param, name)
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let function = self.instantiate(name, &type_args, &bindings)?;
+        let values = values.into_iter().flatten().collect();
+        let args = self.call_args(function, args, values);
+        let result = self
+            .builder
+            .build_call(function, args.as_slice(), "calltmp");
+        Ok(self.call_result(result))
+    }
+
+    /// The instance of a generic function for `type_args`, named like `max<i32>`. It's compiled
+    /// the first time it's called, later calls find it in the module.
+    fn instantiate(
+        &mut self,
+        name: &str,
+        type_args: &[Type],
+        bindings: &std::collections::HashMap<String, Type>,
+    ) -> anyhow::Result<FunctionValue<'ctx>> {
+        let instance = instance_name(name, type_args);
+        if let Some(function) = self.module.get_function(&instance) {
+            return Ok(function);
+        }
+        let generic = &self.generic_functions[name];
+        let params = generic
+            .params
+            .iter()
+            .map(|param| param.substitute(bindings))
+            .collect::<Vec<_>>();
+        let return_type = generic
+            .return_type
+            .as_ref()
+            .map(|ty| ty.substitute(bindings));
+        let mut body = generic.body.clone();
+        substitute_types(&mut body, bindings);
+
+        let outer_block = self.builder.get_insert_block();
+        let outer_variables = self.variables.clone();
+        let outer_types = self.variable_types.clone();
+        let outer_loops = std::mem::take(&mut self.loops);
+        let compiled = self.compile_function(&instance, &params, return_type.as_ref(), &body);
+        self.variables = outer_variables;
+        self.variable_types = outer_types;
+        self.loops = outer_loops;
+        if let Some(block) = outer_block {
+            self.builder.position_at_end(block);
+        }
+        compiled?;
+        Ok(self
+            .module
+            .get_function(&instance)
+            .expect("the instance was just compiled"))
+    }
+
+    /// The language type of a compiled value, which decides the type arguments of a generic call
+    fn value_type(&self, value: AnyValueEnum<'ctx>, expr: &Expr) -> Option<Type> {
+        if let Expr::Ident(name) = expr {
+            if let Some(ty) = self.variable_types.get(name) {
+                return Some(ty.clone());
+            }
+        }
+        match value {
+            AnyValueEnum::IntValue(value) => match (value.get_type().get_bit_width(), expr) {
+                (1, _) => Some(Type::Bool),
+                (32, Expr::Char(_)) => Some(Type::Char),
+                (bits, _) => Some(Type::Int {
+                    bits,
+                    signed: !self.is_unsigned(expr),
+                }),
+            },
+            AnyValueEnum::FloatValue(value) => Some(Type::Float {
+                bits: if value.get_type() == self.context.f32_type() {
+                    32
+                } else {
+                    64
+                },
+            }),
+            AnyValueEnum::StructValue(value) => self
+                .struct_name(value.get_type())
+                .or_else(|| self.enum_name(value.get_type()))
+                .map(|name| Type::Named(name.to_owned()))
+                .or_else(|| self.function_type_of(expr))
+                .or_else(|| self.static_type(expr)),
+            AnyValueEnum::ArrayValue(_) => self.static_type(expr),
+            AnyValueEnum::PointerValue(_) => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    /// Literals get the LLVM integer of the width they were classified as
+    fn compile_int_literal(&self, n: &Integer) -> IntValue<'ctx> {
+        let value = n.to_i128();
+        match n.width() {
+            (128, _) => self
+                .context
+                .i128_type()
+                .const_int_arbitrary_precision(&[value as u64, (value >> 64) as u64]),
+            (bits, signed) => self
+                .context
+                .custom_width_int_type(bits)
+                .const_int(value as u64, signed),
+        }
+    }
+
+    /// Whether an integer expression has an unsigned type, which decides how it is
+    /// divided, compared and extended
+    fn is_unsigned(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Ident(name) => is_unsigned_type(self.variable_types.get(name)),
+            Expr::Call(name, args) if self.generic_functions.contains_key(name) => {
+                self.is_unsigned_generic_call(name, args)
+            }
+            Expr::Call(name, _) => match self.variable_types.get(name) {
+                Some(Type::Function(_, return_type)) if self.variables.contains_key(name) => {
+                    is_unsigned_type(Some(return_type))
+                }
+                _ => is_unsigned_type(self.return_types.get(name)),
+            },
+            Expr::Cast(_, ty) => is_unsigned_type(Some(ty)),
+            Expr::FieldAccess(..) | Expr::ArrayIndexing(..) | Expr::MethodCall { .. } => {
+                is_unsigned_type(self.static_type(expr).as_ref())
+            }
+            Expr::Infix(left, op, right) if !op.is_comparison() && !op.is_logical() => {
+                self.is_unsigned(left) || self.is_unsigned(right)
+            }
+            Expr::Unary(UnaryOp::Negate, operand) => self.is_unsigned(operand),
+            Expr::If { then_branch, .. } => match then_branch.last() {
+                Some(Stmt::Expression(expr, _)) => self.is_unsigned(expr),
+                _ => false,
+            },
+            Expr::Match { arms, .. } => match arms.first().and_then(|arm| arm.body.last()) {
+                Some(Stmt::Expression(expr, _)) => self.is_unsigned(expr),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a generic call returns an unsigned integer. A result of type `T` has the type of
+    /// the arguments passed for `T`, the literals among them only decide it when they're alone.
+    fn is_unsigned_generic_call(&self, name: &str, args: &[Expr]) -> bool {
+        let generic = &self.generic_functions[name];
+        match &generic.return_type {
+            Some(Type::Named(type_param)) if generic.type_params.contains(type_param) => generic
+                .params
+                .iter()
+                .zip(args)
+                .filter(|(param, arg)| {
+                    param.ty.as_ref() == Some(&Type::Named(type_param.clone()))
+                        && !matches!(arg, Expr::Num(_))
+                })
+                .any(|(_, arg)| self.is_unsigned(arg)),
+            return_type => is_unsigned_type(return_type.as_ref()),
+        }
+    }
+
+    /// Records the language type of a new binding, from its annotation or else its value
+    fn bind_type(&mut self, name: &str, ty: Option<&Type>, value: AnyValueEnum<'ctx>, expr: &Expr) {
+        let ty = match (ty, value) {
+            (Some(ty), _) => Some(ty.clone()),
+            (None, AnyValueEnum::IntValue(value)) if self.is_unsigned(expr) => Some(Type::Int {
+                bits: value.get_type().get_bit_width(),
+                signed: false,
+            }),
+            (None, AnyValueEnum::StructValue(value)) => self
+                .struct_name(value.get_type())
+                .or_else(|| self.enum_name(value.get_type()))
+                .map(|name| Type::Named(name.to_owned()))
+                .or_else(|| self.function_type_of(expr))
+                .or_else(|| self.static_type(expr)),
+            // a `char` is an `i32` too, `println!` tells them apart by the recorded type
+            (None, AnyValueEnum::IntValue(_)) => self.static_type(expr),
+            (None, AnyValueEnum::ArrayValue(_)) => self.static_type(expr),
+            (None, AnyValueEnum::PointerValue(_)) => self.static_type(expr).or(Some(Type::Str)),
+            _ => None,
+        };
+        match ty {
+            Some(ty) => self.variable_types.insert(name.to_owned(), ty),
+            None => self.variable_types.remove(name),
+        };
+    }
+
+    /// Lowers a binary operator on two integers of the same width
+    fn build_int_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: IntValue<'ctx>,
+        right: IntValue<'ctx>,
+        signed: bool,
+    ) -> IntValue<'ctx> {
+        match op {
+            BinaryOp::Add => self.builder.build_int_add(left, right, "addtmp"),
+            BinaryOp::Subtract => self.builder.build_int_sub(left, right, "subtmp"),
+            BinaryOp::Multiply => self.builder.build_int_mul(left, right, "multmp"),
+            BinaryOp::Divide if signed => self.builder.build_int_signed_div(left, right, "divtmp"),
+            BinaryOp::Divide => self.builder.build_int_unsigned_div(left, right, "divtmp"),
+            BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::LessThan
+            | BinaryOp::GreaterThan
+            | BinaryOp::LessOrEqual
+            | BinaryOp::GreaterOrEqual => {
+                self.builder
+                    .build_int_compare(int_predicate(op, signed), left, right, "cmptmp")
+            }
+            BinaryOp::And | BinaryOp::Or => {
+                unreachable!("`{}` short-circuits, see `compile_logical`", op)
+            }
+        }
+    }
+
+    /// Lowers `&&`/`||` to a branch around the right operand, so it only runs when the left one
+    /// doesn't decide the result. The result is a phi of the short-circuit constant and the right operand.
+    fn compile_logical(
+        &mut self,
+        op: &BinaryOp,
+        left: &Expr,
+        right: &Expr,
+    ) -> anyhow::Result<IntValue<'ctx>> {
+        let left = self.compile_expr(left)?;
+        let left = self.build_condition(left)?;
+        let left_end = self
+            .builder
+            .get_insert_block()
+            .expect("Not positioned inside a function");
+
+        let function = self.current_function();
+        let rhs_block = self.context.append_basic_block(function, "logic_rhs");
+        let merge_block = self.context.append_basic_block(function, "logic_end");
+        let short_circuit = match op {
+            BinaryOp::And => {
+                self.builder
+                    .build_conditional_branch(left, rhs_block, merge_block);
+                false
+            }
+            _ => {
+                self.builder
+                    .build_conditional_branch(left, merge_block, rhs_block);
+                true
+            }
+        };
+
+        self.builder.position_at_end(rhs_block);
+        let right = self.compile_expr(right)?;
+        let right = self.build_condition(right)?;
+        // the right operand can contain branches of its own, so it may end in a different block
+        let right_end = self
+            .builder
+            .get_insert_block()
+            .expect("Not positioned inside a function");
+        self.builder.build_unconditional_branch(merge_block);
+
+        self.builder.position_at_end(merge_block);
+        let bool_type = self.context.bool_type();
+        let phi = self.builder.build_phi(bool_type, "logictmp");
+        phi.add_incoming(&[
+            (&bool_type.const_int(short_circuit as u64, false), left_end),
+            (&right, right_end),
+        ]);
+        Ok(phi.as_basic_value().into_int_value())
+    }
+
+    /// Lowers a binary operator on two floats of the same type. Comparisons are ordered,
+    /// so they are false when an operand is NaN, apart from `!=`
+    fn build_float_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: FloatValue<'ctx>,
+        right: FloatValue<'ctx>,
+    ) -> AnyValueEnum<'ctx> {
+        let predicate = match op {
+            BinaryOp::Add => return self.builder.build_float_add(left, right, "faddtmp").into(),
+            BinaryOp::Subtract => {
+                return self.builder.build_float_sub(left, right, "fsubtmp").into()
+            }
+            BinaryOp::Multiply => {
+                return self.builder.build_float_mul(left, right, "fmultmp").into()
+            }
+            BinaryOp::Divide => return self.builder.build_float_div(left, right, "fdivtmp").into(),
+            BinaryOp::Equal => FloatPredicate::OEQ,
+            BinaryOp::NotEqual => FloatPredicate::UNE,
+            BinaryOp::LessThan => FloatPredicate::OLT,
+            BinaryOp::GreaterThan => FloatPredicate::OGT,
+            BinaryOp::LessOrEqual => FloatPredicate::OLE,
+            BinaryOp::GreaterOrEqual => FloatPredicate::OGE,
+            BinaryOp::And | BinaryOp::Or => {
+                unreachable!("`{}` short-circuits, see `compile_logical`", op)
+            }
+        };
+        self.builder
+            .build_float_compare(predicate, left, right, "fcmptmp")
+            .into()
+    }
+
+    /// Converts a float to `ty` (`f32` <-> `f64`)
+    fn coerce_float(&self, value: FloatValue<'ctx>, ty: FloatType<'ctx>) -> FloatValue<'ctx> {
+        if value.get_type() == ty {
+            value
+        } else {
+            self.builder.build_float_cast(value, ty, "float_cast")
+        }
+    }
+
+    /// Truncates or extends an integer to `ty`, sign-extending only `signed` values.
+    /// Booleans (`i1`) are always zero-extended so `true` becomes 1 rather than -1
+    fn coerce_int(&self, value: IntValue<'ctx>, ty: IntType<'ctx>, signed: bool) -> IntValue<'ctx> {
+        let width = value.get_type().get_bit_width();
+        if width == ty.get_bit_width() {
+            value
+        } else {
+            self.builder
+                .build_int_cast_sign_flag(value, ty, signed && width != 1, "int_cast")
+        }
+    }
+
+    /// Extends the narrower of two integer operands so binary instructions get matching types
+    fn unify_int_widths(
+        &self,
+        left: IntValue<'ctx>,
+        right: IntValue<'ctx>,
+        signed: bool,
+    ) -> (IntValue<'ctx>, IntValue<'ctx>) {
+        let left_width = left.get_type().get_bit_width();
+        let right_width = right.get_type().get_bit_width();
+        if left_width < right_width {
+            (self.coerce_int(left, right.get_type(), signed), right)
+        } else if right_width < left_width {
+            (left, self.coerce_int(right, left.get_type(), signed))
+        } else {
+            (left, right)
+        }
+    }
+
+    /// Compiles the statements of a block, yielding the value of its trailing expression
+    fn compile_block(&mut self, stmts: &[Stmt]) -> anyhow::Result<Option<AnyValueEnum<'ctx>>> {
+        let mut last_value = None;
+        let outer_span = self.span;
+        for stmt in stmts {
+            // anything following a `return` in the same block is unreachable
+            if self.current_block_terminated() {
+                break;
+            }
+            self.span = Some(stmt.span());
+            last_value = self.compile_stmt(stmt)?;
+        }
+        self.span = outer_span;
+        Ok(last_value)
+    }
+
+    /// Compiles a nested block; bindings made inside it are dropped once it ends
+    fn compile_scoped_block(
+        &mut self,
+        stmts: &[Stmt],
+    ) -> anyhow::Result<Option<AnyValueEnum<'ctx>>> {
+        let outer_variables = self.variables.clone();
+        let outer_types = self.variable_types.clone();
+        let value = self.compile_block(stmts);
+        self.variables = outer_variables;
+        self.variable_types = outer_types;
+        value
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) -> anyhow::Result<Option<AnyValueEnum<'ctx>>> {
+        match stmt {
+            Stmt::Assignment {
+                ident: var_name,
+                mutable: true,
+                ty,
+                expr,
+                ..
+            } => {
+                let value = self.compile_expr_expecting(expr, ty.as_ref())?;
+                let value = self.coerce_to_annotation(var_name, value, ty.as_ref(), expr)?;
+                self.bind_type(var_name, ty.as_ref(), value, expr);
+                let value = BasicValueEnum::try_from(value).map_err(|_| {
+                    anyhow!("Unsupported assignment type for variable {}", var_name)
+                })?;
+                let slot = self.build_entry_alloca(value.get_type(), var_name);
+                self.builder.build_store(slot, value);
+                self.variables.insert(
+                    var_name.clone(),
+                    VariableValue::Mutable(slot, value.get_type()),
+                );
+                Ok(None)
+            }
+            Stmt::Global { ident, .. } => Err(anyhow!(
+                "`{}` has to be declared at the top level, not inside a function",
+                ident
+            )),
+            Stmt::Destructure {
+                pattern, ty, expr, ..
+            } => {
+                let value = self.compile_expr_expecting(expr, ty.as_ref())?;
+                let value = self.coerce_to_annotation("pattern", value, ty.as_ref(), expr)?;
+                let value = BasicValueEnum::try_from(value)
+                    .map_err(|_| anyhow!("Cannot destructure {:?}", value))?;
+                let ty = ty.clone().or_else(|| self.static_type(expr));
+                let unmatched = self
+                    .context
+                    .append_basic_block(self.current_function(), "letunmatched");
+                let mut bindings = Vec::new();
+                self.compile_pattern(pattern, value, ty.as_ref(), unmatched, &mut bindings)?;
+                let matched = self
+                    .builder
+                    .get_insert_block()
+                    .expect("the pattern leaves the builder in a block");
+                // the type checker only accepts patterns that match every value
+                self.builder.position_at_end(unmatched);
+                self.builder.build_unreachable();
+                self.builder.position_at_end(matched);
+                self.bind_pattern(&bindings, expr)?;
+                Ok(None)
+            }
+            Stmt::Reassignment {
+                ident, op, expr, ..
+            } => {
+                self.compile_reassignment(ident, op.as_ref(), expr)?;
+                Ok(None)
+            }
+            Stmt::FieldAssignment {
+                ident,
+                fields,
+                op,
+                expr,
+                ..
+            } => {
+                self.compile_field_assignment(ident, fields, op.as_ref(), expr)?;
+                Ok(None)
+            }
+            Stmt::IndexAssignment {
+                ident,
+                indices,
+                op,
+                expr,
+                ..
+            } => {
+                self.compile_index_assignment(ident, indices, op.as_ref(), expr)?;
+                Ok(None)
+            }
+            Stmt::Assignment {
+                ident: var_name,
+                mutable: false,
+                ty,
+                expr,
+                ..
+            } => {
+                let value = self.compile_expr_expecting(expr, ty.as_ref())?;
+                let value = self.coerce_to_annotation(var_name, value, ty.as_ref(), expr)?;
+                self.bind_type(var_name, ty.as_ref(), value, expr);
+                match value {
+                    AnyValueEnum::IntValue(int_val) => {
+                        self.variables
+                            .insert(var_name.clone(), VariableValue::Int(int_val));
+                    }
+                    AnyValueEnum::FloatValue(float_val) => {
+                        self.variables
+                            .insert(var_name.clone(), VariableValue::Float(float_val));
+                    }
+                    AnyValueEnum::PointerValue(ptr_val) => {
+                        // If you want to refine further, you might check the type of the pointer
+                        // but for now, we'll assume any pointer is a string
+                        self.variables
+                            .insert(var_name.clone(), VariableValue::Ptr(ptr_val));
+                    }
+                    AnyValueEnum::StructValue(struct_val) => {
+                        self.variables
+                            .insert(var_name.clone(), VariableValue::Struct(struct_val));
+                    }
+                    AnyValueEnum::ArrayValue(array_val) => {
+                        self.variables
+                            .insert(var_name.clone(), VariableValue::Array(array_val));
+                    }
+                    // Add other types as necessary
+                    _ => {
+                        return Err(anyhow!(
+                            "Unsupported assignment type for variable {}",
+                            var_name
+                        ))
+                    }
+                }
+                Ok(None)
+            }
+            Stmt::Return(expr, _) => {
+                let function_name = self.current_function().get_name().to_str()?.to_owned();
+                let expected = self.return_types.get(&function_name).cloned();
+                let value = self.compile_expr_expecting(expr, expected.as_ref())?;
+                let return_type = self.current_function().get_type().get_return_type();
+                match (value, return_type) {
+                    (_, None) => {
+                        self.builder.build_return(None);
+                    }
+                    (AnyValueEnum::IntValue(i), Some(BasicTypeEnum::IntType(int_type))) => {
+                        let i = self.coerce_int(i, int_type, !self.is_unsigned(expr));
+                        self.builder.build_return(Some(&i));
+                    }
+                    (AnyValueEnum::FloatValue(f), Some(BasicTypeEnum::FloatType(float_type))) => {
+                        let f = self.coerce_float(f, float_type);
+                        self.builder.build_return(Some(&f));
+                    }
+                    (value, Some(return_type)) => match BasicValueEnum::try_from(value) {
+                        Ok(value) if value.get_type() == return_type => {
+                            self.builder.build_return(Some(&value));
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "Mismatched return type: {:#?} | context: {:?}",
+                                value,
+                                expr
+                            ))
+                        }
+                    },
+                }
+                Ok(None)
+            }
+            Stmt::Expression(expr, _) => Ok(Some(self.compile_expr(expr)?)),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => self.compile_if(condition, then_branch, else_branch.as_deref()),
+            Stmt::While {
+                label,
+                condition,
+                body,
+                ..
+            } => {
+                self.compile_while(label, condition, body)?;
+                Ok(None)
+            }
+            Stmt::For {
+                label,
+                ident,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.compile_for(label, ident, start, end, body)?;
+                Ok(None)
+            }
+            Stmt::Break(label, _) => {
+                let target = self.find_loop(label)?.break_block;
+                self.builder.build_unconditional_branch(target);
+                Ok(None)
+            }
+            Stmt::Continue(label, _) => {
+                let target = self.find_loop(label)?.continue_block;
+                self.builder.build_unconditional_branch(target);
+                Ok(None)
+            }
+            Stmt::FunctionDeclaration { ident, .. } => Err(anyhow!(
+                "Nested function declarations are not supported: {}",
+                ident
+            )),
+            Stmt::StructDeclaration { ident, .. } => Err(anyhow!(
+                "Nested struct declarations are not supported: {}",
+                ident
+            )),
+            Stmt::EnumDeclaration { ident, .. } => Err(anyhow!(
+                "Nested enum declarations are not supported: {}",
+                ident
+            )),
+            Stmt::Module { .. } | Stmt::Use { .. } | Stmt::Impl { .. } => Err(anyhow!(
+                "`mod`, `use` and `impl` can only be declared at the top level of a module"
+            )),
+        }
+    }
+
+    /// Lowers `if`/`else` to a conditional branch into `then` and `else` blocks that rejoin in `ifcont`.
+    /// When every branch reaching `ifcont` yields a value of the same type, the result is a phi of those values.
+    fn compile_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &[Stmt],
+        else_branch: Option<&[Stmt]>,
+    ) -> anyhow::Result<Option<AnyValueEnum<'ctx>>> {
+        let condition = self.compile_expr(condition)?;
+        let condition = self.build_condition(condition)?;
+
+        let function = self.current_function();
+        let then_block = self.context.append_basic_block(function, "then");
+        let else_block = self.context.append_basic_block(function, "else");
+        let merge_block = self.context.append_basic_block(function, "ifcont");
+        self.builder
+            .build_conditional_branch(condition, then_block, else_block);
+
+        self.builder.position_at_end(then_block);
+        let then_value = self.compile_scoped_block(then_branch)?;
+        let then_end = self.branch_to(merge_block);
+
+        self.builder.position_at_end(else_block);
+        let else_value = match else_branch {
+            Some(else_branch) => self.compile_scoped_block(else_branch)?,
+            None => None,
+        };
+        let else_end = self.branch_to(merge_block);
+
+        self.builder.position_at_end(merge_block);
+        if then_end.is_none() && else_end.is_none() {
+            // both branches returned, nothing can reach the merge block
+            self.builder.build_unreachable();
+            return Ok(None);
+        }
+
+        let mut incoming: Vec<(BasicValueEnum<'ctx>, BasicBlock<'ctx>)> = Vec::new();
+        for (value, end) in [(then_value, then_end), (else_value, else_end)] {
+            if let Some(end) = end {
+                match value.and_then(|value| BasicValueEnum::try_from(value).ok()) {
+                    Some(value) => incoming.push((value, end)),
+                    None => return Ok(None),
+                }
+            }
+        }
+        let phi_type = incoming[0].0.get_type();
+        if incoming
+            .iter()
+            .any(|(value, _)| value.get_type() != phi_type)
+        {
+            return Ok(None);
+        }
+        let phi = self.builder.build_phi(phi_type, "iftmp");
+        for (value, block) in &incoming {
+            phi.add_incoming(&[(value, *block)]);
+        }
+        Ok(Some(phi.as_basic_value().into()))
+    }
+
+    /// Lowers `while` to a `loopcond` header that re-checks the condition before every run of `loopbody`
+    fn compile_while(
+        &mut self,
+        label: &Option<String>,
+        condition: &Expr,
+        body: &[Stmt],
+    ) -> anyhow::Result<()> {
+        let function = self.current_function();
+        let cond_block = self.context.append_basic_block(function, "loopcond");
+        let body_block = self.context.append_basic_block(function, "loopbody");
+        let exit_block = self.context.append_basic_block(function, "loopexit");
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(cond_block);
+        let condition = self.compile_expr(condition)?;
+        let condition = self.build_condition(condition)?;
+        self.builder
+            .build_conditional_branch(condition, body_block, exit_block);
+
+        self.builder.position_at_end(body_block);
+        self.compile_loop_body(label, body, cond_block, exit_block)?;
+        self.branch_to(cond_block);
+
+        self.builder.position_at_end(exit_block);
+        Ok(())
+    }
+
+    /// Lowers `for i in start..end` to a counter phi in `forcond`, incremented in `forstep`.
+    /// Both bounds are evaluated once, before the loop starts.
+    fn compile_for(
+        &mut self,
+        label: &Option<String>,
+        ident: &str,
+        start: &Expr,
+        end: &Expr,
+        body: &[Stmt],
+    ) -> anyhow::Result<()> {
+        // the loop variable has the type of the bounds, an unsuffixed literal takes the other's
+        let signed = !self.is_unsigned(start) && !self.is_unsigned(end);
+        let start_value = self.compile_range_bound(start)?;
+        let end_value = self.compile_range_bound(end)?;
+        let (start, end) = match (start, end) {
+            (Expr::Num(_), Expr::Num(_)) => self.unify_int_widths(start_value, end_value, signed),
+            (Expr::Num(_), _) => (
+                self.coerce_int(start_value, end_value.get_type(), true),
+                end_value,
+            ),
+            (_, Expr::Num(_)) => (
+                start_value,
+                self.coerce_int(end_value, start_value.get_type(), true),
+            ),
+            _ => self.unify_int_widths(start_value, end_value, signed),
+        };
+        let counter_type = start.get_type();
+        let ty = Type::Int {
+            bits: counter_type.get_bit_width(),
+            signed,
+        };
+
+        let function = self.current_function();
+        let preheader = self
+            .builder
+            .get_insert_block()
+            .expect("Not positioned inside a function");
+        let cond_block = self.context.append_basic_block(function, "forcond");
+        let body_block = self.context.append_basic_block(function, "forbody");
+        let step_block = self.context.append_basic_block(function, "forstep");
+        let exit_block = self.context.append_basic_block(function, "forexit");
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(cond_block);
+        let counter = self.builder.build_phi(counter_type, ident);
+        counter.add_incoming(&[(&start, preheader)]);
+        let current = counter.as_basic_value().into_int_value();
+        let predicate = if signed {
+            IntPredicate::SLT
+        } else {
+            IntPredicate::ULT
+        };
+        let in_range = self
+            .builder
+            .build_int_compare(predicate, current, end, "forcmp");
+        self.builder
+            .build_conditional_branch(in_range, body_block, exit_block);
+
+        self.builder.position_at_end(body_block);
+        let outer_variables = self.variables.clone();
+        let outer_types = self.variable_types.clone();
+        self.variables
+            .insert(ident.to_owned(), VariableValue::Int(current));
+        self.variable_types.insert(ident.to_owned(), ty);
+        let body_result = self.compile_loop_body(label, body, step_block, exit_block);
+        self.variables = outer_variables;
+        self.variable_types = outer_types;
+        body_result?;
+        self.branch_to(step_block);
+
+        self.builder.position_at_end(step_block);
+        let next = self
+            .builder
+            .build_int_add(current, counter_type.const_int(1, false), "fornext");
+        counter.add_incoming(&[(&next, step_block)]);
+        self.builder.build_unconditional_branch(cond_block);
+
+        self.builder.position_at_end(exit_block);
+        Ok(())
+    }
+
+    fn compile_range_bound(&mut self, bound: &Expr) -> anyhow::Result<IntValue<'ctx>> {
+        match self.compile_expr(bound)? {
+            AnyValueEnum::IntValue(value) if value.get_type().get_bit_width() != 1 => Ok(value),
+            value => Err(anyhow!("Range bounds must be integers, got: {:?}", value)),
+        }
+    }
+
+    /// Compiles a loop body with `continue_block`/`break_block` as the targets of `continue`/`break`
+    fn compile_loop_body(
+        &mut self,
+        label: &Option<String>,
+        body: &[Stmt],
+        continue_block: BasicBlock<'ctx>,
+        break_block: BasicBlock<'ctx>,
+    ) -> anyhow::Result<()> {
+        self.loops.push(LoopContext {
+            label: label.clone(),
+            continue_block,
+            break_block,
+        });
+        let result = self.compile_scoped_block(body);
+        self.loops.pop();
+        result.map(|_| ())
+    }
+
+    /// The innermost loop, or the one carrying `label` when a label is given
+    fn find_loop(&self, label: &Option<String>) -> anyhow::Result<&LoopContext<'ctx>> {
+        match label {
+            None => self
+                .loops
+                .last()
+                .ok_or_else(|| anyhow!("`break`/`continue` outside of a loop")),
+            Some(name) => self
+                .loops
+                .iter()
+                .rev()
+                .find(|ctx| ctx.label.as_ref() == Some(name))
+                .ok_or_else(|| anyhow!("Undeclared loop label: '{}", name)),
+        }
+    }
+
+    /// Stores into the stack slot of a `let mut` binding, applying the operator of a compound assignment first
+    fn compile_reassignment(
+        &mut self,
+        ident: &str,
+        op: Option<&BinaryOp>,
+        expr: &Expr,
+    ) -> anyhow::Result<()> {
+        let (slot, slot_type) = match self.variables.get(ident) {
+            Some(VariableValue::Mutable(slot, slot_type)) => (*slot, *slot_type),
+            Some(_) => {
+                return Err(anyhow!(
+                    "Cannot assign twice to immutable variable `{}`",
+                    ident
+                ))
+            }
+            None => return Err(anyhow!("Undefined variable: {}", ident)),
+        };
+        let signed = !is_unsigned_type(self.variable_types.get(ident));
+        self.build_assignment(ident, slot, slot_type, signed, op, expr)
+    }
+
+    /// Stores into a field of a `let mut` struct, following the path of nested fields with `build_struct_gep`
+    fn compile_field_assignment(
+        &mut self,
+        ident: &str,
+        fields: &[String],
+        op: Option<&BinaryOp>,
+        expr: &Expr,
+    ) -> anyhow::Result<()> {
+        let target = format!("{}.{}", ident, fields.join("."));
+        let (mut slot, mut slot_type) = match self.variables.get(ident) {
+            Some(VariableValue::Mutable(slot, slot_type)) => (*slot, *slot_type),
+            Some(_) => {
+                return Err(anyhow!(
+                    "Cannot assign to `{}`, `{}` is not declared as mutable",
+                    target,
+                    ident
+                ))
+            }
+            None => return Err(anyhow!("Undefined variable: {}", ident)),
+        };
+        let mut ty = self.variable_types.get(ident).cloned();
+        for field in fields {
+            let BasicTypeEnum::StructType(struct_type) = slot_type else {
+                return Err(anyhow!(
+                    "Cannot access field `{}` on {:?}",
+                    field,
+                    slot_type
+                ));
+            };
+            let (index, field_type) = self.field_index(struct_type, field)?;
+            ty = field_type.or_else(|| self.field_type(ty.as_ref()?, field));
+            slot = self
+                .builder
+                .build_struct_gep(struct_type, slot, index, field)
+                .map_err(|_| anyhow!("Invalid field index {} of {:?}", index, struct_type))?;
+            slot_type = struct_type
+                .get_field_type_at_index(index)
+                .expect("field index comes from the struct's declaration");
+        }
+        let signed = !is_unsigned_type(ty.as_ref());
+        self.build_assignment(&target, slot, slot_type, signed, op, expr)
+    }
+
+    /// Stores into an element of a `let mut` array or `Vec`, `grid[i][j] = v` indexes the nested arrays in place
+    fn compile_index_assignment(
+        &mut self,
+        ident: &str,
+        indices: &[Expr],
+        op: Option<&BinaryOp>,
+        expr: &Expr,
+    ) -> anyhow::Result<()> {
+        let target = format!(
+            "{}{}",
+            ident,
+            indices
+                .iter()
+                .map(|index| format!("[{}]", index))
+                .collect::<String>()
+        );
+        match self.variables.get(ident) {
+            Some(VariableValue::Mutable(..)) => {}
+            Some(_) => {
+                return Err(anyhow!(
+                    "Cannot assign to `{}`, `{}` is not declared as mutable",
+                    target,
+                    ident
+                ))
+            }
+            None => return Err(anyhow!("Undefined variable: {}", ident)),
+        }
+        let (last, outer) = indices
+            .split_last()
+            .expect("an index assignment has at least one index");
+        let array = outer
+            .iter()
+            .fold(Expr::Ident(ident.to_owned()), |array, index| {
+                Expr::ArrayIndexing(Box::new(array), Box::new(index.clone()))
+            });
+        let (slot, slot_type) = self.element_pointer(&array, last)?;
+        let element = Expr::ArrayIndexing(Box::new(array), Box::new(last.clone()));
+        let signed = !is_unsigned_type(self.static_type(&element).as_ref());
+        self.build_assignment(&target, slot, slot_type, signed, op, expr)
+    }
+
+    /// Stores `expr` into `slot`, or the result of applying a compound assignment's operator
+    /// to the slot's current value and `expr`
+    fn build_assignment(
+        &mut self,
+        ident: &str,
+        slot: PointerValue<'ctx>,
+        slot_type: BasicTypeEnum<'ctx>,
+        signed: bool,
+        op: Option<&BinaryOp>,
+        expr: &Expr,
+    ) -> anyhow::Result<()> {
+        let value_signed = !self.is_unsigned(expr);
+        let value = self.compile_expr(expr)?;
+        let value: BasicValueEnum<'ctx> = match (value, slot_type, op) {
+            (AnyValueEnum::IntValue(value), BasicTypeEnum::IntType(int_type), op) => {
+                let value = self.coerce_int(value, int_type, value_signed);
+                match op {
+                    Some(op) => {
+                        let current = self
+                            .builder
+                            .build_load(slot_type, slot, ident)
+                            .into_int_value();
+                        self.build_int_binary_op(op, current, value, signed).into()
+                    }
+                    None => value.into(),
+                }
+            }
+            (AnyValueEnum::FloatValue(value), BasicTypeEnum::FloatType(float_type), op) => {
+                let value = self.coerce_float(value, float_type);
+                match op {
+                    Some(op) => {
+                        let current = self
+                            .builder
+                            .build_load(slot_type, slot, ident)
+                            .into_float_value();
+                        self.build_float_binary_op(op, current, value)
+                            .into_float_value()
+                            .into()
+                    }
+                    None => value.into(),
+                }
+            }
+            (value, _, None) => match BasicValueEnum::try_from(value) {
+                Ok(value) if value.get_type() == slot_type => value,
+                _ => return Err(anyhow!("Mismatched types in assignment to `{}`", ident)),
+            },
+            _ => {
+                return Err(anyhow!(
+                    "Compound assignment to `{}` needs numeric operands",
+                    ident
+                ))
+            }
+        };
+        self.builder.build_store(slot, value);
+        Ok(())
+    }
+
+    /// Allocates a stack slot at the top of the current function's entry block, where mem2reg can promote it
+    fn build_entry_alloca(&self, ty: BasicTypeEnum<'ctx>, name: &str) -> PointerValue<'ctx> {
+        let entry = self
+            .current_function()
+            .get_first_basic_block()
+            .expect("Function without an entry block");
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => builder.position_before(&first),
+            None => builder.position_at_end(entry),
+        }
+        builder.build_alloca(ty, name)
+    }
+
+    /// Conditions have to be `bool` (`i1`), integers aren't implicitly truthy
+    fn build_condition(&self, value: AnyValueEnum<'ctx>) -> anyhow::Result<IntValue<'ctx>> {
+        match value {
+            AnyValueEnum::IntValue(int_val) if int_val.get_type().get_bit_width() == 1 => {
+                Ok(int_val)
+            }
+            _ => Err(anyhow!("Expected a boolean condition, got: {:?}", value)),
+        }
+    }
+
+    fn current_function(&self) -> FunctionValue<'ctx> {
+        self.builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent())
+            .expect("Not positioned inside a function")
+    }
+
+    fn current_block_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .is_some_and(|block| block.get_terminator().is_some())
+    }
+
+    /// Branches to `target` unless the current block already ended (e.g. with a `return`).
+    /// Returns the block that falls through to `target`.
+    fn branch_to(&self, target: BasicBlock<'ctx>) -> Option<BasicBlock<'ctx>> {
+        let current = self.builder.get_insert_block()?;
+        if current.get_terminator().is_some() {
+            return None;
+        }
+        self.builder.build_unconditional_branch(target);
+        Some(current)
+    }
+
+    pub fn compile(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
+        self.declare_types(stmts)?;
+        self.declare_generic_functions(stmts);
+        self.declare_functions(stmts)?;
+        self.declare_globals(stmts)?;
+        for stmt in stmts {
+            self.span = Some(stmt.span());
+            match stmt {
+                // already declared by `declare_types`
+                Stmt::StructDeclaration { .. } | Stmt::EnumDeclaration { .. } => {}
+                // already declared by `declare_globals`
+                Stmt::Global { .. } => {}
+                // compiled for each call by `compile_generic_call`
+                Stmt::FunctionDeclaration { type_params, .. } if !type_params.is_empty() => {}
+                Stmt::Return(expr, _) => {
+                    let value = self.compile_expr(expr)?;
+                    if value.get_type().is_void_type() {
+                        self.builder.build_return(None);
+                    } else {
+                        match value {
+                            AnyValueEnum::IntValue(i) => {
+                                self.builder.build_return(Some(&i));
+                            }
+                            AnyValueEnum::PointerValue(p) => {
+                                self.builder.build_return(Some(&p));
+                            }
+                            _ => panic!("Unknown return type: {:#?}", value),
+                        }
+                    };
+                }
+                Stmt::FunctionDeclaration {
+                    ident: name,
+                    params,
+                    return_type,
+                    body,
+                    ..
+                } => {
+                    // a function sees the globals, but not the locals of the functions before it
+                    let globals = (self.variables.clone(), self.variable_types.clone());
+                    self.compile_function(name, params, return_type.as_ref(), body)?;
+                    (self.variables, self.variable_types) = globals;
+                }
+                Stmt::Module { ident, .. } => {
+                    return Err(anyhow!(
+                        "Module `{}` has to be loaded with `parsers::modules` before it's compiled",
+                        ident
+                    ))
+                }
+                Stmt::Use { path, .. } => {
+                    return Err(anyhow!(
+                        "`use {}` has to be resolved with `parsers::modules` before it's compiled",
+                        path.join("::")
+                    ))
+                }
+                Stmt::Impl { ty, .. } => {
+                    return Err(anyhow!(
+                        "`impl {}` has to be resolved with `parsers::modules` before it's compiled",
+                        ty
+                    ))
+                }
+                Stmt::If { .. } => {
+                    return Err(anyhow!(
+                        "`if` statements are only supported inside functions"
+                    ))
+                }
+                Stmt::While { .. } | Stmt::For { .. } => {
+                    return Err(anyhow!("Loops are only supported inside functions"))
+                }
+                Stmt::Break(_, _) | Stmt::Continue(_, _) => {
+                    return Err(anyhow!("`break`/`continue` outside of a loop"))
+                }
+                Stmt::Assignment { mutable: true, .. }
+                | Stmt::Reassignment { .. }
+                | Stmt::FieldAssignment { .. }
+                | Stmt::IndexAssignment { .. } => {
+                    return Err(anyhow!(
+                        "Mutable variables are only supported inside functions"
+                    ))
+                }
+                Stmt::Destructure { .. } => {
+                    return Err(anyhow!(
+                        "Destructuring `let` is only supported inside functions"
+                    ))
+                }
+                Stmt::Assignment {
+                    ident: var_name,
+                    mutable: false,
+                    ty,
+                    expr,
+                    ..
+                } => {
+                    match expr {
+                        Expr::Ident(string_value) => {
+                            // If it's a Var expression, assume it's a string value
+                            self.variables
+                                .insert(var_name.clone(), VariableValue::Str(string_value.clone()));
+                        }
+                        _ => {
+                            let value = self.compile_expr_expecting(expr, ty.as_ref())?;
+                            let value =
+                                self.coerce_to_annotation(var_name, value, ty.as_ref(), expr)?;
+                            self.bind_type(var_name, ty.as_ref(), value, expr);
+                            let value = match value {
+                                AnyValueEnum::FloatValue(value) => VariableValue::Float(value),
+                                AnyValueEnum::StructValue(value) => VariableValue::Struct(value),
+                                value => VariableValue::Int(value.into_int_value()),
+                            };
+                            self.variables.insert(var_name.clone(), value);
+                        }
+                    }
+                }
+                Stmt::Expression(expr, _) => {
+                    self.compile_expr(expr)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the generic functions, which can be called before they're declared
+    fn declare_generic_functions(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if let Stmt::FunctionDeclaration {
+                ident,
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } = stmt
+            {
+                if !type_params.is_empty() {
+                    let generic = GenericFunction {
+                        type_params: type_params.clone(),
+                        params: params.clone(),
+                        return_type: return_type.clone(),
+                        body: body.clone(),
+                    };
+                    self.generic_functions.insert(ident.clone(), generic);
+                }
+            }
+        }
+    }
+
+    /// Adds the prototype of every function that isn't generic, so a function can call the
+    /// ones declared after it, like the type checker allows
+    fn declare_functions(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
+        for stmt in stmts {
+            if let Stmt::FunctionDeclaration {
+                ident,
+                type_params,
+                params,
+                return_type,
+                ..
+            } = stmt
+            {
+                if type_params.is_empty() {
+                    self.span = Some(stmt.span());
+                    self.declare_function(ident, params, return_type.as_ref())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds every `const` to its value and creates an LLVM global for every `static`, so
+    /// functions declared before them can use them too. Their values have to fold to constants,
+    /// a `const` is declared after the ones its value refers to.
+    fn declare_globals(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
+        for stmt in globals_in_dependency_order(stmts) {
+            let Stmt::Global {
+                ident,
+                kind,
+                ty,
+                expr,
+                span,
+                ..
+            } = stmt
+            else {
+                continue;
+            };
+            self.span = Some(*span);
+            let value = self.compile_expr_expecting(expr, Some(ty))?;
+            let value = match self.coerce_to_annotation(ident, value, Some(ty), expr)? {
+                AnyValueEnum::IntValue(value) if value.is_const() => BasicValueEnum::from(value),
+                AnyValueEnum::FloatValue(value) if value.is_const() => value.into(),
+                AnyValueEnum::PointerValue(value) if value.is_const() => value.into(),
+                _ => {
+                    return Err(anyhow!(
+                        "The value of `{}` has to be known at compile time",
+                        ident
+                    ))
+                }
+            };
+            let variable = match (kind, value) {
+                (GlobalKind::Const, BasicValueEnum::IntValue(value)) => VariableValue::Int(value),
+                (GlobalKind::Const, BasicValueEnum::FloatValue(value)) => {
+                    VariableValue::Float(value)
+                }
+                (GlobalKind::Const, value) => VariableValue::Ptr(value.into_pointer_value()),
+                (GlobalKind::Static | GlobalKind::StaticMut, value) => {
+                    let global = self.module.add_global(value.get_type(), None, ident);
+                    global.set_initializer(&value);
+                    global.set_constant(*kind == GlobalKind::Static);
+                    VariableValue::Mutable(global.as_pointer_value(), value.get_type())
+                }
+            };
+            self.globals.insert(ident.clone(), variable.clone());
+            self.variables.insert(ident.clone(), variable);
+            self.variable_types.insert(ident.clone(), ty.clone());
+        }
+        Ok(())
+    }
+
+    /// Creates a named LLVM struct type for every struct and enum declaration. The types are created
+    /// before any body is set, so fields can refer to types declared further down.
+    fn declare_types(&mut self, stmts: &[Stmt]) -> anyhow::Result<()> {
+        let declarations = stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::StructDeclaration { ident, fields, .. } => Some((ident, fields)),
+            _ => None,
+        });
+        let enums = stmts.iter().filter_map(|stmt| match stmt {
+            Stmt::EnumDeclaration {
+                ident, variants, ..
+            } => Some((ident, variants)),
+            _ => None,
+        });
+        for (ident, fields) in declarations.clone() {
+            if self.struct_types.contains_key(ident) {
+                return Err(anyhow!("Struct `{}` is defined more than once", ident));
+            }
+            let struct_type = self.context.opaque_struct_type(ident);
+            self.struct_types
+                .insert(ident.clone(), (struct_type, fields.clone()));
+        }
+        for (ident, _) in enums.clone() {
+            if self.struct_types.contains_key(ident) || self.enum_types.contains_key(ident) {
+                return Err(anyhow!("Enum `{}` is defined more than once", ident));
+            }
+            let layout = EnumLayout {
+                ty: self.context.opaque_struct_type(ident),
+                variants: Vec::new(),
+            };
+            self.enum_types.insert(ident.clone(), layout);
+        }
+        for (ident, fields) in declarations {
+            let field_types = fields
+                .iter()
+                .map(|field| {
+                    self.llvm_type(&field.ty).ok_or_else(|| {
+                        anyhow!(
+                            "Field `{}.{}` can't have type {}",
+                            ident,
+                            field.ident,
+                            field.ty
+                        )
+                    })
+                })
+                .collect::<anyhow::Result<Vec<BasicTypeEnum<'ctx>>>>()?;
+            self.struct_types[ident].0.set_body(&field_types, false);
+        }
+        // the size of an enum depends on its payloads, so its body is only set once they are all sized
+        let mut pending = enums.collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let unsized_count = pending.len();
+            for (ident, variants) in std::mem::take(&mut pending) {
+                let payload_types = variants
+                    .iter()
+                    .map(|variant| {
+                        let field_types = variant
+                            .fields
+                            .iter()
+                            .map(|ty| {
+                                self.llvm_type(ty).ok_or_else(|| {
+                                    anyhow!(
+                                        "Variant `{}::{}` can't hold {}",
+                                        ident,
+                                        variant.ident,
+                                        ty
+                                    )
+                                })
+                            })
+                            .collect::<anyhow::Result<Vec<BasicTypeEnum<'ctx>>>>()?;
+                        Ok(self.context.struct_type(&field_types, false))
+                    })
+                    .collect::<anyhow::Result<Vec<StructType<'ctx>>>>()?;
+                if payload_types.iter().all(|ty| ty.is_sized()) {
+                    self.set_enum_body(ident, variants, payload_types);
+                } else {
+                    pending.push((ident, variants));
+                }
+            }
+            if pending.len() == unsized_count {
+                return Err(anyhow!(
+                    "Enum `{}` contains itself, it would have an infinite size",
+                    pending[0].0
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Lays an enum out as its `i32` tag followed by enough `i64` words for its largest payload
+    fn set_enum_body(
+        &mut self,
+        ident: &str,
+        variants: &[Variant],
+        payload_types: Vec<StructType<'ctx>>,
+    ) {
+        let target_data = self.execution_engine.get_target_data();
+        let payload_size = payload_types
+            .iter()
+            .map(|ty| target_data.get_abi_size(ty))
+            .max()
+            .unwrap_or(0);
+        let payload = self
+            .context
+            .i64_type()
+            .array_type(payload_size.div_ceil(8) as u32);
+        let layout = self
+            .enum_types
+            .get_mut(ident)
+            .expect("enums are declared before their bodies are set");
+        layout
+            .ty
+            .set_body(&[self.context.i32_type().into(), payload.into()], false);
+        layout.variants = variants.iter().cloned().zip(payload_types).collect();
+    }
+
+    /// Name of a declared struct from its LLVM type
+    fn struct_name(&self, struct_type: StructType<'ctx>) -> Option<&str> {
+        self.struct_types
+            .iter()
+            .find(|(_, (ty, _))| *ty == struct_type)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Name of a declared enum from its LLVM type
+    fn enum_name(&self, enum_type: StructType<'ctx>) -> Option<&str> {
+        self.enum_types
+            .iter()
+            .find(|(_, layout)| layout.ty == enum_type)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Layout of `enum_name` and the tag, declaration and payload type of one of its variants
+    fn enum_variant(
+        &self,
+        enum_name: &str,
+        variant: &str,
+    ) -> anyhow::Result<(EnumLayout<'ctx>, u64, Variant, StructType<'ctx>)> {
+        let layout = self
+            .enum_types
+            .get(enum_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Undefined enum: {}", enum_name))?;
+        let (tag, (declared, payload_type)) = layout
+            .variants
+            .iter()
+            .enumerate()
+            .find(|(_, (declared, _))| declared.ident == variant)
+            .map(|(tag, (declared, payload_type))| (tag, (declared.clone(), *payload_type)))
+            .ok_or_else(|| anyhow!("Enum `{}` has no variant `{}`", enum_name, variant))?;
+        Ok((layout, tag as u64, declared, payload_type))
+    }
+
+    /// Position of `field` within a struct type, with its language type when the struct is declared.
+    /// The elements of a tuple, an anonymous struct, are fields `0`, `1` and so on.
+    fn field_index(
+        &self,
+        struct_type: StructType<'ctx>,
+        field: &str,
+    ) -> anyhow::Result<(u32, Option<Type>)> {
+        let Some(name) = self.struct_name(struct_type) else {
+            return match field.parse::<u32>() {
+                Ok(index) if index < struct_type.count_fields() => Ok((index, None)),
+                _ => Err(anyhow!("{:?} has no field `{}`", struct_type, field)),
+            };
+        };
+        self.struct_types[name]
+            .1
+            .iter()
+            .position(|declared| declared.ident == field)
+            .map(|index| {
+                let ty = self.struct_types[name].1[index].ty.clone();
+                (index as u32, Some(ty))
+            })
+            .ok_or_else(|| anyhow!("Struct `{}` has no field `{}`", name, field))
+    }
+
+    /// Language type of `field` within a struct or tuple of type `ty`
+    fn field_type(&self, ty: &Type, field: &str) -> Option<Type> {
+        match ty {
+            Type::Named(name) => {
+                let (_, fields) = self.struct_types.get(name)?;
+                let declared = fields.iter().find(|declared| declared.ident == field)?;
+                Some(declared.ty.clone())
+            }
+            Type::Tuple(types) => types.get(field.parse::<usize>().ok()?).cloned(),
+            _ => None,
+        }
+    }
+
+    /// The language type of `expr` when it's known without compiling it
+    fn static_type(&self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Ident(name) => self.variable_types.get(name).cloned(),
+            Expr::Call(name, _) => match self.variable_types.get(name) {
+                Some(Type::Function(_, return_type)) if self.variables.contains_key(name) => {
+                    Some((**return_type).clone())
+                }
+                _ => self.return_types.get(name).cloned(),
+            },
+            Expr::Cast(_, ty) => Some(ty.clone()),
+            Expr::StructLiteral(name, _) => Some(Type::Named(name.clone())),
+            Expr::FieldAccess(operand, field) => {
+                self.field_type(&self.static_type(operand)?, field)
+            }
+            Expr::Tuple(elements) => elements
+                .iter()
+                .map(|element| self.static_type(element))
+                .collect::<Option<Vec<_>>>()
+                .map(Type::Tuple),
+            Expr::Array(elements) => self
+                .array_element_type(elements)
+                .map(|ty| Type::Array(Box::new(ty), elements.len())),
+            Expr::ArrayIndexing(array, _) => match self.static_type(array)? {
+                Type::Array(element, _) | Type::Vec(element) | Type::Slice(element) => {
+                    Some(*element)
+                }
+                _ => None,
+            },
+            Expr::Slice(array, ..) => match self.static_type(array)? {
+                Type::Array(element, _) | Type::Vec(element) | Type::Slice(element) => {
+                    Some(Type::Slice(element))
+                }
+                Type::Str => Some(Type::Slice(Box::new(Type::Int {
+                    bits: 8,
+                    signed: false,
+                }))),
+                _ => None,
+            },
+            Expr::QualifiedIdent(path) if path == &["std", "args"] => {
+                Some(Type::Ptr(Box::new(Type::Ptr(Box::new(Type::Int {
+                    bits: 8,
+                    signed: true,
+                })))))
+            }
+            Expr::Num(n) => {
+                let (bits, signed) = n.width();
+                Some(Type::Int { bits, signed })
+            }
+            Expr::Float(_) => Some(Type::F64),
+            Expr::Bool(_) => Some(Type::Bool),
+            Expr::Char(_) => Some(Type::Char),
+            Expr::Str(_) => Some(Type::Str),
+            Expr::MacroCall(name, _) if name == "format" => Some(Type::Str),
+            Expr::MethodCall {
+                receiver, method, ..
+            } if self.method_function(receiver, method).is_some() => {
+                let function = self.method_function(receiver, method)?;
+                self.return_types.get(&function).cloned()
+            }
+            Expr::MethodCall { method, .. } if method == "len" => Some(Type::Int {
+                bits: 64,
+                signed: false,
+            }),
+            Expr::MethodCall {
+                receiver, method, ..
+            } if method == "pop" => match self.static_type(receiver)? {
+                Type::Vec(element) => Some(*element),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The element type of an array literal, decided by its first element that isn't an unsuffixed literal
+    /// or else its first element
+    fn array_element_type(&self, elements: &[Expr]) -> Option<Type> {
+        elements
+            .iter()
+            .filter(|element| !matches!(element, Expr::Num(_) | Expr::Float(_)))
+            .find_map(|element| self.static_type(element))
+            .or_else(|| self.static_type(elements.first()?))
+    }
+
+    /// Builds an array literal, converting each element to the element type of `expected`
+    fn compile_array(
+        &mut self,
+        elements: &[Expr],
+        expected: Option<&Type>,
+    ) -> anyhow::Result<ArrayValue<'ctx>> {
+        let element_type = match expected {
+            Some(Type::Array(ty, len)) if *len == elements.len() => Some((**ty).clone()),
+            _ => self.array_element_type(elements),
+        };
+        let mut values = Vec::new();
+        for (index, element) in elements.iter().enumerate() {
+            let value = self.compile_expr_expecting(element, element_type.as_ref())?;
+            let value = self.coerce_to_annotation(
+                &format!("element {}", index),
+                value,
+                element_type.as_ref(),
+                element,
+            )?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("Array element {} can't hold {:?}", index, value))?;
+            values.push(value);
+        }
+        let llvm_element_type = match (values.first(), &element_type) {
+            (Some(value), _) => value.get_type(),
+            (None, Some(ty)) => self
+                .llvm_type(ty)
+                .ok_or_else(|| anyhow!("An array can't hold {}", ty))?,
+            (None, None) => return Err(anyhow!("Cannot infer the element type of an empty array")),
+        };
+        let mut array = llvm_element_type
+            .array_type(values.len() as u32)
+            .get_undef();
+        for (index, value) in values.into_iter().enumerate() {
+            if value.get_type() != llvm_element_type {
+                return Err(anyhow!(
+                    "Array element {} is {:?}, expected {:?}",
+                    index,
+                    value.get_type(),
+                    llvm_element_type
+                ));
+            }
+            array = self
+                .builder
+                .build_insert_value(array, value, index as u32, "array")
+                .ok_or_else(|| anyhow!("Invalid array index {}", index))?
+                .into_array_value();
+        }
+        Ok(array)
+    }
+
+    /// Pointer to element `index` of an array, `Vec` or slice, with the element's LLVM type.
+    /// The elements of a `let mut` array are addressed in place so they can be assigned.
+    fn element_pointer(
+        &mut self,
+        array: &Expr,
+        index: &Expr,
+    ) -> anyhow::Result<(PointerValue<'ctx>, BasicTypeEnum<'ctx>)> {
+        // `std::args[1]` indexes the C `argv`, which holds `argc` arguments, a raw pointer is unchecked
+        if let Some(Type::Ptr(_)) = self.static_type(array) {
+            let ptr = self.compile_expr(array)?.into_pointer_value();
+            let index = self.compile_index(index)?;
+            if matches!(array, Expr::QualifiedIdent(path) if path == &["std", "args"]) {
+                let argc = self
+                    .module
+                    .get_global("argc_global")
+                    .expect("argc_global not found");
+                let argc = self
+                    .builder
+                    .build_load(self.context.i32_type(), argc.as_pointer_value(), "argc")
+                    .into_int_value();
+                let len = self.coerce_int(argc, self.context.i64_type(), true);
+                self.build_bounds_check(index, len);
+            }
+            return Ok(self.pointer_element(ptr, index));
+        }
+        let (data, len, element_type) = self.elements(array)?;
+        let index = self.compile_index(index)?;
+        self.build_bounds_check(index, len);
+        Ok((self.element_at(data, element_type, index), element_type))
+    }
+
+    /// The elements of an array, `Vec`, slice or string: a pointer to the first, their number
+    /// and their LLVM type. A `let mut` array is used in place, any other array is copied to a
+    /// stack slot of the current function first.
+    fn elements(
+        &mut self,
+        array: &Expr,
+    ) -> anyhow::Result<(PointerValue<'ctx>, IntValue<'ctx>, BasicTypeEnum<'ctx>)> {
+        let place = match array {
+            Expr::Ident(name) => match self.variables.get(name) {
+                Some(VariableValue::Mutable(slot, slot_type)) => Some((*slot, *slot_type)),
+                _ => None,
+            },
+            Expr::ArrayIndexing(inner, inner_index) => {
+                Some(self.element_pointer(inner, inner_index)?)
+            }
+            _ => None,
+        };
+        let (slot, slot_type) = match place {
+            Some(place) => place,
+            None => {
+                let value = self.compile_expr(array)?;
+                let value = BasicValueEnum::try_from(value)
+                    .map_err(|_| anyhow!("Cannot index into {:?}", value))?;
+                let slot = self.build_entry_alloca(value.get_type(), "indexed");
+                self.builder.build_store(slot, value);
+                (slot, value.get_type())
+            }
+        };
+        let i64_type = self.context.i64_type();
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        match slot_type {
+            BasicTypeEnum::ArrayType(array_type) => Ok((
+                slot,
+                i64_type.const_int(array_type.len() as u64, false),
+                array_type.get_element_type(),
+            )),
+            // a `Vec` header starts with the same `{ data, len }` as a slice
+            BasicTypeEnum::StructType(struct_type)
+                if struct_type == self.vec_type || struct_type == self.slice_type =>
+            {
+                let element_type = match self.static_type(array) {
+                    Some(Type::Vec(element) | Type::Slice(element)) => self.llvm_type(&element),
+                    _ => None,
+                }
+                .ok_or_else(|| anyhow!("Unknown element type of `{}`", array))?;
+                let data = self
+                    .builder
+                    .build_struct_gep(struct_type, slot, 0, "data")
+                    .expect("a Vec or slice has a data field");
+                let data = self
+                    .builder
+                    .build_load(i8_ptr_type, data, "data")
+                    .into_pointer_value();
+                let len = self
+                    .builder
+                    .build_struct_gep(struct_type, slot, 1, "len")
+                    .expect("a Vec or slice has a len field");
+                let len = self
+                    .builder
+                    .build_load(i64_type, len, "len")
+                    .into_int_value();
+                Ok((data, len, element_type))
+            }
+            // a string, whose UTF-8 bytes end at its terminator
+            BasicTypeEnum::PointerType(ptr_type) => {
+                let string = self
+                    .builder
+                    .build_load(ptr_type, slot, "string")
+                    .into_pointer_value();
+                let strlen = self
+                    .module
+                    .get_function("strlen")
+                    .expect("strlen function not found");
+                let len = self
+                    .builder
+                    .build_call(strlen, &[string.into()], "len")
+                    .try_as_basic_value()
+                    .left()
+                    .expect("strlen returns a length")
+                    .into_int_value();
+                Ok((string, len, self.context.i8_type().into()))
+            }
+            _ => Err(anyhow!("Cannot index into {:?}", slot_type)),
+        }
+    }
+
+    /// Pointer to element `index` of the elements of type `element_type` starting at `data`
+    fn element_at(
+        &self,
+        data: PointerValue<'ctx>,
+        element_type: BasicTypeEnum<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let elements = self.builder.build_pointer_cast(
+            data,
+            element_type.ptr_type(AddressSpace::default()),
+            "elements",
+        );
+        unsafe {
+            self.builder
+                .build_gep(element_type, elements, &[index], "element")
+        }
+    }
+
+    /// Builds the `{ data, len }` of `array[start..end]`, which points into the elements of an
+    /// array, `Vec` or slice, or the characters of a string. Slicing an array that isn't a
+    /// `let mut` variable points into a copy that lives until the current function returns.
+    fn compile_slice(
+        &mut self,
+        array: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> anyhow::Result<StructValue<'ctx>> {
+        let (data, len, element_type) = self.elements(array)?;
+        let start = match start {
+            Some(start) => self.compile_index(start)?,
+            None => self.context.i64_type().const_zero(),
+        };
+        let end = match end {
+            Some(end) => self.compile_index(end)?,
+            None => len,
+        };
+        self.build_range_check(start, end, len);
+        let data = self.element_at(data, element_type, start);
+        let data = self.builder.build_pointer_cast(
+            data,
+            self.context.i8_type().ptr_type(AddressSpace::default()),
+            "data",
+        );
+        let len = self.builder.build_int_sub(end, start, "len");
+        let slice = self
+            .builder
+            .build_insert_value(self.slice_type.get_undef(), data, 0, "slice")
+            .expect("a slice has a data field")
+            .into_struct_value();
+        let slice = self
+            .builder
+            .build_insert_value(slice, len, 1, "slice")
+            .expect("a slice has a len field");
+        Ok(slice.into_struct_value())
+    }
+
+    /// An index converted to `i64`, the type lengths are compared with
+    fn compile_index(&mut self, index: &Expr) -> anyhow::Result<IntValue<'ctx>> {
+        let signed = !self.is_unsigned(index);
+        match self.compile_expr(index)? {
+            AnyValueEnum::IntValue(index) => {
+                Ok(self.coerce_int(index, self.context.i64_type(), signed))
+            }
+            value => Err(anyhow!("Array index must be an integer, got {:?}", value)),
+        }
+    }
+
+    /// Panics unless `index` is below `len`. A negative index compares as a huge unsigned one,
+    /// so one comparison covers both ends.
+    fn build_bounds_check(&self, index: IntValue<'ctx>, len: IntValue<'ctx>) {
+        if !self.bounds_checks {
+            return;
+        }
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, index, len, "inbounds");
+        let function = self.current_function();
+        let out_of_bounds_block = self.context.append_basic_block(function, "outofbounds");
+        let in_bounds_block = self.context.append_basic_block(function, "inbounds");
+        self.builder
+            .build_conditional_branch(in_bounds, in_bounds_block, out_of_bounds_block);
+
+        self.builder.position_at_end(out_of_bounds_block);
+        let (location, line) = self.panic_location();
+        let panic_bounds = self
+            .module
+            .get_function("__akame_panic_bounds")
+            .expect("panic_bounds function not found");
+        self.builder.build_call(
+            panic_bounds,
+            &[location.into(), len.into(), index.into(), line.into()],
+            "panic",
+        );
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(in_bounds_block);
+    }
+
+    /// Panics unless `start..end` lies within `0..len`
+    fn build_range_check(&self, start: IntValue<'ctx>, end: IntValue<'ctx>, len: IntValue<'ctx>) {
+        if !self.bounds_checks {
+            return;
+        }
+        let ordered = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, start, end, "ordered");
+        let within = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, end, len, "within");
+        let in_bounds = self.builder.build_and(ordered, within, "inbounds");
+        let function = self.current_function();
+        let out_of_bounds_block = self.context.append_basic_block(function, "outofbounds");
+        let in_bounds_block = self.context.append_basic_block(function, "inbounds");
+        self.builder
+            .build_conditional_branch(in_bounds, in_bounds_block, out_of_bounds_block);
+
+        self.builder.position_at_end(out_of_bounds_block);
+        let (location, line) = self.panic_location();
+        let panic_slice = self
+            .module
+            .get_function("__akame_panic_slice")
+            .expect("panic_slice function not found");
+        self.builder.build_call(
+            panic_slice,
+            &[
+                location.into(),
+                start.into(),
+                end.into(),
+                len.into(),
+                line.into(),
+            ],
+            "panic",
+        );
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(in_bounds_block);
+    }
+
+    /// Panics with `message`, leaving the builder in an unreachable block
+    fn build_panic(&self, message: &str) {
+        let (location, line) = self.panic_location();
+        let message = self
+            .builder
+            .build_global_string_ptr(message, "panic_message")
+            .as_pointer_value();
+        let panic = self
+            .module
+            .get_function("__akame_panic")
+            .expect("panic function not found");
+        self.builder.build_call(
+            panic,
+            &[location.into(), message.into(), line.into()],
+            "panic",
+        );
+        self.builder.build_unreachable();
+    }
+
+    /// `path:line:column` of the statement being compiled and the text of its line, as strings
+    /// for the panic routines
+    fn panic_location(&self) -> (PointerValue<'ctx>, PointerValue<'ctx>) {
+        let (location, line) = match self.span {
+            Some(span) if span.file() < self.files.len() => {
+                let file = &self.files[span.file()];
+                let (line, column) = span.location(&file.source);
+                let text = file.source.lines().nth(line - 1).unwrap_or_default();
+                (
+                    format!("{}:{}:{}", file.path.display(), line, column),
+                    format!("{:>5} | {}", line, text.trim()),
+                )
+            }
+            _ => ("<unknown>".to_string(), String::new()),
+        };
+        let location = self
+            .builder
+            .build_global_string_ptr(&location, "panic_location");
+        let line = self.builder.build_global_string_ptr(&line, "panic_line");
+        (location.as_pointer_value(), line.as_pointer_value())
+    }
+
+    /// Element `index` of a raw pointer, which is indexed as an array of pointers like `argv`
+    fn pointer_element(
+        &self,
+        ptr: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> (PointerValue<'ctx>, BasicTypeEnum<'ctx>) {
+        let element_type = self.context.i32_type().ptr_type(AddressSpace::default());
+        let element = unsafe {
+            self.builder
+                .build_gep(element_type, ptr, &[index], "array_indexing")
+        };
+        (element, element_type.into())
+    }
+
+    /// Pointer to element `index` in the heap storage of the `Vec` header at `vec`
+    fn vec_element(
+        &self,
+        vec: PointerValue<'ctx>,
+        element_type: BasicTypeEnum<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let data = self
+            .builder
+            .build_load(
+                self.context.i8_type().ptr_type(AddressSpace::default()),
+                self.vec_field(vec, 0),
+                "data",
+            )
+            .into_pointer_value();
+        self.element_at(data, element_type, index)
+    }
+
+    /// The function an `impl` block declares for `method` on the type of `receiver`, like `Point::len`.
+    /// `declare_functions` records every signature up front, so a method can be called before its
+    /// `impl` block.
+    fn method_function(&self, receiver: &Expr, method: &str) -> Option<String> {
+        match self.static_type(receiver)? {
+            Type::Named(name) => Some(format!("{}::{}", name, method))
+                .filter(|function| self.return_types.contains_key(function)),
+            _ => None,
+        }
+    }
+
+    /// A method of an `impl` block, called with the receiver as its first argument, or
+    /// `len` of an array or `Vec`, and `push` and `pop` on a `let mut` `Vec`
+    fn compile_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        if let Some(function) = self.method_function(receiver, method) {
+            let args = [std::slice::from_ref(receiver), args].concat();
+            return self.compile_expr(&Expr::Call(function, args));
+        }
+        let i64_type = self.context.i64_type();
+        match (method, args) {
+            ("len", []) => match self.compile_expr(receiver)? {
+                AnyValueEnum::ArrayValue(array) => Ok(i64_type
+                    .const_int(array.get_type().len() as u64, false)
+                    .into()),
+                AnyValueEnum::StructValue(vec)
+                    if vec.get_type() == self.vec_type || vec.get_type() == self.slice_type =>
+                {
+                    Ok(self
+                        .builder
+                        .build_extract_value(vec, 1, "len")
+                        .expect("a Vec header or slice has a len field")
+                        .into())
+                }
+                value => Err(anyhow!("No method `len` on {:?}", value)),
+            },
+            ("push", [arg]) => {
+                let (vec, element) = self.vec_slot(receiver, method)?;
+                let element_type = self
+                    .llvm_type(&element)
+                    .ok_or_else(|| anyhow!("A Vec can't hold {}", element))?;
+                let value = self.compile_expr_expecting(arg, Some(&element))?;
+                let value = self.coerce_to_annotation("element", value, Some(&element), arg)?;
+                let value = BasicValueEnum::try_from(value)
+                    .map_err(|_| anyhow!("Cannot push {:?}", value))?;
+                let vec_grow = self
+                    .module
+                    .get_function("__akame_vec_grow")
+                    .expect("vec_grow function not found");
+                let element_size = element_type
+                    .size_of()
+                    .expect("the elements of a Vec are sized");
+                self.builder
+                    .build_call(vec_grow, &[vec.into(), element_size.into()], "grow");
+                let len_slot = self.vec_field(vec, 1);
+                let len = self
+                    .builder
+                    .build_load(i64_type, len_slot, "len")
+                    .into_int_value();
+                let slot = self.vec_element(vec, element_type, len);
+                self.builder.build_store(slot, value);
+                let len = self
+                    .builder
+                    .build_int_add(len, i64_type.const_int(1, false), "len");
+                self.builder.build_store(len_slot, len);
+                Ok(self.unit_value())
+            }
+            ("pop", []) => {
+                let (vec, element) = self.vec_slot(receiver, method)?;
+                let element_type = self
+                    .llvm_type(&element)
+                    .ok_or_else(|| anyhow!("A Vec can't hold {}", element))?;
+                let len_slot = self.vec_field(vec, 1);
+                let len = self
+                    .builder
+                    .build_load(i64_type, len_slot, "len")
+                    .into_int_value();
+                let empty = self.builder.build_int_compare(
+                    IntPredicate::EQ,
+                    len,
+                    i64_type.const_zero(),
+                    "empty",
+                );
+                let function = self.current_function();
+                let empty_block = self.context.append_basic_block(function, "popempty");
+                let pop_block = self.context.append_basic_block(function, "pop");
+                self.builder
+                    .build_conditional_branch(empty, empty_block, pop_block);
+
+                self.builder.position_at_end(empty_block);
+                self.build_panic("Cannot pop from an empty Vec");
+
+                self.builder.position_at_end(pop_block);
+                let len = self
+                    .builder
+                    .build_int_sub(len, i64_type.const_int(1, false), "len");
+                self.builder.build_store(len_slot, len);
+                let slot = self.vec_element(vec, element_type, len);
+                Ok(self.builder.build_load(element_type, slot, "popped").into())
+            }
+            ("len" | "push" | "pop", _) => Err(anyhow!(
+                "Wrong number of arguments to `{}`: {}",
+                method,
+                args.len()
+            )),
+            _ => Err(anyhow!("No method `{}` on `{}`", method, receiver)),
+        }
+    }
+
+    /// `println!` becomes a `printf` call and `format!` a string allocated with `realloc` and
+    /// filled by `snprintf`. The format string gets a conversion for each placeholder from the
+    /// type of its argument.
+    fn compile_macro_call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        let Some((Expr::Str(format), args)) = args.split_first() else {
+            return Err(anyhow!("`{}!` expects a string literal first", name));
+        };
+        let pieces = parse_format_string(format)
+            .map_err(|err| anyhow!("Invalid format string in `{}!`: {}", name, err))?;
+        let mut spec = String::new();
+        let mut values: Vec<BasicMetadataValueEnum<'ctx>> = vec![];
+        let mut args = args.iter();
+        for piece in pieces {
+            match piece {
+                FormatPiece::Text(text) => spec.push_str(&text.replace('%', "%%")),
+                FormatPiece::Placeholder => {
+                    let arg = args
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing argument for `{}!`", name))?;
+                    let (conversion, value) = self.format_arg(arg)?;
+                    spec.push_str(conversion);
+                    values.push(value);
+                }
+            }
+        }
+        if args.next().is_some() {
+            return Err(anyhow!("Too many arguments for `{}!`", name));
+        }
+        match name {
+            "println" => {
+                spec.push('\n');
+                let printf = self
+                    .module
+                    .get_function("printf")
+                    .expect("printf function not found");
+                let spec = self.builder.build_global_string_ptr(&spec, "format_spec");
+                let args = [vec![spec.as_pointer_value().into()], values].concat();
+                self.builder.build_call(printf, &args, "printf_call");
+                Ok(self.unit_value())
+            }
+            "format" => {
+                let snprintf = self
+                    .module
+                    .get_function("snprintf")
+                    .expect("snprintf function not found");
+                let realloc = self
+                    .module
+                    .get_function("realloc")
+                    .expect("realloc function not found");
+                let i64_type = self.context.i64_type();
+                let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+                let spec = self
+                    .builder
+                    .build_global_string_ptr(&spec, "format_spec")
+                    .as_pointer_value();
+                // a first call without a buffer returns the length
+                let measure = [
+                    vec![
+                        i8_ptr_type.const_null().into(),
+                        i64_type.const_zero().into(),
+                        spec.into(),
+                    ],
+                    values.clone(),
+                ]
+                .concat();
+                let len = self
+                    .builder
+                    .build_call(snprintf, &measure, "format_len")
+                    .try_as_basic_value()
+                    .left()
+                    .expect("snprintf returns an int")
+                    .into_int_value();
+                let size = self.builder.build_int_add(
+                    self.coerce_int(len, i64_type, false),
+                    i64_type.const_int(1, false),
+                    "format_size",
+                );
+                let buffer = self
+                    .builder
+                    .build_call(
+                        realloc,
+                        &[i8_ptr_type.const_null().into(), size.into()],
+                        "format_buffer",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .expect("realloc returns a pointer")
+                    .into_pointer_value();
+                let fill = [vec![buffer.into(), size.into(), spec.into()], values].concat();
+                self.builder.build_call(snprintf, &fill, "format_fill");
+                Ok(buffer.into())
+            }
+            _ => Err(anyhow!("Unknown macro: {}!", name)),
+        }
+    }
+
+    /// The `printf` conversion for an argument of `println!` or `format!` and the value passed for
+    /// it. Integers are widened to 64 bits, floats to `f64` and a `bool` becomes `"true"` or `"false"`.
+    /// A `char` is printed as its UTF-8 encoding, like in the interpreter.
+    fn format_arg(
+        &mut self,
+        arg: &Expr,
+    ) -> anyhow::Result<(&'static str, BasicMetadataValueEnum<'ctx>)> {
+        let ty = self.static_type(arg);
+        let i64_type = self.context.i64_type();
+        match self.compile_expr(arg)? {
+            AnyValueEnum::IntValue(value) if value.get_type().get_bit_width() == 1 => {
+                let strings = ["true", "false"].map(|s| {
+                    self.builder
+                        .build_global_string_ptr(s, "bool_string")
+                        .as_pointer_value()
+                });
+                let string =
+                    self.builder
+                        .build_select(value, strings[0], strings[1], "bool_string");
+                Ok(("%s", string.into()))
+            }
+            AnyValueEnum::IntValue(value) if ty == Some(Type::Char) => {
+                Ok(("%s", self.encode_utf8(value).into()))
+            }
+            AnyValueEnum::IntValue(value) => {
+                let unsigned = self.is_unsigned(arg);
+                let value = self.coerce_int(value, i64_type, !unsigned);
+                Ok((if unsigned { "%llu" } else { "%lld" }, value.into()))
+            }
+            AnyValueEnum::FloatValue(value) => {
+                let value = self.coerce_float(value, self.context.f64_type());
+                Ok(("%g", value.into()))
+            }
+            AnyValueEnum::PointerValue(value) => Ok(("%s", value.into())),
+            value => Err(anyhow!("Cannot format {:?}", value.get_type())),
+        }
+    }
+
+    /// Writes the UTF-8 encoding of the code point `c` to a string on the stack
+    fn encode_utf8(&mut self, c: IntValue<'ctx>) -> PointerValue<'ctx> {
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let int = |n: u64| i32_type.const_int(n, false);
+        let buffer = self.build_entry_alloca(i8_type.array_type(5).into(), "utf8");
+        // the number of bytes after the first one
+        let mut continuation = int(0);
+        for limit in [0x80, 0x800, 0x10000] {
+            let above = self
+                .builder
+                .build_int_compare(IntPredicate::UGE, c, int(limit), "above");
+            let above = self.builder.build_int_z_extend(above, i32_type, "above");
+            continuation = self
+                .builder
+                .build_int_add(continuation, above, "continuation");
+        }
+        // the first byte holds the highest bits, after as many 1 bits as the encoding has bytes
+        let ascii = self
+            .builder
+            .build_int_compare(IntPredicate::EQ, continuation, int(0), "ascii");
+        let lead_bits = self
+            .builder
+            .build_int_add(continuation, int(1), "lead_bits");
+        let marker = self
+            .builder
+            .build_right_shift(int(0x100), lead_bits, false, "marker");
+        let marker = self.builder.build_int_sub(int(0x100), marker, "marker");
+        let marker = self
+            .builder
+            .build_select(ascii, int(0), marker, "marker")
+            .into_int_value();
+        let shift = self.builder.build_int_mul(continuation, int(6), "shift");
+        let lead = self.builder.build_right_shift(c, shift, false, "lead");
+        let lead = self.builder.build_or(lead, marker, "lead");
+        let mut bytes = vec![lead];
+        // each following byte holds the next 6 bits, the first one past the end terminates the string
+        for i in 1..=3 {
+            let remaining = self
+                .builder
+                .build_int_sub(continuation, int(i), "remaining");
+            let shift = self.builder.build_int_mul(remaining, int(6), "shift");
+            let bits = self.builder.build_right_shift(c, shift, false, "bits");
+            let bits = self.builder.build_and(bits, int(0x3F), "bits");
+            let byte = self.builder.build_or(bits, int(0x80), "byte");
+            let present =
+                self.builder
+                    .build_int_compare(IntPredicate::ULE, int(i), continuation, "present");
+            let byte = self
+                .builder
+                .build_select(present, byte, int(0), "byte")
+                .into_int_value();
+            bytes.push(byte);
+        }
+        bytes.push(int(0));
+        for (i, byte) in bytes.into_iter().enumerate() {
+            let byte = self.builder.build_int_truncate(byte, i8_type, "byte");
+            let slot = unsafe {
+                self.builder.build_in_bounds_gep(
+                    i8_type,
+                    buffer,
+                    &[self.context.i64_type().const_int(i as u64, false)],
+                    "utf8_byte",
+                )
+            };
+            self.builder.build_store(slot, byte);
+        }
+        buffer
+    }
+
+    /// The stack slot of the `let mut` `Vec` a modifying method is called on, with its element type
+    fn vec_slot(
+        &self,
+        receiver: &Expr,
+        method: &str,
+    ) -> anyhow::Result<(PointerValue<'ctx>, Type)> {
+        let Expr::Ident(name) = receiver else {
+            return Err(anyhow!("`{}` can only be called on a variable", method));
+        };
+        match (self.variables.get(name), self.variable_types.get(name)) {
+            (Some(VariableValue::Mutable(slot, _)), Some(Type::Vec(element))) => {
+                Ok((*slot, (**element).clone()))
+            }
+            (Some(VariableValue::Mutable(..)), _) => {
+                Err(anyhow!("No method `{}` on `{}`", method, name))
+            }
+            (Some(_), _) => Err(anyhow!(
+                "Cannot call `{}` on `{}`, it is not declared as mutable",
+                method,
+                name
+            )),
+            (None, _) => Err(anyhow!("Undefined variable: {}", name)),
+        }
+    }
+
+    /// Builds the anonymous struct of a tuple literal, converting each element to its type in `expected`
+    fn compile_tuple(
+        &mut self,
+        elements: &[Expr],
+        expected: Option<&Type>,
+    ) -> anyhow::Result<StructValue<'ctx>> {
+        let expected = match expected {
+            Some(Type::Tuple(types)) if types.len() == elements.len() => Some(types),
+            _ => None,
+        };
+        let mut values = Vec::new();
+        for (index, element) in elements.iter().enumerate() {
+            let ty = expected.map(|types| &types[index]);
+            let value = self.compile_expr_expecting(element, ty)?;
+            let value = self.coerce_to_annotation(&index.to_string(), value, ty, element)?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("Tuple element {} can't hold {:?}", index, value))?;
+            values.push(value);
+        }
+        let types = values
+            .iter()
+            .map(|value| value.get_type())
+            .collect::<Vec<_>>();
+        let mut tuple = self.context.struct_type(&types, false).get_undef();
+        for (index, value) in values.into_iter().enumerate() {
+            tuple = self
+                .builder
+                .build_insert_value(tuple, value, index as u32, "tuple")
+                .ok_or_else(|| anyhow!("Invalid tuple index {}", index))?
+                .into_struct_value();
+        }
+        Ok(tuple)
+    }
+
+    /// Fills a stack slot field by field with `build_struct_gep` and loads the finished struct.
+    /// Fields are evaluated in the order they are written.
+    fn compile_struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[(String, Expr)],
+    ) -> anyhow::Result<StructValue<'ctx>> {
+        let (struct_type, declared) = self
+            .struct_types
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Undefined struct: {}", name))?;
+        if let Some(missing) = declared
+            .iter()
+            .find(|declared| !fields.iter().any(|(field, _)| *field == declared.ident))
+        {
+            return Err(anyhow!(
+                "Missing field `{}` in `{}` literal",
+                missing.ident,
+                name
+            ));
+        }
+        let slot = self.build_entry_alloca(struct_type.into(), name);
+        for (field, expr) in fields {
+            let (index, ty) = self.field_index(struct_type, field)?;
+            let value = self.compile_expr(expr)?;
+            let value = self.coerce_to_annotation(field, value, ty.as_ref(), expr)?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("Field `{}` can't hold {:?}", field, value))?;
+            let field_slot = self
+                .builder
+                .build_struct_gep(struct_type, slot, index, field)
+                .map_err(|_| anyhow!("Invalid field index {} of `{}`", index, name))?;
+            self.builder.build_store(field_slot, value);
+        }
+        Ok(self
+            .builder
+            .build_load(struct_type, slot, name)
+            .into_struct_value())
+    }
+
+    /// Reads a field straight out of the stack slot of a `let mut` struct, or extracts it from a struct value
+    fn compile_field_access(
+        &mut self,
+        operand: &Expr,
+        field: &str,
+    ) -> anyhow::Result<BasicValueEnum<'ctx>> {
+        if let Expr::Ident(name) = operand {
+            if let Some(VariableValue::Mutable(slot, BasicTypeEnum::StructType(struct_type))) =
+                self.variables.get(name)
+            {
+                let (slot, struct_type) = (*slot, *struct_type);
+                let (index, _) = self.field_index(struct_type, field)?;
+                let field_slot = self
+                    .builder
+                    .build_struct_gep(struct_type, slot, index, field)
+                    .map_err(|_| anyhow!("Invalid field index {} of `{}`", index, name))?;
+                let field_type = struct_type
+                    .get_field_type_at_index(index)
+                    .expect("field index comes from the struct's declaration");
+                return Ok(self.builder.build_load(field_type, field_slot, field));
+            }
+        }
+        match self.compile_expr(operand)? {
+            AnyValueEnum::StructValue(value) => {
+                let (index, _) = self.field_index(value.get_type(), field)?;
+                self.builder
+                    .build_extract_value(value, index, field)
+                    .ok_or_else(|| anyhow!("Invalid field index {} of {:?}", index, value))
+            }
+            value => Err(anyhow!("Cannot access field `{}` on {:?}", field, value)),
+        }
+    }
+
+    /// Stores the tag and then the payload fields into a stack slot and loads the finished enum
+    fn compile_enum_variant(
+        &mut self,
+        enum_name: &str,
+        variant: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<StructValue<'ctx>> {
+        let (layout, tag, declared, payload_type) = self.enum_variant(enum_name, variant)?;
+        if declared.fields.len() != args.len() {
+            return Err(anyhow!(
+                "`{}::{}` takes {} value(s) but {} were given",
+                enum_name,
+                variant,
+                declared.fields.len(),
+                args.len()
+            ));
+        }
+        let slot = self.build_entry_alloca(layout.ty.into(), enum_name);
+        let tag_slot = self
+            .builder
+            .build_struct_gep(layout.ty, slot, 0, "tag")
+            .map_err(|_| anyhow!("`{}` has no tag", enum_name))?;
+        self.builder
+            .build_store(tag_slot, self.context.i32_type().const_int(tag, false));
+        let payload_slot = self
+            .builder
+            .build_struct_gep(layout.ty, slot, 1, "payload")
+            .map_err(|_| anyhow!("`{}` has no payload", enum_name))?;
+        for (index, (arg, ty)) in args.iter().zip(&declared.fields).enumerate() {
+            let value = self.compile_expr(arg)?;
+            let value = self.coerce_to_annotation(variant, value, Some(ty), arg)?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("`{}::{}` can't hold {:?}", enum_name, variant, value))?;
+            let field_slot = self
+                .builder
+                .build_struct_gep(payload_type, payload_slot, index as u32, variant)
+                .map_err(|_| anyhow!("Invalid payload index {} of `{}`", index, variant))?;
+            self.builder.build_store(field_slot, value);
+        }
+        Ok(self
+            .builder
+            .build_load(layout.ty, slot, enum_name)
+            .into_struct_value())
+    }
+
+    /// Lowers `match` on an enum to a `switch` on its tag with a block per variant, which tries the
+    /// arms that can match that variant in order. Matches on other types try every arm in order.
+    /// The arm bodies rejoin in `matchcont`, like the branches of `compile_if`.
+    fn compile_match(
+        &mut self,
+        scrutinee: &Expr,
+        arms: &[MatchArm],
+    ) -> anyhow::Result<Option<AnyValueEnum<'ctx>>> {
+        let value = self.compile_expr(scrutinee)?;
+        let value =
+            BasicValueEnum::try_from(value).map_err(|_| anyhow!("Cannot match on {:?}", value))?;
+        let function = self.current_function();
+        let mut context = MatchContext {
+            body_blocks: vec![None; arms.len()],
+            incoming: Vec::new(),
+            merge_block: self.context.append_basic_block(function, "matchcont"),
+        };
+        let layout = match value {
+            BasicValueEnum::StructValue(value) => self
+                .enum_name(value.get_type())
+                .map(|name| (name.to_owned(), self.enum_types[name].clone())),
+            _ => None,
+        };
+        if let Some((enum_name, layout)) = layout {
+            let slot = self.build_entry_alloca(layout.ty.into(), "scrutinee");
+            self.builder.build_store(slot, value);
+            let tag_slot = self
+                .builder
+                .build_struct_gep(layout.ty, slot, 0, "tag")
+                .map_err(|_| anyhow!("`{}` has no tag", enum_name))?;
+            let tag = self
+                .builder
+                .build_load(self.context.i32_type(), tag_slot, "tag")
+                .into_int_value();
+            let cases = layout
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(tag, (variant, _))| {
+                    let name = format!("{}::{}", enum_name, variant.ident);
+                    (
+                        self.context.i32_type().const_int(tag as u64, false),
+                        self.context.append_basic_block(function, &name),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let no_match = self.context.append_basic_block(function, "nomatch");
+            self.builder.build_switch(tag, no_match, &cases);
+            self.builder.position_at_end(no_match);
+            self.builder.build_unreachable();
+
+            for ((_, block), (variant, payload_type)) in cases.iter().zip(&layout.variants) {
+                self.builder.position_at_end(*block);
+                // the tag already rules out the arms for other variants
+                let candidates = arms
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, arm)| match &arm.pattern {
+                        Pattern::Variant { variant: name, .. } => *name == variant.ident,
+                        _ => true,
+                    });
+                for (index, arm) in candidates {
+                    let next_arm = self.context.append_basic_block(function, "nextarm");
+                    let mut bindings = Vec::new();
+                    match &arm.pattern {
+                        Pattern::Variant { fields, .. } => self.compile_payload_patterns(
+                            (layout.ty, slot, *payload_type),
+                            &variant.fields,
+                            fields,
+                            next_arm,
+                            &mut bindings,
+                        )?,
+                        pattern => {
+                            self.compile_pattern(pattern, value, None, next_arm, &mut bindings)?
+                        }
+                    }
+                    self.compile_arm(index, arm, scrutinee, &bindings, next_arm, &mut context)?;
+                }
+                // the type checker makes sure some arm matches every value
+                self.builder.build_unreachable();
+            }
+        } else {
+            let ty = self.static_type(scrutinee);
+            for (index, arm) in arms.iter().enumerate() {
+                let next_arm = self.context.append_basic_block(function, "nextarm");
+                let mut bindings = Vec::new();
+                self.compile_pattern(&arm.pattern, value, ty.as_ref(), next_arm, &mut bindings)?;
+                self.compile_arm(index, arm, scrutinee, &bindings, next_arm, &mut context)?;
+            }
+            self.builder.build_unreachable();
+        }
+
+        self.builder.position_at_end(context.merge_block);
+        if context.incoming.is_empty() {
+            // every arm returned, nothing can reach the merge block
+            self.builder.build_unreachable();
+            return Ok(None);
+        }
+        let mut incoming: Vec<(BasicValueEnum<'ctx>, BasicBlock<'ctx>)> = Vec::new();
+        for (value, end) in context.incoming {
+            match value.and_then(|value| BasicValueEnum::try_from(value).ok()) {
+                Some(value) => incoming.push((value, end)),
+                None => return Ok(None),
+            }
+        }
+        let phi_type = incoming[0].0.get_type();
+        if incoming
+            .iter()
+            .any(|(value, _)| value.get_type() != phi_type)
+        {
+            return Ok(None);
+        }
+        let phi = self.builder.build_phi(phi_type, "matchtmp");
+        for (value, block) in &incoming {
+            phi.add_incoming(&[(value, *block)]);
+        }
+        Ok(Some(phi.as_basic_value().into()))
+    }
+
+    /// Runs an arm whose pattern matched: checks its guard and jumps to its body, which is compiled
+    /// the first time it's reached. Leaves the builder in `next_arm`, where the following arm is tried.
+    fn compile_arm(
+        &mut self,
+        index: usize,
+        arm: &MatchArm,
+        scrutinee: &Expr,
+        bindings: &[PatternBinding<'ctx>],
+        next_arm: BasicBlock<'ctx>,
+        context: &mut MatchContext<'ctx>,
+    ) -> anyhow::Result<()> {
+        let outer_variables = self.variables.clone();
+        let outer_types = self.variable_types.clone();
+        self.bind_pattern(bindings, scrutinee)?;
+        if let Some(guard) = &arm.guard {
+            let guard = self.compile_expr(guard)?;
+            let guard = self.build_condition(guard)?;
+            self.branch_if(guard, next_arm);
+        }
+        let body_block = match context.body_blocks[index] {
+            Some(block) => block,
+            None => {
+                let matched_block = self
+                    .builder
+                    .get_insert_block()
+                    .expect("Not positioned inside a function");
+                let block = self
+                    .context
+                    .append_basic_block(self.current_function(), "arm");
+                self.builder.position_at_end(block);
+                let value = self.compile_scoped_block(&arm.body)?;
+                if let Some(end) = self.branch_to(context.merge_block) {
+                    context.incoming.push((value, end));
+                }
+                self.builder.position_at_end(matched_block);
+                context.body_blocks[index] = Some(block);
+                block
+            }
+        };
+        self.builder.build_unconditional_branch(body_block);
+        self.variables = outer_variables;
+        self.variable_types = outer_types;
+        self.builder.position_at_end(next_arm);
+        Ok(())
+    }
+
+    /// Emits the tests of `pattern` against `value`, branching to `unmatched` as soon as one fails.
+    /// Leaves the builder in a block that is only reached when the whole pattern matched.
+    fn compile_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: BasicValueEnum<'ctx>,
+        ty: Option<&Type>,
+        unmatched: BasicBlock<'ctx>,
+        bindings: &mut Vec<PatternBinding<'ctx>>,
+    ) -> anyhow::Result<()> {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(name) => bindings.push((name.clone(), value, ty.cloned())),
+            Pattern::Literal(literal) => {
+                let matched = self.build_literal_test(literal, value)?;
+                self.branch_if(matched, unmatched);
+            }
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let (layout, tag, declared, payload_type) =
+                    self.enum_variant(enum_name, variant)?;
+                let slot = self.build_entry_alloca(layout.ty.into(), enum_name);
+                self.builder.build_store(slot, value);
+                let tag_slot = self
+                    .builder
+                    .build_struct_gep(layout.ty, slot, 0, "tag")
+                    .map_err(|_| anyhow!("`{}` has no tag", enum_name))?;
+                let actual = self
+                    .builder
+                    .build_load(self.context.i32_type(), tag_slot, "tag")
+                    .into_int_value();
+                let expected = self.context.i32_type().const_int(tag, false);
+                let matched =
+                    self.builder
+                        .build_int_compare(IntPredicate::EQ, actual, expected, variant);
+                self.branch_if(matched, unmatched);
+                self.compile_payload_patterns(
+                    (layout.ty, slot, payload_type),
+                    &declared.fields,
+                    fields,
+                    unmatched,
+                    bindings,
+                )?;
+            }
+            Pattern::Tuple(elements) => {
+                let BasicValueEnum::StructValue(tuple) = value else {
+                    return Err(anyhow!("Cannot match {:?} against a tuple pattern", value));
+                };
+                for (index, element) in elements.iter().enumerate() {
+                    if *element == Pattern::Wildcard {
+                        continue;
+                    }
+                    let element_value = self
+                        .builder
+                        .build_extract_value(tuple, index as u32, "element")
+                        .ok_or_else(|| anyhow!("{:?} has no element {}", tuple, index))?;
+                    let element_type = match ty {
+                        Some(Type::Tuple(types)) => types.get(index),
+                        _ => None,
+                    };
+                    self.compile_pattern(
+                        element,
+                        element_value,
+                        element_type,
+                        unmatched,
+                        bindings,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Matches the payload fields of the enum in `slot` against their patterns, once its tag matched
+    fn compile_payload_patterns(
+        &mut self,
+        (enum_type, slot, payload_type): (StructType<'ctx>, PointerValue<'ctx>, StructType<'ctx>),
+        field_types: &[Type],
+        patterns: &[Pattern],
+        unmatched: BasicBlock<'ctx>,
+        bindings: &mut Vec<PatternBinding<'ctx>>,
+    ) -> anyhow::Result<()> {
+        for (index, (pattern, ty)) in patterns.iter().zip(field_types).enumerate() {
+            if *pattern == Pattern::Wildcard {
+                continue;
+            }
+            let payload_slot = self
+                .builder
+                .build_struct_gep(enum_type, slot, 1, "payload")
+                .map_err(|_| anyhow!("{:?} has no payload", enum_type))?;
+            let field_slot = self
+                .builder
+                .build_struct_gep(payload_type, payload_slot, index as u32, "field")
+                .map_err(|_| anyhow!("Invalid payload index {} of {:?}", index, payload_type))?;
+            let field_type = payload_type
+                .get_field_type_at_index(index as u32)
+                .expect("payload index comes from the variant's declaration");
+            let field = self.builder.build_load(field_type, field_slot, "field");
+            self.compile_pattern(pattern, field, Some(ty), unmatched, bindings)?;
+        }
+        Ok(())
+    }
+
+    /// `value == literal` for a literal pattern, strings are compared by content
+    fn build_literal_test(
+        &mut self,
+        literal: &Expr,
+        value: BasicValueEnum<'ctx>,
+    ) -> anyhow::Result<IntValue<'ctx>> {
+        match (value, self.compile_expr(literal)?) {
+            (BasicValueEnum::IntValue(value), AnyValueEnum::IntValue(literal)) => {
+                let literal = self.coerce_int(literal, value.get_type(), true);
+                Ok(self
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, value, literal, "pattern"))
+            }
+            (BasicValueEnum::FloatValue(value), AnyValueEnum::FloatValue(literal)) => {
+                let literal = self.coerce_float(literal, value.get_type());
+                Ok(self
+                    .builder
+                    .build_float_compare(FloatPredicate::OEQ, value, literal, "pattern"))
+            }
+            (BasicValueEnum::PointerValue(value), AnyValueEnum::PointerValue(literal)) => {
+                let strcmp = self
+                    .module
+                    .get_function("strcmp")
+                    .expect("strcmp function not found");
+                let order = self
+                    .builder
+                    .build_call(strcmp, &[value.into(), literal.into()], "strcmp")
+                    .try_as_basic_value()
+                    .left()
+                    .expect("strcmp returns an i32")
+                    .into_int_value();
+                Ok(self.builder.build_int_compare(
+                    IntPredicate::EQ,
+                    order,
+                    self.context.i32_type().const_zero(),
+                    "pattern",
+                ))
+            }
+            (value, _) => Err(anyhow!(
+                "Cannot match {:?} against the pattern {:?}",
+                value,
+                literal
+            )),
+        }
+    }
+
+    /// Continues in a new block when `condition` holds, otherwise branches to `otherwise`
+    fn branch_if(&self, condition: IntValue<'ctx>, otherwise: BasicBlock<'ctx>) {
+        let matched = self
+            .context
+            .append_basic_block(self.current_function(), "matched");
+        self.builder
+            .build_conditional_branch(condition, matched, otherwise);
+        self.builder.position_at_end(matched);
+    }
+
+    /// Brings the bindings of a matched pattern into scope as immutable variables
+    fn bind_pattern(
+        &mut self,
+        bindings: &[PatternBinding<'ctx>],
+        scrutinee: &Expr,
+    ) -> anyhow::Result<()> {
+        for (name, value, ty) in bindings {
+            self.bind_type(name, ty.as_ref(), (*value).into(), scrutinee);
+            let value = match *value {
+                BasicValueEnum::IntValue(value) => VariableValue::Int(value),
+                BasicValueEnum::FloatValue(value) => VariableValue::Float(value),
+                BasicValueEnum::PointerValue(value) => VariableValue::Ptr(value),
+                BasicValueEnum::StructValue(value) => VariableValue::Struct(value),
+                BasicValueEnum::ArrayValue(value) => VariableValue::Array(value),
+                value => return Err(anyhow!("Cannot bind `{}` to {:?}", name, value)),
+            };
+            self.variables.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+
+    /// Adds a function with the LLVM signature of its annotations and records its types
+    fn declare_function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        return_type: Option<&Type>,
+    ) -> anyhow::Result<FunctionValue<'ctx>> {
+        let param_types = params
+            .iter()
+            .map(|param| {
+                let ty = param.ty.as_ref().unwrap_or(&Type::I32);
+                self.llvm_type(ty)
+                    .map(|ty| ty.into())
+                    .ok_or_else(|| anyhow!("Parameter `{}` can't have type {}", param.ident, ty))
+            })
+            .collect::<anyhow::Result<Vec<BasicMetadataTypeEnum<'ctx>>>>()?;
+        let return_type = return_type.cloned().unwrap_or(Type::I32);
+        let fn_type = match self.llvm_type(&return_type) {
+            Some(ty) => ty.fn_type(&param_types, false),
+            None => self.context.void_type().fn_type(&param_types, false),
+        };
+        let function = self.module.add_function(name, fn_type, None);
+        self.return_types.insert(name.to_owned(), return_type);
+        self.param_types.insert(
+            name.to_owned(),
+            params
+                .iter()
+                .map(|param| param.ty.clone().unwrap_or(Type::I32))
+                .collect(),
+        );
+        Ok(function)
+    }
+
+    /// Compiles the body of a function, declaring it first unless `declare_functions` did
+    fn compile_function(
+        &mut self,
+        name: &str,
+        params: &[Param],
+        return_type: Option<&Type>,
+        body: &[Stmt],
+    ) -> anyhow::Result<()> {
+        let function = match self.module.get_function(name) {
+            Some(function) => function,
+            None => self.declare_function(name, params, return_type)?,
+        };
+        let params = params
+            .iter()
+            .map(|param| {
+                let ty = param.ty.clone().unwrap_or(Type::I32);
+                (param.ident.clone(), ty)
+            })
+            .collect::<Vec<_>>();
+        let return_type = return_type.cloned().unwrap_or(Type::I32);
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        self.compile_function_body(function, &params, 0, &return_type, body)
+    }
+
+    /// Binds the parameters of `function`, starting at `first_param`, and compiles its body
+    fn compile_function_body(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        params: &[(String, Type)],
+        first_param: u32,
+        return_type: &Type,
+        body: &[Stmt],
+    ) -> anyhow::Result<()> {
+        for (i, (ident, ty)) in params.iter().enumerate() {
+            let value = match function.get_nth_param(first_param + i as u32).unwrap() {
+                BasicValueEnum::IntValue(value) => VariableValue::Int(value),
+                BasicValueEnum::FloatValue(value) => VariableValue::Float(value),
+                BasicValueEnum::PointerValue(value) => VariableValue::Ptr(value),
+                BasicValueEnum::StructValue(value) => VariableValue::Struct(value),
+                BasicValueEnum::ArrayValue(value) => VariableValue::Array(value),
+                value => return Err(anyhow!("Unsupported parameter type: {:?}", value)),
+            };
+            self.variables.insert(ident.clone(), value);
+            self.variable_types.insert(ident.clone(), ty.clone());
+        }
+
+        // a trailing closure takes its types from the return type
+        let tail_value = match body.split_last() {
+            Some((Stmt::Expression(tail, span), stmts)) => {
+                self.compile_block(stmts)?;
+                if self.current_block_terminated() {
+                    None
+                } else {
+                    self.span = Some(*span);
+                    Some(self.compile_expr_expecting(tail, Some(return_type))?)
+                }
+            }
+            _ => self.compile_block(body)?,
+        };
+        let tail_signed = match body.last() {
+            Some(Stmt::Expression(expr, _)) => !self.is_unsigned(expr),
+            _ => true,
+        };
+
+        // without an explicit return, a trailing expression of the right type is the result, otherwise zero
+        if !self.current_block_terminated() {
+            match function.get_type().get_return_type() {
+                None => {
+                    self.builder.build_return(None);
+                }
+                Some(BasicTypeEnum::IntType(int_type)) => {
+                    let ret = match tail_value {
+                        Some(AnyValueEnum::IntValue(value)) => {
+                            self.coerce_int(value, int_type, tail_signed)
+                        }
+                        _ => int_type.const_int(0, false),
+                    };
+                    self.builder.build_return(Some(&ret));
+                }
+                Some(BasicTypeEnum::FloatType(float_type)) => {
+                    let ret = match tail_value {
+                        Some(AnyValueEnum::FloatValue(value)) => {
+                            self.coerce_float(value, float_type)
+                        }
+                        _ => float_type.const_zero(),
+                    };
+                    self.builder.build_return(Some(&ret));
+                }
+                Some(ret_type) => {
+                    let ret = tail_value
+                        .and_then(|value| BasicValueEnum::try_from(value).ok())
+                        .filter(|value| value.get_type() == ret_type)
+                        .unwrap_or_else(|| ret_type.const_zero());
+                    self.builder.build_return(Some(&ret));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A closure is a `{ ptr code, ptr env }` pair. The code takes the environment as its first parameter.
+    fn closure_type(&self) -> StructType<'ctx> {
+        let ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        self.context
+            .struct_type(&[ptr_type.into(), ptr_type.into()], false)
+    }
+
+    /// LLVM signature of the code of a closure of type `fn(params) -> return_type`
+    fn closure_fn_type(
+        &self,
+        params: &[Type],
+        return_type: &Type,
+    ) -> anyhow::Result<FunctionType<'ctx>> {
+        let env_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let param_types = std::iter::once(Ok(env_type.into()))
+            .chain(params.iter().map(|ty| {
+                self.llvm_type(ty)
+                    .map(|ty| ty.into())
+                    .ok_or_else(|| anyhow!("A closure parameter can't have type {}", ty))
+            }))
+            .collect::<anyhow::Result<Vec<BasicMetadataTypeEnum<'ctx>>>>()?;
+        Ok(match self.llvm_type(return_type) {
+            Some(ty) => ty.fn_type(&param_types, false),
+            None => self.context.void_type().fn_type(&param_types, false),
+        })
+    }
+
+    /// Parameter and return types of a closure: its annotations, then the function type expected where it's used, then `i32`
+    fn closure_signature(
+        params: &[Param],
+        return_type: Option<&Type>,
+        expected: Option<&Type>,
+    ) -> (Vec<Type>, Type) {
+        let (expected_params, expected_return) = match expected {
+            Some(Type::Function(expected_params, expected_return))
+                if expected_params.len() == params.len() =>
+            {
+                (Some(expected_params), Some(&**expected_return))
+            }
+            _ => (None, None),
+        };
+        let param_types = params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| match (&param.ty, expected_params) {
+                (Some(ty), _) => ty.clone(),
+                (None, Some(expected_params)) => expected_params[i].clone(),
+                (None, None) => Type::I32,
+            })
+            .collect();
+        let return_type = return_type
+            .or(expected_return)
+            .cloned()
+            .unwrap_or(Type::I32);
+        (param_types, return_type)
+    }
+
+    /// The function type of a closure or function value, when it's known without compiling it
+    fn function_type_of(&self, expr: &Expr) -> Option<Type> {
+        let ty = match expr {
+            Expr::Closure {
+                params,
+                return_type,
+                ..
+            } => {
+                let (params, return_type) =
+                    Self::closure_signature(params, return_type.as_ref(), None);
+                Type::Function(params, Box::new(return_type))
+            }
+            Expr::Ident(name) if !self.variables.contains_key(name) => Type::Function(
+                self.param_types.get(name)?.clone(),
+                Box::new(self.return_types.get(name)?.clone()),
+            ),
+            Expr::Ident(name) => self.variable_types.get(name)?.clone(),
+            Expr::Call(name, _) => match self.variable_types.get(name) {
+                Some(Type::Function(_, return_type)) if self.variables.contains_key(name) => {
+                    (**return_type).clone()
+                }
+                _ => self.return_types.get(name)?.clone(),
+            },
+            _ => return None,
+        };
+        matches!(ty, Type::Function(..)).then_some(ty)
+    }
+
+    /// Compiles the body of a closure into its own function and pairs it with a heap allocated
+    /// copy of the variables it refers to. Closures capture by value, so they can outlive the
+    /// function that created them. Globals aren't captured, a `static mut` is shared.
+    fn compile_closure(
+        &mut self,
+        params: &[Param],
+        return_type: Option<&Type>,
+        body: &[Stmt],
+        expected: Option<&Type>,
+    ) -> anyhow::Result<StructValue<'ctx>> {
+        let (param_types, return_type) = Self::closure_signature(params, return_type, expected);
+        let fn_type = self.closure_fn_type(&param_types, &return_type)?;
+
+        let mut names = std::collections::BTreeSet::new();
+        referenced_names(body, &mut names);
+        let mut captured = Vec::new();
+        for name in names {
+            let is_global = self
+                .globals
+                .get(&name)
+                .is_some_and(|global| self.variables.get(&name) == Some(global));
+            if !self.variables.contains_key(&name)
+                || is_global
+                || params.iter().any(|param| param.ident == name)
+            {
+                continue;
+            }
+            let value = self.compile_expr(&Expr::Ident(name.clone()))?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("Cannot capture `{}` in a closure", name))?;
+            let ty = self.variable_types.get(&name).cloned();
+            captured.push((name, value, ty));
+        }
+        let env_type = self.context.struct_type(
+            &captured
+                .iter()
+                .map(|(_, value, _)| value.get_type())
+                .collect::<Vec<_>>(),
+            false,
+        );
+        let env = if captured.is_empty() {
+            self.context
+                .i8_type()
+                .ptr_type(AddressSpace::default())
+                .const_null()
+        } else {
+            let env = self
+                .builder
+                .build_malloc(env_type, "env")
+                .map_err(|err| anyhow!("Cannot allocate a closure environment: {}", err))?;
+            for (index, (name, value, _)) in captured.iter().enumerate() {
+                let slot = self
+                    .builder
+                    .build_struct_gep(env_type, env, index as u32, name)
+                    .map_err(|_| anyhow!("Invalid capture index {}", index))?;
+                self.builder.build_store(slot, *value);
+            }
+            env
+        };
+
+        let function = self.module.add_function("closure", fn_type, None);
+        let outer_block = self
+            .builder
+            .get_insert_block()
+            .expect("Not positioned inside a function");
+        let outer_variables = self.variables.clone();
+        let outer_types = self.variable_types.clone();
+        let outer_loops = std::mem::take(&mut self.loops);
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let env_param = function
+            .get_nth_param(0)
+            .expect("closures take their environment first")
+            .into_pointer_value();
+        let mut bindings = Vec::new();
+        for (index, (name, value, ty)) in captured.iter().enumerate() {
+            let slot = self
+                .builder
+                .build_struct_gep(env_type, env_param, index as u32, name)
+                .map_err(|_| anyhow!("Invalid capture index {}", index))?;
+            let value = self.builder.build_load(value.get_type(), slot, name);
+            bindings.push((name.clone(), value, ty.clone()));
+        }
+        self.bind_pattern(&bindings, &Expr::Bool(false))?;
+        for (name, _, ty) in &bindings {
+            if ty.is_none() {
+                self.variable_types.remove(name);
+            }
+        }
+        let params = params
+            .iter()
+            .zip(&param_types)
+            .map(|(param, ty)| (param.ident.clone(), ty.clone()))
+            .collect::<Vec<_>>();
+        let compiled = self.compile_function_body(function, &params, 1, &return_type, body);
+
+        self.variables = outer_variables;
+        self.variable_types = outer_types;
+        self.loops = outer_loops;
+        self.builder.position_at_end(outer_block);
+        compiled?;
+        Ok(self.build_closure(function, env))
+    }
+
+    /// Pairs the code of a closure with its environment
+    fn build_closure(
+        &self,
+        function: FunctionValue<'ctx>,
+        env: PointerValue<'ctx>,
+    ) -> StructValue<'ctx> {
+        let closure = self.closure_type().get_undef();
+        let code = function.as_global_value().as_pointer_value();
+        let closure = self
+            .builder
+            .build_insert_value(closure, code, 0, "closure")
+            .expect("closures have a code field")
+            .into_struct_value();
+        self.builder
+            .build_insert_value(closure, env, 1, "closure")
+            .expect("closures have an environment field")
+            .into_struct_value()
+    }
+
+    /// A closure for a declared function, its code is a wrapper that ignores the environment
+    fn function_closure(&mut self, function: FunctionValue<'ctx>) -> StructValue<'ctx> {
+        let name = format!("{}.closure", function.get_name().to_string_lossy());
+        let wrapper = self.module.get_function(&name).unwrap_or_else(|| {
+            let fn_type = function.get_type();
+            let env_type = self.context.i8_type().ptr_type(AddressSpace::default());
+            let param_types = std::iter::once(env_type.into())
+                .chain(fn_type.get_param_types().into_iter().map(|ty| ty.into()))
+                .collect::<Vec<BasicMetadataTypeEnum<'ctx>>>();
+            let wrapper_type = match fn_type.get_return_type() {
+                Some(ty) => ty.fn_type(&param_types, false),
+                None => self.context.void_type().fn_type(&param_types, false),
+            };
+            let wrapper = self.module.add_function(&name, wrapper_type, None);
+            let builder = self.context.create_builder();
+            builder.position_at_end(self.context.append_basic_block(wrapper, "entry"));
+            let args = wrapper
+                .get_params()
+                .into_iter()
+                .skip(1)
+                .map(|arg| arg.into())
+                .collect::<Vec<_>>();
+            let result = builder.build_call(function, &args, "call");
+            match result.try_as_basic_value().left() {
+                Some(value) => builder.build_return(Some(&value)),
+                None => builder.build_return(None),
+            };
+            wrapper
+        });
+        let env = self
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::default())
+            .const_null();
+        self.build_closure(wrapper, env)
+    }
+
+    /// Calls the closure held in the variable `name` through its code pointer, passing its environment first
+    fn compile_closure_call(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        let Some(Type::Function(params, return_type)) = self.variable_types.get(name).cloned()
+        else {
+            return Err(anyhow!("Cannot call `{}`, it isn't a function", name));
+        };
+        if params.len() != args.len() {
+            return Err(anyhow!(
+                "`{}` takes {} argument(s) but {} were given",
+                name,
+                params.len(),
+                args.len()
+            ));
+        }
+        let closure = match self.compile_expr(&Expr::Ident(name.to_owned()))? {
+            AnyValueEnum::StructValue(closure) => closure,
+            value => return Err(anyhow!("Cannot call `{}`: {:?}", name, value)),
+        };
+        let code = self
+            .builder
+            .build_extract_value(closure, 0, "code")
+            .ok_or_else(|| anyhow!("`{}` has no code pointer", name))?
+            .into_pointer_value();
+        let env = self
+            .builder
+            .build_extract_value(closure, 1, "env")
+            .ok_or_else(|| anyhow!("`{}` has no environment", name))?;
+        let mut call_args: Vec<inkwell::values::BasicMetadataValueEnum> = vec![env.into()];
+        for (arg, ty) in args.iter().zip(&params) {
+            let value = self.compile_expr_expecting(arg, Some(ty))?;
+            let value = self.coerce_to_annotation(name, value, Some(ty), arg)?;
+            let value = BasicValueEnum::try_from(value)
+                .map_err(|_| anyhow!("Cannot pass {:?} to `{}`", value, name))?;
+            call_args.push(value.into());
+        }
+        let fn_type = self.closure_fn_type(&params, &return_type)?;
+        let result = self
+            .builder
+            .build_indirect_call(fn_type, code, &call_args, "calltmp");
+        Ok(match result.try_as_basic_value().left() {
+            Some(value) => value.into(),
+            // a void closure, like a void function call
+            None => AnyValueEnum::PointerValue(
+                self.context
+                    .i32_type()
+                    .ptr_type(AddressSpace::default())
+                    .const_zero(),
+            ),
+        })
+    }
+
+    /// LLVM type for an annotation, `None` for `()` and undeclared structs and enums.
+    /// Tuples are anonymous structs, so two tuples of the same element types share their LLVM type.
+    /// Every `Vec` has the same header, whatever its element type.
+    fn llvm_type(&self, ty: &Type) -> Option<BasicTypeEnum<'ctx>> {
+        match ty {
+            Type::Int { bits, .. } => Some(self.context.custom_width_int_type(*bits).into()),
+            Type::Float { bits: 32 } => Some(self.context.f32_type().into()),
+            Type::Float { .. } => Some(self.context.f64_type().into()),
+            Type::Bool => Some(self.context.bool_type().into()),
+            Type::Char => Some(self.context.i32_type().into()),
+            Type::Str | Type::Ptr(_) => Some(
+                self.context
+                    .i8_type()
+                    .ptr_type(AddressSpace::default())
+                    .into(),
+            ),
+            Type::Unit => None,
+            Type::Function(..) => Some(self.closure_type().into()),
+            Type::Tuple(types) => {
+                let types = types
+                    .iter()
+                    .map(|ty| self.llvm_type(ty))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(self.context.struct_type(&types, false).into())
+            }
+            Type::Array(ty, len) => Some(self.llvm_type(ty)?.array_type(*len as u32).into()),
+            Type::Vec(_) => Some(self.vec_type.into()),
+            Type::Slice(_) => Some(self.slice_type.into()),
+            Type::Named(name) => match self.struct_types.get(name) {
+                Some((ty, _)) => Some((*ty).into()),
+                None => self.enum_types.get(name).map(|layout| layout.ty.into()),
+            },
+        }
+    }
+
+    /// Converts the value bound by `let name: ty = ...` to the annotated type.
+    /// Integers are cast to the annotated width, any other mismatch is an error.
+    fn coerce_to_annotation(
+        &self,
+        name: &str,
+        value: AnyValueEnum<'ctx>,
+        ty: Option<&Type>,
+        expr: &Expr,
+    ) -> anyhow::Result<AnyValueEnum<'ctx>> {
+        let Some(ty) = ty else {
+            return Ok(value);
+        };
+        match (value, self.llvm_type(ty)) {
+            (AnyValueEnum::IntValue(value), Some(BasicTypeEnum::IntType(int_type))) => Ok(self
+                .coerce_int(value, int_type, !self.is_unsigned(expr))
+                .into()),
+            (AnyValueEnum::FloatValue(value), Some(BasicTypeEnum::FloatType(float_type))) => {
+                Ok(self.coerce_float(value, float_type).into())
+            }
+            (value, Some(llvm_type)) if value.get_type() == llvm_type.as_any_type_enum() => {
+                Ok(value)
+            }
+            _ => Err(anyhow!(
+                "`{}` is annotated as {} but got {:?}",
+                name,
+                ty,
+                value
+            )),
         }
-        Ok(())
     }
 
     pub fn stdlib_call(&self, idents: &Vec<String>) -> anyhow::Result<BasicValueEnum<'ctx>> {
@@ -582,11 +4323,10 @@ impl<'ctx> Compiler<'ctx> {
                     // return argv
                     return Ok(argv.into());
                 }
-                _ => panic!("Unknown std function: {}", second_ident),
+                _ => Err(anyhow!("Unknown path `{}`", idents.join("::"))),
             }
         } else {
-            // Handle error: no identifier
-            panic!("No identifier found");
+            Err(anyhow!("Expected an item after `std::`"))
         }
     }
 }
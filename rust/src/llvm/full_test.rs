@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use inkwell::targets::{InitializationConfig, Target};
+
+    use crate::{llvm::codegen::emit_from_program, parsers::modules::load_source};
+
+    /// Compiles `src` and returns the LLVM IR of its module
+    fn emit(src: &str, bounds_checks: bool) -> anyhow::Result<String> {
+        Target::initialize_native(&InitializationConfig::default()).map_err(|err| anyhow::anyhow!(err))?;
+        let program = load_source(src, Path::new("."))?;
+        Ok(emit_from_program(program, bounds_checks)?.to_string())
+    }
+
+    #[test]
+    fn test_format_strings() -> anyhow::Result<()> {
+        let src = r#"
+fn main() {
+    let n: i32 = -1;
+    let u: u8 = 2;
+    let f = 1.5;
+    let c = 'é';
+    let b = true;
+    println!("{} {} {} {} {} {}%", n, u, f, c, b, "s");
+    let s = format!("{}!", u);
+}
+"#;
+        let ir = emit(src, true)?;
+        // integers are widened to 64 bits, a `char` and a `bool` are printed as strings like in the interpreter
+        assert!(ir.contains(r#"c"%lld %llu %g %s %s %s%%\0A\00""#), "{}", ir);
+        assert!(ir.contains(r#"c"%llu!\00""#), "{}", ir);
+        // the UTF-8 encoding of the `char` is written to a buffer on the stack
+        assert!(ir.contains("alloca [5 x i8]"), "{}", ir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bounds_checks() -> anyhow::Result<()> {
+        let src = r#"
+fn main() {
+    let a = [1, 2, 3];
+    let i: u64 = 2;
+    let x = a[i];
+    let s = a[1..i];
+}
+"#;
+        let ir = emit(src, true)?;
+        assert!(ir.contains("call void @__akame_panic_bounds("), "{}", ir);
+        assert!(ir.contains("call void @__akame_panic_slice("), "{}", ir);
+        // `--no-bounds-checks` leaves the checks out, the panic routines are still defined
+        let ir = emit(src, false)?;
+        assert!(ir.contains("define void @__akame_panic_bounds("), "{}", ir);
+        assert!(!ir.contains("call void @__akame_panic_bounds("), "{}", ir);
+        assert!(!ir.contains("call void @__akame_panic_slice("), "{}", ir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_escapes() -> anyhow::Result<()> {
+        let src = r#"
+fn main() {
+    let s = "a\tb\u{e9}";
+    print(s);
+}
+"#;
+        // the global holds the decoded UTF-8 bytes and the terminator
+        let ir = emit(src, true)?;
+        assert!(ir.contains(r#"[6 x i8] c"a\09b\C3\A9\00""#), "{}", ir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_std_items() -> anyhow::Result<()> {
+        // a path the type checker would reject is an error, not a crash
+        let err = emit("fn main() { let x = std::nothing; }", true).unwrap_err();
+        assert!(err.to_string().contains("Unknown path `std::nothing`"), "{}", err);
+        Ok(())
+    }
+}
@@ -5,26 +5,32 @@ use std::{
 
 use inkwell::context::Context;
 
-use crate::which_bin;
+use crate::{parsers::modules::Program, which_bin};
 
-use self::{ast::Ast, compiler::Compiler};
+use self::compiler::Compiler;
 
 pub mod ast;
 pub mod codegen;
 mod compiler;
+mod full_test;
 
 /* we're creating a wrapper around the user's main function so we can initalize the runtime */
 pub const GLOBAL_ENTRY: &str = "main";
 pub const USER_DEFINED_ENTRY: &str = "_main";
 pub const BUILD_DIR: &str = "_build";
 
-pub fn emit(stmts: Vec<ast::Stmt>) -> anyhow::Result<()> {
-    let llvm_ir = codegen::emit_from_statements(stmts)?;
+pub fn emit(program: Program, bounds_checks: bool) -> anyhow::Result<()> {
+    let llvm_ir = codegen::emit_from_program(program, bounds_checks)?;
     println!("{}", llvm_ir.to_string());
     Ok(())
 }
 
-pub fn compile_ast(ast: Ast, out_dir: Option<PathBuf>) -> anyhow::Result<()> {
+/// Compiles a program to a binary with clang. Without `bounds_checks`, indexing out of bounds is undefined behaviour.
+pub fn compile_ast(
+    program: Program,
+    out_dir: Option<PathBuf>,
+    bounds_checks: bool,
+) -> anyhow::Result<()> {
     // create build directory if it doesn't exist
     match std::path::Path::new(BUILD_DIR).try_exists() {
         Ok(bool) => {
@@ -39,8 +45,10 @@ pub fn compile_ast(ast: Ast, out_dir: Option<PathBuf>) -> anyhow::Result<()> {
     }
     // emit the LLVM IR to a file
     let context = Context::create();
-    let mut compiler = Compiler::new(&context);
-    compiler.compile_ir(ast)?;
+    let mut compiler = Compiler::new(&context)
+        .with_sources(program.files)
+        .with_bounds_checks(bounds_checks);
+    compiler.compile_ir(program.ast)?;
     let ir_out: String = format!("{}/{}", BUILD_DIR, "main.ll");
     if compiler
         .module
@@ -37,6 +37,10 @@ enum Commands {
         /// Source to emit LLVM IR from
         //#[arg(short, long, value_name = "FILE")]
         file: PathBuf,
+
+        /// Leave out the bounds checks on indexing, for release builds
+        #[arg(long)]
+        no_bounds_checks: bool,
     },
     Compile {
         /// Source to compile
@@ -45,6 +49,10 @@ enum Commands {
 
         #[arg(short, long, value_name = "bin/main")]
         out_dir: Option<PathBuf>,
+
+        /// Leave out the bounds checks on indexing, for release builds
+        #[arg(long)]
+        no_bounds_checks: bool,
     },
     /// Parse source code file
     Parse {
@@ -61,6 +69,10 @@ enum Commands {
         /// Source to parse
         //#[arg(short, long, value_name = "FILE")]
         file: PathBuf,
+
+        /// Arguments passed to the script as `std::args`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
     },
     Repl,
 }
@@ -84,16 +96,21 @@ fn main() -> anyhow::Result<()> {
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
     match args.command {
-        Some(Commands::Emit { file }) => {
+        Some(Commands::Emit {
+            file,
+            no_bounds_checks,
+        }) => {
             println!("[*] Emitting IR for file: {}", file.display());
-            let script = std::fs::read_to_string(file.as_path())
-                .expect("Something went wrong reading the input file");
-            match red_interpreter::parse(&script) {
-                Ok(ast) => {
+            match parsers::modules::load_program(&file) {
+                Ok(program) => {
                     if args.debug > 0 {
-                        println!("AST: {:?}", ast);
+                        println!("AST: {:?}", program.ast);
                     }
-                    llvm::emit(ast)?
+                    if let Err(err) = types::checker::check_modules(&program) {
+                        println!("{}", err);
+                        std::process::exit(1);
+                    }
+                    llvm::emit(program, !no_bounds_checks)?
                 }
                 Err(err) => {
                     println!("{:?}", err);
@@ -103,24 +120,28 @@ fn main() -> anyhow::Result<()> {
         }
         Some(Commands::Parse { file }) => {
             println!("[*] Parsing file: {}", file.display());
-            let script = std::fs::read_to_string(file.as_path())
-                .expect("Something went wrong reading the file");
-            match red_interpreter::parse(&script) {
-                Ok(ast) => {
-                    println!("AST: {:#?}", ast);
+            match parsers::modules::load_program(&file) {
+                Ok(program) => {
+                    println!("AST: {:#?}", program.ast);
                 }
                 Err(err) => {
                     println!("Error: {:?}", err);
                 }
             }
         }
-        Some(Commands::Compile { file, out_dir }) => {
+        Some(Commands::Compile {
+            file,
+            out_dir,
+            no_bounds_checks,
+        }) => {
             println!("[*] Compiling: {:?}", file);
-            let script = std::fs::read_to_string(file.as_path())
-                .expect("Something went wrong reading the file");
-            match red_interpreter::parse(&script) {
-                Ok(ast) => {
-                    llvm::compile_ast(ast, out_dir)?;
+            match parsers::modules::load_program(&file) {
+                Ok(program) => {
+                    if let Err(err) = types::checker::check_modules(&program) {
+                        println!("{}", err);
+                        std::process::exit(1);
+                    }
+                    llvm::compile_ast(program, out_dir, !no_bounds_checks)?;
                 }
                 Err(err) => {
                     println!("Error: {:?}", err);
@@ -132,8 +153,8 @@ fn main() -> anyhow::Result<()> {
             let res = which_bin("clang");
             println!("clang: {:?}", res);
         }
-        Some(Commands::Run { file }) => {
-            red_interpreter::run_script(&file);
+        Some(Commands::Run { file, args }) => {
+            red_interpreter::run_script(&file, &args);
         }
         Some(Commands::Repl) => {
             if let Err(e) = red_interpreter::repl::interactive() {
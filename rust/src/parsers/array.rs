@@ -1,20 +1,44 @@
-use nom::{multi::separated_list0, sequence::delimited, bytes::complete::tag, branch::alt};
+use nom::{multi::{separated_list0, many1}, sequence::{delimited, preceded}, bytes::complete::tag, branch::alt, combinator::opt};
 
 use crate::llvm::ast::Expr;
 
-use super::{statements::space_opt, ParseResult, expressions::expression, tokens::{parse_identifier, parse_qualified_identifier}};
+use super::{error::CustomError, statements::space_opt, ParseResult, expressions::parse_infix_expr, tokens::{parse_identifier, parse_qualified_identifier}};
 
 pub fn parse_array(input: &str) -> ParseResult<&str, Expr> {
     delimited(
         tag("["),
-        separated_list0(space_opt(tag(",")), expression),
-        tag("]")
+        separated_list0(space_opt(tag(",")), parse_infix_expr),
+        space_opt(tag("]"))
     )(input)
     .map(|(next_input, vec)| (next_input, Expr::Array(vec)))
 }
 
+/// What a `[...]` following an array selects
+enum Index {
+    Element(Expr),
+    /// `start..end`, either bound can be left out
+    Range(Option<Expr>, Option<Expr>),
+}
+
+fn parse_index(input: &str) -> ParseResult<&str, Index> {
+    let (input, _) = tag("[")(input)?;
+    let (input, start) = opt(space_opt(parse_infix_expr))(input)?;
+    let (input, range) = opt(preceded(space_opt(tag("..")), opt(parse_infix_expr)))(input)?;
+    let (input, _) = space_opt(tag("]"))(input)?;
+    match (start, range) {
+        (start, Some(end)) => Ok((input, Index::Range(start, end))),
+        (Some(index), None) => Ok((input, Index::Element(index))),
+        (None, None) => Err(nom::Err::Error(CustomError::UnexpectedToken("Expected an index"))),
+    }
+}
+
 pub fn parse_array_indexing(input: &str) -> ParseResult<&str, Expr> {
     let (input, array) = alt((parse_qualified_identifier, parse_identifier))(input)?;
-    let (input, index) = delimited(tag("["), expression, tag("]"))(input)?;
-    Ok((input, Expr::ArrayIndexing(Box::new(array), Box::new(index))))
-}
\ No newline at end of file
+    // `grid[i][j]` nests as ArrayIndexing(ArrayIndexing(grid, i), j), `a[1..3][0]` indexes the slice
+    let (input, indices) = many1(parse_index)(input)?;
+    let indexed = indices.into_iter().fold(array, |array, index| match index {
+        Index::Element(index) => Expr::ArrayIndexing(Box::new(array), Box::new(index)),
+        Index::Range(start, end) => Expr::Slice(Box::new(array), start.map(Box::new), end.map(Box::new)),
+    });
+    Ok((input, indexed))
+}
@@ -0,0 +1,103 @@
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::{opt, value, verify};
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{delimited, preceded, terminated, tuple};
+use nom::Parser;
+
+use crate::llvm::ast::{Expr, MatchArm, Pattern, Span, Stmt, UnaryOp, Variant};
+
+use super::expressions::parse_infix_expr;
+use super::functions::parse_block_or_expr;
+use super::statements::space_opt;
+use super::structs::braced_list;
+use super::tuples::tuple_list;
+use super::tokens::{keyword, parse_boolean, parse_char, parse_name, parse_negative_number, parse_number, parse_path, parse_string, parse_visibility, skip_space};
+use super::types::parse_type;
+use super::ParseResult;
+
+/// `( a, b )`, used for payload types, constructor arguments and payload patterns
+pub fn parenthesised<'a, O, F>(item: F) -> impl FnMut(&'a str) -> ParseResult<&'a str, Vec<O>>
+where
+    F: FnMut(&'a str) -> ParseResult<&'a str, O>,
+{
+    delimited(
+        space_opt(tag("(")),
+        separated_list0(space_opt(tag(",")), item),
+        space_opt(tag(")")),
+    )
+}
+
+/// `enum Shape { Circle(i32), Rect(i32, i32), Empty }`
+pub fn parse_enum_declaration(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, _) = space_opt(keyword("enum"))(input)?;
+    let (input, ident) = space_opt(parse_name)(input)?;
+    let (input, variants) = braced_list(|input| {
+        let (input, ident) = space_opt(parse_name)(input)?;
+        let (input, fields) = opt(parenthesised(parse_type))(input)?;
+        Ok((input, Variant { ident, fields: fields.unwrap_or_default() }))
+    })(input)?;
+    Ok((input, Stmt::EnumDeclaration { ident, variants, public, span }))
+}
+
+/// `Enum::Variant` or `shapes::Enum::Variant`, only capitalised enum names so `std::args` stays a path
+fn parse_variant_path(input: &str) -> ParseResult<&str, (String, String)> {
+    let (input, mut path) = space_opt(verify(parse_path, |path: &[String]| {
+        path.len() >= 2 && path[path.len() - 2].starts_with(|c: char| c.is_ascii_uppercase())
+    }))(input)?;
+    let variant = path.pop().unwrap();
+    Ok((input, (path.join("::"), variant)))
+}
+
+/// `Shape::Rect(1, 2)` or `Shape::Empty`
+pub fn parse_enum_variant(input: &str) -> ParseResult<&str, Expr> {
+    let (input, (enum_name, variant)) = parse_variant_path(input)?;
+    let (input, args) = opt(parenthesised(parse_infix_expr))(input)?;
+    Ok((input, Expr::EnumVariant { enum_name, variant, args: args.unwrap_or_default() }))
+}
+
+/// `_`, a binding, a literal, an enum variant with patterns for its payload or a tuple of patterns
+pub fn parse_pattern(input: &str) -> ParseResult<&str, Pattern> {
+    space_opt(alt((
+        value(Pattern::Wildcard, keyword("_")),
+        tuple((parse_variant_path, opt(parenthesised(parse_pattern)))).map(
+            |((enum_name, variant), fields)| Pattern::Variant {
+                enum_name,
+                variant,
+                fields: fields.unwrap_or_default(),
+            },
+        ),
+        parse_negative_number.map(Pattern::Literal),
+        preceded(tag("-"), parse_number).map(|literal| match literal {
+            Expr::Num(n) => Pattern::Literal(Expr::Num(-n)),
+            literal => Pattern::Literal(Expr::Unary(UnaryOp::Negate, Box::new(literal))),
+        }),
+        alt((parse_number, parse_char, parse_string, parse_boolean)).map(Pattern::Literal),
+        parse_name.map(Pattern::Binding),
+        tuple_list(parse_pattern).map(Pattern::Tuple),
+    )))(input)
+}
+
+/// `pattern if guard => body`, the body is a block or a single expression
+fn parse_match_arm(input: &str) -> ParseResult<&str, MatchArm> {
+    let (input, pattern) = parse_pattern(input)?;
+    let (input, guard) = opt(preceded(space_opt(keyword("if")), parse_infix_expr))(input)?;
+    let (input, _) = space_opt(tag("=>"))(input)?;
+    let (input, body) = parse_block_or_expr(input)?;
+    Ok((input, MatchArm { pattern, guard, body }))
+}
+
+/// `match expr { pattern => body, ... }`, the comma after an arm is optional
+pub fn parse_match_expr(input: &str) -> ParseResult<&str, Expr> {
+    let (input, _) = space_opt(keyword("match"))(input)?;
+    let (input, scrutinee) = parse_infix_expr(input)?;
+    let (input, arms) = delimited(
+        space_opt(tag("{")),
+        many0(terminated(parse_match_arm, opt(space_opt(tag(","))))),
+        space_opt(tag("}")),
+    )(input)?;
+    Ok((input, Expr::Match { scrutinee: Box::new(scrutinee), arms }))
+}
@@ -4,6 +4,10 @@ use nom::error::ContextError;
 pub enum CustomError<I> {
     MainFunctionWithParams(I),
     UnexpectedToken(I),
+    IntegerOverflow(I),
+    InvalidNumber(I),
+    InvalidEscape(I),
+    UnterminatedComment(I),
     // ... add other variants as needed
 }
 
@@ -22,6 +26,10 @@ impl std::fmt::Display for CustomError<&str> {
         match self {
             CustomError::MainFunctionWithParams(input) => write!(f, "Main function defenition cannot require arguments. Consider using `std::args` to access the arguments passed to the program: \x1b[91m{}\x1b[0m", input),
             CustomError::UnexpectedToken(input) => write!(f, "Unexpected token: {}", input),
+            CustomError::IntegerOverflow(literal) => write!(f, "Integer literal out of range: \x1b[91m{}\x1b[0m", literal),
+            CustomError::InvalidNumber(literal) => write!(f, "Invalid integer literal: \x1b[91m{}\x1b[0m", literal),
+            CustomError::InvalidEscape(escape) => write!(f, "Invalid escape sequence: \x1b[91m{}\x1b[0m (expected one of \\n \\t \\r \\\\ \\\" \\' \\0 \\x00-\\x7F \\u{{...}})", escape),
+            CustomError::UnterminatedComment(comment) => write!(f, "Unterminated block comment: \x1b[91m{}\x1b[0m", comment.lines().next().unwrap_or_default()),
             // ... handle other variants similarly
         }
     }
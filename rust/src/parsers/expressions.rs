@@ -1,81 +1,183 @@
-use nom::combinator::opt;
+use nom::combinator::{not, opt};
+use nom::multi::many0;
 use nom::{branch::alt, multi::separated_list0};
 use nom::bytes::complete::tag;
-use nom::character::complete::multispace0;
 use nom::error::context;
 
-use nom::sequence::{delimited, tuple};
+use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::Parser;
 
 
 
-use crate::llvm::ast::Expr;
-use crate::parsers::statements::space_opt;
+use crate::llvm::ast::{BinaryOp, Expr, Span, Stmt, UnaryOp};
+use crate::parsers::statements::{parse_block, space_opt};
 
 
 use super::ParseResult;
+use super::error::CustomError;
 
 use super::array::{parse_array, parse_array_indexing};
+use super::structs::{parse_field, parse_struct_literal};
+use super::enums::{parenthesised, parse_enum_variant, parse_match_expr};
+use super::functions::parse_closure;
+use super::tuples::parse_tuple;
+use super::types::parse_type;
 use super::tokens::{
     parse_identifier, 
     parse_number, 
+    parse_negative_number,
+    parse_float,
     parse_string, 
     parse_char, 
-    parse_variable, 
-    get_identifier, parse_qualified_identifier,
+    parse_boolean,
+    parse_qualified_identifier, parse_path,
+    keyword, skip_space,
 };
 
+/// `f(a, b)`, or `shapes::area(s)` for a function in another module
 pub fn parse_function_call(input: &str) -> ParseResult<&str, Expr> {
     //println!("parse_function_call INPUT: {}", input);
-    let (input, name) = space_opt(parse_identifier)(input)?;
+    let (input, path) = space_opt(parse_path)(input)?;
     //println!("parse_function_call NAME: {}", name);
-    let (input, _) = multispace0(input)?;
+    let (input, _) = skip_space(input)?;
     let (input, args) = delimited(
         tag("("), 
-        separated_list0(tag(","), expression),
-        tag(")")
+        separated_list0(space_opt(tag(",")), parse_infix_expr),
+        space_opt(tag(")"))
     )(input)?;
-    let id = get_identifier(name).unwrap();
-    
-    Ok((input, Expr::Call(id, args)))
+    Ok((input, Expr::Call(path.join("::"), args)))
 }
 
+/// `println!("{}", x)`, a builtin macro called with its arguments in parentheses
+pub fn parse_macro_call(input: &str) -> ParseResult<&str, Expr> {
+    let (input, name) = space_opt(parse_identifier)(input)?;
+    // `a != b` is a comparison
+    let (input, _) = pair(tag("!"), not(tag("=")))(input)?;
+    let (input, args) = parenthesised(parse_infix_expr)(input)?;
+    match name {
+        Expr::Ident(name) => Ok((input, Expr::MacroCall(name, args))),
+        _ => Err(nom::Err::Error(CustomError::UnexpectedToken("Expected a macro name"))),
+    }
+}
+
+/// `if` in expression position has to have an `else` so it always yields a value
+pub fn parse_if_expr(input: &str) -> ParseResult<&str, Expr> {
+    let (input, _) = space_opt(keyword("if"))(input)?;
+    let (input, condition) = parse_infix_expr(input)?;
+    let (input, then_branch) = parse_block(input)?;
+    let (input, _) = space_opt(keyword("else"))(input)?;
+    let (input, else_branch) = alt((
+        |input| {
+            let (rest, _) = skip_space(input)?;
+            parse_if_expr(rest).map(|(input, expr)| (input, vec![Stmt::Expression(expr, Span::new(rest))]))
+        },
+        parse_block,
+    ))(input)?;
+
+    Ok((input, Expr::If { condition: Box::new(condition), then_branch, else_branch }))
+}
 
 pub fn parse_primary_expr(input: &str) -> ParseResult<&str, Expr> {
     alt((
+        parse_if_expr,
+        parse_match_expr,
+        parse_closure,
+        parse_struct_literal,
+        parse_enum_variant,
+        parse_macro_call,
+        parse_function_call,
         parse_array_indexing,
+        parse_boolean,
         parse_qualified_identifier,
         parse_identifier,
         parse_array,
+        parse_float,
         parse_number,
         parse_string,
         parse_char,
-        delimited(tag("("), expression, tag(")")),
+        parse_tuple,
+        delimited(tag("("), parse_infix_expr, tag(")")),
     ))(input)
 }
 
-pub fn parse_infix_expr(input: &str) -> ParseResult<&str, Expr> {
-    let (input, _) = multispace0(input)?;
-    let (input, left) =  parse_variable(input)?;
-    let (input, _) = multispace0(input)?;
+/// Field accesses and method calls chain onto a primary expression, `a.b.c` is `(a.b).c`, `t.0.1` is `(t.0).1`
+/// and `v.len() + 1` calls `len` on `v`
+fn parse_postfix_expr(input: &str) -> ParseResult<&str, Expr> {
+    let (input, expr) = parse_primary_expr(input)?;
+    let (input, suffixes) = many0(pair(parse_field, opt(parenthesised(parse_infix_expr))))(input)?;
+    let expr = suffixes.into_iter().fold(expr, |expr, (field, args)| match args {
+        Some(args) => Expr::MethodCall { receiver: Box::new(expr), method: field, args },
+        None => Expr::FieldAccess(Box::new(expr), field),
+    });
+    Ok((input, expr))
+}
+
+/// Prefix operators bind tighter than any binary operator, `!a == b` is `(!a) == b`.
+/// Negated literals are folded into negative literals.
+fn parse_unary_expr(input: &str) -> ParseResult<&str, Expr> {
+    alt((
+        preceded(space_opt(tag("!")), parse_unary_expr)
+            .map(|expr| Expr::Unary(UnaryOp::Not, Box::new(expr))),
+        preceded(skip_space, parse_negative_number),
+        preceded(space_opt(tag("-")), parse_unary_expr).map(|expr| match expr {
+            Expr::Num(n) => Expr::Num(-n),
+            Expr::Float(n) => Expr::Float(-n),
+            expr => Expr::Unary(UnaryOp::Negate, Box::new(expr)),
+        }),
+        parse_postfix_expr,
+    ))(input)
+}
+
+/// `expr as T`, binding tighter than every binary operator but looser than unary ones
+fn parse_cast_expr(input: &str) -> ParseResult<&str, Expr> {
+    let (input, expr) = parse_unary_expr(input)?;
+    let (input, casts) = many0(preceded(space_opt(keyword("as")), parse_type))(input)?;
+    let expr = casts.into_iter().fold(expr, |expr, ty| Expr::Cast(Box::new(expr), ty));
+    Ok((input, expr))
+}
+
+fn parse_binary_operator(input: &str) -> ParseResult<&str, BinaryOp> {
+    // two character operators have to be tried before their one character prefixes
     let (input, op) = alt((
-        tag("+"),
-        tag("-"),
-        tag("*"),
-        tag("/"),
+        tag("&&"),
+        tag("||"),
         tag("=="),
         tag("!="),
-        tag("<"),
-        tag(">"),
         tag("<="),
         tag(">="),
+        tag("<"),
+        tag(">"),
+        tag("+"),
+        tag("-"),
+        tag("*"),
+        tag("/"),
     ))(input)?;
-    let (input, _) = multispace0(input)?;
-    let (input, right) = parse_variable(input)?;
-    let (input, _) = multispace0(input)?;
+    Ok((input, op.into()))
+}
 
-    let infix = Expr::Infix(Box::new(left), op.into(), Box::new(right));
-    //let (input, _) = opt(tag(";"))(input)?;
-    Ok((input, infix))
+/// Precedence climbing: parses operands with `parse_cast_expr` and folds every
+/// operator binding at least as tightly as `min_precedence` into a left-associative tree.
+fn parse_binary_expr(input: &str, min_precedence: u8) -> ParseResult<&str, Expr> {
+    let (mut input, mut left) = space_opt(parse_cast_expr)(input)?;
+    loop {
+        let (rest, op) = match space_opt(parse_binary_operator)(input) {
+            Ok(res) => res,
+            Err(nom::Err::Error(_)) => break,
+            Err(err) => return Err(err),
+        };
+        let precedence = op.precedence();
+        if precedence < min_precedence {
+            break;
+        }
+        let (rest, right) = parse_binary_expr(rest, precedence + 1)?;
+        left = Expr::Infix(Box::new(left), op, Box::new(right));
+        input = rest;
+    }
+    Ok((input, left))
+}
+
+pub fn parse_infix_expr(input: &str) -> ParseResult<&str, Expr> {
+    parse_binary_expr(input, 0)
 }
 
 
@@ -83,18 +185,14 @@ pub fn expression(input: &str) -> ParseResult<&str, Expr> {
     context(
         "expr",
         tuple((
-                alt((
-                    parse_function_call,
-                    parse_infix_expr,
-                    parse_primary_expr,
-                )),
+            parse_infix_expr,
             opt(tuple((
                 tag(";"),
-                multispace0,
+                skip_space,
             )))
         ))
     )(input)
         .map(|(input, (expr, _))| 
         (input, expr)
     )
-}
\ No newline at end of file
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::{llvm::ast::{Expr, BinaryOp, Stmt}, parsers::parse_program};
+    use crate::{llvm::{ast::{instance_name, parse_format_string, Expr, FormatPiece, GlobalKind, BinaryOp, Field, MatchArm, Param, Pattern, Span, Stmt, Type, UnaryOp, Variant}, USER_DEFINED_ENTRY}, parsers::{modules::{load_program, load_source}, parse_program}, types::integer::Integer};
 
     #[test]
     fn test_function_declarations() -> anyhow::Result<()> {
@@ -23,48 +23,1198 @@ mod tests {
         let expected = vec![
             Stmt::FunctionDeclaration {
                 ident: "hello".to_string(),
-                params: vec!["num".to_string()],
+                type_params: vec![],
+                params: vec![param("num")],
+                return_type: None,
                 body: vec![Stmt::Return(Expr::Infix(
                     Box::new(Expr::Ident("num".to_string())),
                     BinaryOp::Add,  
-                    Box::new(Expr::Num(5)),
-                ))],
+                    Box::new(Expr::Num(5.into())),
+                ), Span::default())],
+                public: false,
+                span: Span::default(),
             },
             Stmt::FunctionDeclaration {
-                ident: "main".to_string(),
+                ident: USER_DEFINED_ENTRY.to_string(),
+                type_params: vec![],
                 params: vec![],
+                return_type: None,
                 body: vec![
                     Stmt::Assignment {
                         ident: "ee".to_string(),
+                        mutable: false,
+                        ty: None,
                         expr: Expr::Str("hello".to_string()),
+                        span: Span::default(),
                     },
                     Stmt::Assignment {
                         ident: "value".to_string(),
+                        mutable: false,
+                        ty: None,
                         expr: Expr::ArrayIndexing(
                             Box::new(Expr::QualifiedIdent(vec!["std".to_string(), "args".to_string()])),
-                            Box::new(Expr::Num(1))
+                            Box::new(Expr::Num(1.into()))
                         ),
+                        span: Span::default(),
                     },
                     Stmt::Expression(Expr::Call(
                         "printf".to_string(),
                         vec![Expr::Ident("value".to_string())],
-                    )),
+                    ), Span::default()),
                     Stmt::Assignment {
                         ident: "number".to_string(),
+                        mutable: false,
+                        ty: None,
                         expr: Expr::Call(
                             "hello".to_string(),
-                            vec![Expr::Num(3)],
+                            vec![Expr::Num(3.into())],
                         ),
+                        span: Span::default(),
                     },
                     Stmt::Expression(Expr::Call(
                         "printd".to_string(),
                         vec![Expr::Ident("number".to_string())],
-                    )),
+                    ), Span::default()),
                 ],
+                public: false,
+                span: Span::default(),
             },
         ];
 
         assert_eq!(parsed, expected);
         Ok(())
     }
+
+    fn infix(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+        Expr::Infix(Box::new(left), op, Box::new(right))
+    }
+
+    fn ident(name: &str) -> Expr {
+        Expr::Ident(name.to_string())
+    }
+
+    fn param(name: &str) -> Param {
+        Param { ident: name.to_string(), ty: None }
+    }
+
+    fn parse_single_expr(src: &str) -> anyhow::Result<Expr> {
+        match parse_program(src)?.as_slice() {
+            [Stmt::Expression(expr, _)] => Ok(expr.clone()),
+            other => Err(anyhow::anyhow!("expected a single expression, got {:?}", other)),
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("a + b * c;")?,
+            infix(ident("a"), BinaryOp::Add, infix(ident("b"), BinaryOp::Multiply, ident("c"))),
+        );
+        assert_eq!(
+            parse_single_expr("a * b - c / d;")?,
+            infix(
+                infix(ident("a"), BinaryOp::Multiply, ident("b")),
+                BinaryOp::Subtract,
+                infix(ident("c"), BinaryOp::Divide, ident("d")),
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_left_associativity() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("10 - 4 - 3;")?,
+            infix(infix(Expr::Num(10.into()), BinaryOp::Subtract, Expr::Num(4.into())), BinaryOp::Subtract, Expr::Num(3.into())),
+        );
+        assert_eq!(
+            parse_single_expr("8 / 4 * 2;")?,
+            infix(infix(Expr::Num(8.into()), BinaryOp::Divide, Expr::Num(4.into())), BinaryOp::Multiply, Expr::Num(2.into())),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parenthesised_groups() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("(x + 1) * 2;")?,
+            infix(infix(ident("x"), BinaryOp::Add, Expr::Num(1.into())), BinaryOp::Multiply, Expr::Num(2.into())),
+        );
+        assert_eq!(
+            parse_single_expr("((a - b)) / ( c + d );")?,
+            infix(
+                infix(ident("a"), BinaryOp::Subtract, ident("b")),
+                BinaryOp::Divide,
+                infix(ident("c"), BinaryOp::Add, ident("d")),
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_complex_operands() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("hello(3) + 1;")?,
+            infix(Expr::Call("hello".to_string(), vec![Expr::Num(3.into())]), BinaryOp::Add, Expr::Num(1.into())),
+        );
+        assert_eq!(
+            parse_single_expr("add(a * 2, b) * arr[i + 1] - std::args[1];")?,
+            infix(
+                infix(
+                    Expr::Call(
+                        "add".to_string(),
+                        vec![infix(ident("a"), BinaryOp::Multiply, Expr::Num(2.into())), ident("b")],
+                    ),
+                    BinaryOp::Multiply,
+                    Expr::ArrayIndexing(
+                        Box::new(ident("arr")),
+                        Box::new(infix(ident("i"), BinaryOp::Add, Expr::Num(1.into()))),
+                    ),
+                ),
+                BinaryOp::Subtract,
+                Expr::ArrayIndexing(
+                    Box::new(Expr::QualifiedIdent(vec!["std".to_string(), "args".to_string()])),
+                    Box::new(Expr::Num(1.into())),
+                ),
+            ),
+        );
+        assert_eq!(
+            parse_single_expr("grid[1][2];")?,
+            Expr::ArrayIndexing(
+                Box::new(Expr::ArrayIndexing(Box::new(ident("grid")), Box::new(Expr::Num(1.into())))),
+                Box::new(Expr::Num(2.into())),
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_expressions_in_statements() -> anyhow::Result<()> {
+        let parsed = parse_program(
+            r#"
+        fn area(w, h) {
+            let doubled = (w + h) * 2;
+            return doubled - w * h;
+        }
+        "#,
+        )?;
+
+        let expected = vec![Stmt::FunctionDeclaration {
+            ident: "area".to_string(),
+            type_params: vec![],
+            params: vec![param("w"), param("h")],
+            return_type: None,
+            body: vec![
+                Stmt::Assignment {
+                    ident: "doubled".to_string(),
+                    mutable: false,
+                    ty: None,
+                    expr: infix(infix(ident("w"), BinaryOp::Add, ident("h")), BinaryOp::Multiply, Expr::Num(2.into())),
+                    span: Span::default(),
+                },
+                Stmt::Return(infix(
+                    ident("doubled"),
+                    BinaryOp::Subtract,
+                    infix(ident("w"), BinaryOp::Multiply, ident("h")),
+                ), Span::default()),
+            ],
+            public: false,
+            span: Span::default(),
+        }];
+
+        assert_eq!(parsed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparison_operators() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("a + 1 < b * 2;")?,
+            infix(
+                infix(ident("a"), BinaryOp::Add, Expr::Num(1.into())),
+                BinaryOp::LessThan,
+                infix(ident("b"), BinaryOp::Multiply, Expr::Num(2.into())),
+            ),
+        );
+        assert_eq!(parse_single_expr("a <= b;")?, infix(ident("a"), BinaryOp::LessOrEqual, ident("b")));
+        assert_eq!(parse_single_expr("a >= b;")?, infix(ident("a"), BinaryOp::GreaterOrEqual, ident("b")));
+        assert_eq!(parse_single_expr("a != b;")?, infix(ident("a"), BinaryOp::NotEqual, ident("b")));
+        assert_eq!(
+            parse_single_expr("hello(1) == 6;")?,
+            infix(Expr::Call("hello".to_string(), vec![Expr::Num(1.into())]), BinaryOp::Equal, Expr::Num(6.into())),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_boolean_literals() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("true;")?, Expr::Bool(true));
+        assert_eq!(
+            parse_single_expr("x == false;")?,
+            infix(ident("x"), BinaryOp::Equal, Expr::Bool(false)),
+        );
+        // keywords only match whole words
+        assert_eq!(parse_single_expr("trueish;")?, ident("trueish"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_else_if_else_statement() -> anyhow::Result<()> {
+        let parsed = parse_program(
+            r#"
+        fn sign(n) {
+            if n < 0 {
+                return 0 - 1;
+            } else if n == 0 {
+                return 0;
+            } else {
+                printd(n);
+            }
+            return 1;
+        }
+        "#,
+        )?;
+
+        let expected = vec![Stmt::FunctionDeclaration {
+            ident: "sign".to_string(),
+            type_params: vec![],
+            params: vec![param("n")],
+            return_type: None,
+            body: vec![
+                Stmt::If {
+                    condition: infix(ident("n"), BinaryOp::LessThan, Expr::Num(0.into())),
+                    then_branch: vec![Stmt::Return(infix(Expr::Num(0.into()), BinaryOp::Subtract, Expr::Num(1.into())), Span::default())],
+                    else_branch: Some(vec![Stmt::If {
+                        condition: infix(ident("n"), BinaryOp::Equal, Expr::Num(0.into())),
+                        then_branch: vec![Stmt::Return(Expr::Num(0.into()), Span::default())],
+                        else_branch: Some(vec![Stmt::Expression(Expr::Call(
+                            "printd".to_string(),
+                            vec![ident("n")],
+                        ), Span::default())]),
+                        span: Span::default(),
+                    }]),
+                    span: Span::default(),
+                },
+                Stmt::Return(Expr::Num(1.into()), Span::default()),
+            ],
+            public: false,
+            span: Span::default(),
+        }];
+
+        assert_eq!(parsed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_without_else() -> anyhow::Result<()> {
+        let parsed = parse_program("if ready { go(); }")?;
+        assert_eq!(
+            parsed,
+            vec![Stmt::If {
+                condition: ident("ready"),
+                then_branch: vec![Stmt::Expression(Expr::Call("go".to_string(), vec![]), Span::default())],
+                else_branch: None,
+                span: Span::default(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_expression() -> anyhow::Result<()> {
+        let parsed = parse_program("let smaller = if a < b { a } else if b < c { b } else { c };")?;
+        assert_eq!(
+            parsed,
+            vec![Stmt::Assignment {
+                ident: "smaller".to_string(),
+                mutable: false,
+                ty: None,
+                expr: Expr::If {
+                    condition: Box::new(infix(ident("a"), BinaryOp::LessThan, ident("b"))),
+                    then_branch: vec![Stmt::Expression(ident("a"), Span::default())],
+                    else_branch: vec![Stmt::Expression(Expr::If {
+                        condition: Box::new(infix(ident("b"), BinaryOp::LessThan, ident("c"))),
+                        then_branch: vec![Stmt::Expression(ident("b"), Span::default())],
+                        else_branch: vec![Stmt::Expression(ident("c"), Span::default())],
+                    }, Span::default())],
+                },
+                span: Span::default(),
+            }]
+        );
+        // an `if` expression can be an operand
+        assert_eq!(
+            parse_single_expr("1 + if flag { 2 } else { 3 };")?,
+            infix(
+                Expr::Num(1.into()),
+                BinaryOp::Add,
+                Expr::If {
+                    condition: Box::new(ident("flag")),
+                    then_branch: vec![Stmt::Expression(Expr::Num(2.into()), Span::default())],
+                    else_branch: vec![Stmt::Expression(Expr::Num(3.into()), Span::default())],
+                },
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_keywords_are_not_identifiers() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("iffy + elsewhere;")?, infix(ident("iffy"), BinaryOp::Add, ident("elsewhere")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_while_loop() -> anyhow::Result<()> {
+        let parsed = parse_program("while n > 0 { step(n); break; }")?;
+        assert_eq!(
+            parsed,
+            vec![Stmt::While {
+                label: None,
+                condition: infix(ident("n"), BinaryOp::GreaterThan, Expr::Num(0.into())),
+                body: vec![
+                    Stmt::Expression(Expr::Call("step".to_string(), vec![ident("n")]), Span::default()),
+                    Stmt::Break(None, Span::default()),
+                ],
+                span: Span::default(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_labelled_for_loop() -> anyhow::Result<()> {
+        let parsed = parse_program("'outer: for i in 0..n + 1 { for j in i..n { continue 'outer; } }")?;
+        assert_eq!(
+            parsed,
+            vec![Stmt::For {
+                label: Some("outer".to_string()),
+                ident: "i".to_string(),
+                start: Expr::Num(0.into()),
+                end: infix(ident("n"), BinaryOp::Add, Expr::Num(1.into())),
+                body: vec![Stmt::For {
+                    label: None,
+                    ident: "j".to_string(),
+                    start: ident("i"),
+                    end: ident("n"),
+                    body: vec![Stmt::Continue(Some("outer".to_string()), Span::default())],
+                    span: Span::default(),
+                }],
+                span: Span::default(),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutable_bindings_and_reassignment() -> anyhow::Result<()> {
+        let parsed = parse_program("let mut total = 0;\ntotal = total + 1;\ntotal += 2;\ntotal /= 3;\ntotal == 1;")?;
+        assert_eq!(
+            parsed,
+            vec![
+                Stmt::Assignment {
+                    ident: "total".to_string(),
+                    mutable: true,
+                    ty: None,
+                    expr: Expr::Num(0.into()),
+                    span: Span::default(),
+                },
+                Stmt::Reassignment {
+                    ident: "total".to_string(),
+                    op: None,
+                    expr: infix(ident("total"), BinaryOp::Add, Expr::Num(1.into())),
+                    span: Span::default(),
+                },
+                Stmt::Reassignment {
+                    ident: "total".to_string(),
+                    op: Some(BinaryOp::Add),
+                    expr: Expr::Num(2.into()),
+                    span: Span::default(),
+                },
+                Stmt::Reassignment {
+                    ident: "total".to_string(),
+                    op: Some(BinaryOp::Divide),
+                    expr: Expr::Num(3.into()),
+                    span: Span::default(),
+                },
+                Stmt::Expression(infix(ident("total"), BinaryOp::Equal, Expr::Num(1.into())), Span::default()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_logical_operator_precedence() -> anyhow::Result<()> {
+        // `&&` binds tighter than `||`, comparisons tighter than both
+        assert_eq!(
+            parse_single_expr("a < b || c && d == e;")?,
+            infix(
+                infix(ident("a"), BinaryOp::LessThan, ident("b")),
+                BinaryOp::Or,
+                infix(ident("c"), BinaryOp::And, infix(ident("d"), BinaryOp::Equal, ident("e"))),
+            ),
+        );
+        let not = |expr| Expr::Unary(UnaryOp::Not, Box::new(expr));
+        assert_eq!(
+            parse_single_expr("!done && !!ready;")?,
+            infix(not(ident("done")), BinaryOp::And, not(not(ident("ready")))),
+        );
+        assert_eq!(
+            parse_single_expr("!(a != b);")?,
+            not(infix(ident("a"), BinaryOp::NotEqual, ident("b"))),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_literals() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("0xFF;")?, Expr::Num(255.into()));
+        assert_eq!(parse_single_expr("0b1010;")?, Expr::Num(10.into()));
+        assert_eq!(parse_single_expr("0o17;")?, Expr::Num(15.into()));
+        assert_eq!(parse_single_expr("1_000_000;")?, Expr::Num(1_000_000.into()));
+        assert_eq!(parse_single_expr("0xdead_beef;")?, Expr::Num(Integer::Int64(0xdead_beef)));
+        // literals keep the width they need instead of being cut down to i32
+        assert_eq!(parse_single_expr("9_000_000_000;")?, Expr::Num(Integer::Int64(9_000_000_000)));
+        assert_eq!(
+            parse_single_expr("170141183460469231731687303715884105727;")?,
+            Expr::Num(Integer::Int128(i128::MAX)),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_negative_literals_and_unary_minus() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("-5;")?, Expr::Num((-5).into()));
+        assert_eq!(parse_single_expr("-2147483648;")?, Expr::Num(i32::MIN.into()));
+        assert_eq!(
+            parse_single_expr("3 - -x;")?,
+            infix(Expr::Num(3.into()), BinaryOp::Subtract, Expr::Unary(UnaryOp::Negate, Box::new(ident("x")))),
+        );
+        assert_eq!(
+            parse_single_expr("-(1 + 2) * 3;")?,
+            infix(
+                Expr::Unary(UnaryOp::Negate, Box::new(infix(Expr::Num(1.into()), BinaryOp::Add, Expr::Num(2.into())))),
+                BinaryOp::Multiply,
+                Expr::Num(3.into()),
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_literal_overflow_is_a_parse_error() {
+        let err = parse_program("let x = 170141183460469231731687303715884105728;").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        assert!(parse_program("let x = 0x_;").is_err());
+    }
+
+    #[test]
+    fn test_literal_suffixes_and_casts() -> anyhow::Result<()> {
+        let cast = |expr, bits, signed| Expr::Cast(Box::new(expr), Type::Int { bits, signed });
+        assert_eq!(parse_single_expr("10u8;")?, cast(Expr::Num(10.into()), 8, false));
+        assert_eq!(parse_single_expr("0xFF_i64;")?, cast(Expr::Num(255.into()), 64, true));
+        // `as` binds tighter than binary operators but looser than unary ones
+        assert_eq!(
+            parse_single_expr("x as u8 + 1;")?,
+            infix(cast(ident("x"), 8, false), BinaryOp::Add, Expr::Num(1.into())),
+        );
+        assert_eq!(
+            parse_single_expr("-x as i64 * 2;")?,
+            infix(cast(Expr::Unary(UnaryOp::Negate, Box::new(ident("x"))), 64, true), BinaryOp::Multiply, Expr::Num(2.into())),
+        );
+        assert_eq!(parse_single_expr("c as u16 as i128;")?, cast(cast(ident("c"), 16, false), 128, true));
+        let err = parse_program("let x = 256u8;").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        // the range of a suffixed literal is checked after applying the sign
+        assert_eq!(parse_single_expr("-128i8;")?, cast(Expr::Num((-128).into()), 8, true));
+        assert_eq!(parse_single_expr("-32768i16;")?, cast(Expr::Num((-32768).into()), 16, true));
+        assert_eq!(parse_single_expr("- 9223372036854775808i64;")?, cast(Expr::Num(i128::from(i64::MIN).into()), 64, true));
+        assert_eq!(parse_single_expr("-5i8 as i64;")?, cast(cast(Expr::Num((-5).into()), 8, true), 64, true));
+        assert_eq!(parse_single_expr("-5;")?, Expr::Num((-5).into()));
+        let err = parse_program("let x = 128i8;").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        let err = parse_program("let x = -129i8;").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        let err = parse_program("let x = -1u8;").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_literals() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("1.5;")?, Expr::Float(1.5));
+        assert_eq!(parse_single_expr("6.02e23;")?, Expr::Float(6.02e23));
+        assert_eq!(parse_single_expr("1_000.5E-3;")?, Expr::Float(1.0005));
+        assert_eq!(parse_single_expr("-0.25;")?, Expr::Float(-0.25));
+        let cast = |value, bits| Expr::Cast(Box::new(Expr::Float(value)), Type::Float { bits });
+        assert_eq!(parse_single_expr("2.5f32;")?, cast(2.5, 32));
+        assert_eq!(parse_single_expr("2f64;")?, cast(2.0, 64));
+        assert_eq!(
+            parse_single_expr("n as f64 / 2.0;")?,
+            infix(Expr::Cast(Box::new(ident("n")), Type::Float { bits: 64 }), BinaryOp::Divide, Expr::Float(2.0)),
+        );
+        // a range bound is still an integer
+        assert!(matches!(&parse_program("for i in 0..10 { }")?[..], [Stmt::For { start: Expr::Num(_), end: Expr::Num(_), .. }]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_sequences() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr(r#""say \"hi\"\n\tand\\or \x41\u{1F600}\0";"#)?,
+            Expr::Str("say \"hi\"\n\tand\\or A\u{1F600}\0".to_string()),
+        );
+        assert_eq!(parse_single_expr(r"'\n';")?, Expr::Char('\n'));
+        assert_eq!(parse_single_expr(r"'\'';")?, Expr::Char('\''));
+        assert_eq!(parse_single_expr(r"'\u{e9}';")?, Expr::Char('é'));
+        assert_eq!(parse_single_expr("'\"';")?, Expr::Char('"'));
+        for (src, escape) in [
+            (r#"let s = "bad \q";"#, r"\q"),
+            (r#"let s = "\x80";"#, r"\x80"),
+            (r#"let s = "\x4";"#, r"\x4"),
+            (r#"let s = "\u{D800}";"#, r"\u{D800}"),
+            (r#"let s = "\u{1234567}";"#, r"\u{1234567}"),
+            (r"let c = '\u41';", r"\u"),
+        ] {
+            let err = parse_program(src).unwrap_err().to_string();
+            assert!(err.contains(&format!("Invalid escape sequence: \x1b[91m{}\x1b[0m", escape)), "{}", err);
+        }
+        Ok(())
+    }
+
+    /// Parses `commented` and checks it gives the same program as `plain`
+    fn assert_comments_ignored(commented: &str, plain: &str) -> anyhow::Result<()> {
+        assert_eq!(parse_program(commented)?, parse_program(plain)?, "{}", commented);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments_in_declarations() -> anyhow::Result<()> {
+        assert_comments_ignored(
+            r#"// leading comment
+            /* a block */ fn /* name */ add /* params */ ( /* a */ a /* : */ : /* ty */ i64 /* , */ , b: i64 /* ) */ ) /* -> */ -> /* ret */ i64 // trailing
+            /* body */ { // open
+                /* expr */ a /* op */ + // end of line
+                b /* tail */
+            } // after
+            /* between */
+            fn main() { /* empty */ } /* eof */ // eof line"#,
+            "fn add(a: i64, b: i64) -> i64 { a + b } fn main() { }",
+        )
+    }
+
+    #[test]
+    fn test_comments_in_statements() -> anyhow::Result<()> {
+        assert_comments_ignored(
+            r#"fn f() {
+                let /* m */ mut /* n */ x /* : */ : /* t */ u8 /* = */ = /* v */ 1 /* ; */ ; // done
+                x /* op */ += /* rhs */ 2; /* between */ x = x // first
+                    * /* mul */ 3;
+                /* if */ if /* cond */ x > 1 /* then */ { x = 0; } /* else */ else /* if */ if x < 0 { } else // else
+                { }
+                'outer /* : */ : /* while */ while /* c */ true { break /* label */ 'outer; }
+                for /* i */ i /* in */ in /* s */ 0 /* .. */ .. /* e */ 10 /* block */ { continue; }
+                return /* value */ x;
+            }"#,
+            "fn f() { let mut x: u8 = 1; x += 2; x = x * 3; if x > 1 { x = 0; } else if x < 0 { } else { } 'outer: while true { break 'outer; } for i in 0..10 { continue; } return x; }",
+        )
+    }
+
+    #[test]
+    fn test_comments_in_expressions() -> anyhow::Result<()> {
+        assert_comments_ignored(
+            r#"fn f() {
+                let a = /* unary */ - /* neg */ ( /* paren */ 1 /* op */ + 2 /* close */ ) as /* ty */ i64;
+                let b = call( /* first */ 1 /* , */ , /* second */ [ /* arr */ 1, // one
+                    2 /* two */ ] /* done */ );
+                let c = std::args[1] /* idx */ ;
+                let d = if /* c */ true { /* then */ 1 } else { 2 /* else */ };
+                let e = "// not /* a comment */" /* but this is */;
+                let g = 10 /* / */ / /* div */ 2 // trailing /* unclosed
+                ;
+            }"#,
+            r#"fn f() { let a = -(1 + 2) as i64; let b = call(1, [1, 2]); let c = std::args[1]; let d = if true { 1 } else { 2 }; let e = "// not /* a comment */"; let g = 10 / 2; }"#,
+        )
+    }
+
+    #[test]
+    fn test_nested_block_comments() -> anyhow::Result<()> {
+        assert_comments_ignored(
+            "/* outer /* inner */ still /* deeper /* deepest */ */ comment */ fn f() { /**/ 1 /***/ }",
+            "fn f() { 1 }",
+        )?;
+        let err = parse_program("fn f() { } /* open /* nested */").unwrap_err();
+        assert!(err.to_string().contains("Unterminated block comment"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_annotations() -> anyhow::Result<()> {
+        let parsed = parse_program("fn greet(name: str, times: u8, raw: *i8) -> i64 { let mut count: i64 = 0; count }")?;
+        let int = |bits, signed| Type::Int { bits, signed };
+        assert_eq!(
+            parsed,
+            vec![Stmt::FunctionDeclaration {
+                ident: "greet".to_string(),
+                type_params: vec![],
+                params: vec![
+                    Param { ident: "name".to_string(), ty: Some(Type::Str) },
+                    Param { ident: "times".to_string(), ty: Some(int(8, false)) },
+                    Param { ident: "raw".to_string(), ty: Some(Type::Ptr(Box::new(int(8, true)))) },
+                ],
+                return_type: Some(int(64, true)),
+                body: vec![
+                    Stmt::Assignment {
+                        ident: "count".to_string(),
+                        mutable: true,
+                        ty: Some(int(64, true)),
+                        expr: Expr::Num(0.into()),
+                        span: Span::default(),
+                    },
+                    Stmt::Expression(ident("count"), Span::default()),
+                ],
+                public: false,
+                span: Span::default(),
+            }]
+        );
+        // annotations are optional and can be mixed
+        let parsed = parse_program("fn log(a, b: bool) -> () { }")?;
+        assert_eq!(
+            parsed,
+            vec![Stmt::FunctionDeclaration {
+                ident: "log".to_string(),
+                type_params: vec![],
+                params: vec![param("a"), Param { ident: "b".to_string(), ty: Some(Type::Bool) }],
+                return_type: Some(Type::Unit),
+                body: vec![],
+                public: false,
+                span: Span::default(),
+            }]
+        );
+        // any other name is taken to be a struct or enum, the type checker reports it if it isn't declared
+        assert_eq!(
+            parse_program("let x: i33 = 1;")?,
+            vec![Stmt::Assignment { ident: "x".to_string(), mutable: false, ty: Some(Type::Named("i33".to_string())), expr: Expr::Num(1.into()), span: Span::default() }]
+        );
+        assert!(parse_program("let x: 33 = 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct_declaration_and_literal() -> anyhow::Result<()> {
+        let parsed = parse_program("struct Point { x: i32, y: f64, } struct Line { from: Point, to: Point }")?;
+        let field = |ident: &str, ty| Field { ident: ident.to_string(), ty };
+        assert_eq!(
+            parsed,
+            vec![
+                Stmt::StructDeclaration {
+                    ident: "Point".to_string(),
+                    fields: vec![field("x", Type::I32), field("y", Type::F64)],
+                    public: false,
+                    span: Span::default(),
+                },
+                Stmt::StructDeclaration {
+                    ident: "Line".to_string(),
+                    fields: vec![field("from", Type::Named("Point".to_string())), field("to", Type::Named("Point".to_string()))],
+                    public: false,
+                    span: Span::default(),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_single_expr("Point { y: 2.0, x: a + 1 };")?,
+            Expr::StructLiteral("Point".to_string(), vec![
+                ("y".to_string(), Expr::Float(2.0)),
+                ("x".to_string(), infix(ident("a"), BinaryOp::Add, Expr::Num(1.into()))),
+            ])
+        );
+        // a lowercase name followed by a block is a condition, not a literal
+        assert!(matches!(
+            &parse_program("fn f() { if done { x = 1; } }")?[..],
+            [Stmt::FunctionDeclaration { body, .. }] if matches!(&body[..], [Stmt::If { condition: Expr::Ident(_), .. }])
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_access_and_assignment() -> anyhow::Result<()> {
+        let field = |expr, name: &str| Expr::FieldAccess(Box::new(expr), name.to_string());
+        assert_eq!(
+            parse_single_expr("line.from.x * -p.y;")?,
+            infix(
+                field(field(ident("line"), "from"), "x"),
+                BinaryOp::Multiply,
+                Expr::Unary(UnaryOp::Negate, Box::new(field(ident("p"), "y"))),
+            )
+        );
+        assert_eq!(parse_single_expr("origin().x;")?, field(Expr::Call("origin".to_string(), vec![]), "x"));
+        assert_eq!(
+            parse_program("line.to.y += 2;")?,
+            vec![Stmt::FieldAssignment {
+                ident: "line".to_string(),
+                fields: vec!["to".to_string(), "y".to_string()],
+                op: Some(BinaryOp::Add),
+                expr: Expr::Num(2.into()),
+                span: Span::default(),
+            }]
+        );
+        // `..` after a number is still a range
+        assert!(matches!(&parse_program("for i in 0..p.x { }")?[..], [Stmt::For { end: Expr::FieldAccess(..), .. }]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enum_declaration_and_variants() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_program("enum Shape { Circle(i32), Rect(i32, u8), Empty, }")?,
+            vec![Stmt::EnumDeclaration {
+                ident: "Shape".to_string(),
+                variants: vec![
+                    Variant { ident: "Circle".to_string(), fields: vec![Type::I32] },
+                    Variant { ident: "Rect".to_string(), fields: vec![Type::I32, Type::Int { bits: 8, signed: false }] },
+                    Variant { ident: "Empty".to_string(), fields: vec![] },
+                ],
+                public: false,
+                span: Span::default(),
+            }]
+        );
+        assert_eq!(
+            parse_single_expr("Shape::Rect(w, w + 1);")?,
+            Expr::EnumVariant {
+                enum_name: "Shape".to_string(),
+                variant: "Rect".to_string(),
+                args: vec![ident("w"), infix(ident("w"), BinaryOp::Add, Expr::Num(1.into()))],
+            }
+        );
+        assert_eq!(
+            parse_single_expr("Shape::Empty;")?,
+            Expr::EnumVariant { enum_name: "Shape".to_string(), variant: "Empty".to_string(), args: vec![] }
+        );
+        // lowercase paths are still stdlib paths
+        assert_eq!(parse_single_expr("std::printf;")?, Expr::QualifiedIdent(vec!["std".to_string(), "printf".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_expression() -> anyhow::Result<()> {
+        let parsed = parse_single_expr(
+            "match shape { Shape::Rect(w, _) if w > 2 => { w } Shape::Circle(-1) => 0, Shape::Empty => 1, other => 2 };",
+        )?;
+        let variant = |name: &str, fields| Pattern::Variant { enum_name: "Shape".to_string(), variant: name.to_string(), fields };
+        let body = |expr| vec![Stmt::Expression(expr, Span::default())];
+        assert_eq!(
+            parsed,
+            Expr::Match {
+                scrutinee: Box::new(ident("shape")),
+                arms: vec![
+                    MatchArm {
+                        pattern: variant("Rect", vec![Pattern::Binding("w".to_string()), Pattern::Wildcard]),
+                        guard: Some(infix(ident("w"), BinaryOp::GreaterThan, Expr::Num(2.into()))),
+                        body: body(ident("w")),
+                    },
+                    MatchArm {
+                        pattern: variant("Circle", vec![Pattern::Literal(Expr::Num((-1).into()))]),
+                        guard: None,
+                        body: body(Expr::Num(0.into())),
+                    },
+                    MatchArm { pattern: variant("Empty", vec![]), guard: None, body: body(Expr::Num(1.into())) },
+                    MatchArm { pattern: Pattern::Binding("other".to_string()), guard: None, body: body(Expr::Num(2.into())) },
+                ],
+            }
+        );
+        assert!(matches!(
+            &parse_single_expr("match c { 'a' => true, \"s\" => false, true => x };")?,
+            Expr::Match { arms, .. } if matches!(
+                arms.iter().map(|arm| &arm.pattern).collect::<Vec<_>>()[..],
+                [Pattern::Literal(Expr::Char('a')), Pattern::Literal(Expr::Str(_)), Pattern::Literal(Expr::Bool(true))]
+            )
+        ));
+        assert!(parse_program("let x = match y { => 1 };").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_closures_and_function_types() -> anyhow::Result<()> {
+        let body = |expr| vec![Stmt::Expression(expr, Span::default())];
+        assert_eq!(
+            parse_single_expr("|x, y: i64| x + y;")?,
+            Expr::Closure {
+                params: vec![param("x"), Param { ident: "y".to_string(), ty: Some(Type::Int { bits: 64, signed: true }) }],
+                return_type: None,
+                body: body(infix(ident("x"), BinaryOp::Add, ident("y"))),
+            }
+        );
+        assert_eq!(
+            parse_single_expr("|| -> bool { true };")?,
+            Expr::Closure { params: vec![], return_type: Some(Type::Bool), body: body(Expr::Bool(true)) }
+        );
+        // a closure returning a closure
+        assert_eq!(
+            parse_single_expr("|x| |y| x;")?,
+            Expr::Closure {
+                params: vec![param("x")],
+                return_type: None,
+                body: body(Expr::Closure { params: vec![param("y")], return_type: None, body: body(ident("x")) }),
+            }
+        );
+        let parsed = parse_program("fn apply(f: fn(i32, bool) -> u8, g: fn()) -> fn(i32) { f }")?;
+        assert!(matches!(
+            &parsed[..],
+            [Stmt::FunctionDeclaration { params, return_type: Some(ret), .. }]
+                if params[0].ty == Some(Type::Function(vec![Type::I32, Type::Bool], Box::new(Type::Int { bits: 8, signed: false })))
+                    && params[1].ty == Some(Type::Function(vec![], Box::new(Type::I32)))
+                    && *ret == Type::Function(vec![Type::I32], Box::new(Type::I32))
+        ));
+        assert_eq!(Type::Function(vec![Type::I32, Type::Bool], Box::new(Type::Unit)).to_string(), "fn(i32, bool) -> ()");
+        assert!(parse_program("let f = |x x;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generic_functions() -> anyhow::Result<()> {
+        let parsed = parse_program("fn max<T>(a: T, b: T) -> T { a } fn pair< A , B >(a: A, b: B) {}")?;
+        let t = || Some(Type::Named("T".to_string()));
+        assert!(matches!(
+            &parsed[..],
+            [Stmt::FunctionDeclaration { type_params: max, params, return_type, .. }, Stmt::FunctionDeclaration { type_params: pair, .. }]
+                if *max == ["T"] && params[0].ty == t() && params[1].ty == t() && *return_type == t() && *pair == ["A", "B"]
+        ));
+        assert_eq!(parse_single_expr("max(1, 2);")?, Expr::Call("max".to_string(), vec![Expr::Num(1.into()), Expr::Num(2.into())]));
+        assert!(parse_program("fn max<>(a: i32) {}").is_err());
+        assert_eq!(instance_name("max", &[Type::Int { bits: 8, signed: false }, Type::Named("Point".to_string())]), "max<u8, Point>");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuples() -> anyhow::Result<()> {
+        assert_eq!(parse_single_expr("(a, 1);")?, Expr::Tuple(vec![ident("a"), Expr::Num(1.into())]));
+        assert_eq!(parse_single_expr("(a,);")?, Expr::Tuple(vec![ident("a")]));
+        assert_eq!(parse_single_expr("(a);")?, ident("a"));
+        assert_eq!(parse_single_expr("( a , b , );")?, Expr::Tuple(vec![ident("a"), ident("b")]));
+        assert_eq!(
+            parse_single_expr("t.0.1;")?,
+            Expr::FieldAccess(Box::new(Expr::FieldAccess(Box::new(ident("t")), "0".to_string())), "1".to_string())
+        );
+        assert!(matches!(
+            &parse_program("let t: (i32, (bool,)) = x;")?[..],
+            [Stmt::Assignment { ty: Some(Type::Tuple(types)), .. }]
+                if *types == [Type::Int { bits: 32, signed: true }, Type::Tuple(vec![Type::Bool])]
+        ));
+        assert!(matches!(
+            &parse_program("let (q, _) : (i32, i32) = divmod(a, b);")?[..],
+            [Stmt::Destructure { pattern: Pattern::Tuple(patterns), ty: Some(Type::Tuple(_)), expr: Expr::Call(name, _), .. }]
+                if *patterns == [Pattern::Binding("q".to_string()), Pattern::Wildcard] && name == "divmod"
+        ));
+        assert!(matches!(
+            parse_single_expr("match t { (0, x) => x, _ => 1 };")?,
+            Expr::Match { arms, .. } if arms[0].pattern == Pattern::Tuple(vec![Pattern::Literal(Expr::Num(0.into())), Pattern::Binding("x".to_string())])
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays_and_vecs() -> anyhow::Result<()> {
+        assert!(matches!(
+            &parse_program("let a: [i32; 3] = [1, 2, 3]; let mut v: Vec<u8> = Vec::new();")?[..],
+            [
+                Stmt::Assignment { ty: Some(Type::Array(element, 3)), expr: Expr::Array(elements), .. },
+                Stmt::Assignment { ty: Some(Type::Vec(byte)), mutable: true, expr, .. },
+            ] if **element == Type::Int { bits: 32, signed: true } && elements.len() == 3
+                && **byte == Type::Int { bits: 8, signed: false } && expr.is_vec_new()
+        ));
+        assert_eq!(
+            parse_single_expr("v.push(1);")?,
+            Expr::MethodCall { receiver: Box::new(ident("v")), method: "push".to_string(), args: vec![Expr::Num(1.into())] }
+        );
+        assert_eq!(
+            parse_single_expr("p.items.len();")?,
+            Expr::MethodCall {
+                receiver: Box::new(Expr::FieldAccess(Box::new(ident("p")), "items".to_string())),
+                method: "len".to_string(),
+                args: vec![],
+            }
+        );
+        assert!(matches!(
+            &parse_program("a[i] = 1; grid[i][j] += 1;")?[..],
+            [
+                Stmt::IndexAssignment { ident: a, indices, op: None, .. },
+                Stmt::IndexAssignment { ident: grid, indices: grid_indices, op: Some(BinaryOp::Add), .. },
+            ] if a == "a" && *indices == [ident("i")] && grid == "grid" && *grid_indices == [ident("i"), ident("j")]
+        ));
+        assert!(parse_program("let a: [i32; n] = x;").is_err());
+        assert!(parse_program("a[0].x = 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_slices() -> anyhow::Result<()> {
+        let slice = |array: &str, start: Option<Expr>, end: Option<Expr>| Expr::Slice(Box::new(ident(array)), start.map(Box::new), end.map(Box::new));
+        assert_eq!(parse_single_expr("arr[1..3];")?, slice("arr", Some(Expr::Num(1.into())), Some(Expr::Num(3.into()))));
+        assert_eq!(parse_single_expr("s[ ..n + 1];")?, slice("s", None, Some(Expr::Infix(Box::new(ident("n")), BinaryOp::Add, Box::new(Expr::Num(1.into()))))));
+        assert_eq!(parse_single_expr("s[i..];")?, slice("s", Some(ident("i")), None));
+        assert_eq!(parse_single_expr("s[..];")?, slice("s", None, None));
+        assert_eq!(
+            parse_single_expr("a[1..][0];")?,
+            Expr::ArrayIndexing(Box::new(slice("a", Some(Expr::Num(1.into())), None)), Box::new(Expr::Num(0.into())))
+        );
+        assert!(matches!(
+            &parse_program("fn sum(xs: &[i32]) -> i32 { 0 }")?[..],
+            [Stmt::FunctionDeclaration { params, .. }] if params[0].ty == Some(Type::Slice(Box::new(Type::I32)))
+        ));
+        assert!(parse_single_expr("a[];").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_declarations_and_paths() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_program("pub mod shapes; mod util { fn helper() {} } use shapes::area; pub use util::helper;")?,
+            vec![
+                Stmt::Module { ident: "shapes".to_string(), body: None, public: true, span: Span::default() },
+                Stmt::Module {
+                    ident: "util".to_string(),
+                    body: Some(vec![Stmt::FunctionDeclaration {
+                        ident: "helper".to_string(),
+                        type_params: vec![],
+                        params: vec![],
+                        return_type: None,
+                        body: vec![],
+                        public: false,
+                        span: Span::default(),
+                    }]),
+                    public: false,
+                    span: Span::default(),
+                },
+                Stmt::Use { path: vec!["shapes".to_string(), "area".to_string()], public: false, span: Span::default() },
+                Stmt::Use { path: vec!["util".to_string(), "helper".to_string()], public: true, span: Span::default() },
+            ]
+        );
+        assert!(matches!(
+            &parse_program("pub struct Point { x: i32 } pub enum E { A } pub fn f() {}")?[..],
+            [
+                Stmt::StructDeclaration { public: true, .. },
+                Stmt::EnumDeclaration { public: true, .. },
+                Stmt::FunctionDeclaration { public: true, .. },
+            ]
+        ));
+        assert_eq!(
+            parse_single_expr("shapes::area(geo::Point { x: 1 });")?,
+            Expr::Call(
+                "shapes::area".to_string(),
+                vec![Expr::StructLiteral("geo::Point".to_string(), vec![("x".to_string(), Expr::Num(1.into()))])],
+            )
+        );
+        assert_eq!(
+            parse_single_expr("shapes::Shape::Rect(1);")?,
+            Expr::EnumVariant { enum_name: "shapes::Shape".to_string(), variant: "Rect".to_string(), args: vec![Expr::Num(1.into())] }
+        );
+        assert_eq!(
+            parse_single_expr("x as shapes::Shape;")?,
+            Expr::Cast(Box::new(ident("x")), Type::Named("shapes::Shape".to_string()))
+        );
+        assert_eq!(parse_single_expr("shapes::area;")?, Expr::QualifiedIdent(vec!["shapes".to_string(), "area".to_string()]));
+        assert!(parse_program("mod;").is_err());
+        assert!(parse_program("use shapes::area").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_loading_module_files() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("red-modules-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shapes"))?;
+        std::fs::write(dir.join("main.ak"), "mod shapes;\nuse shapes::Shape;\nfn main() { let s = Shape::Rect(2, 3); shapes::area(s); }\n")?;
+        std::fs::write(
+            dir.join("shapes.ak"),
+            "pub mod circle;\npub enum Shape { Rect(i32, i32) }\npub fn area(s: Shape) -> i32 { circle::unit() }\n",
+        )?;
+        std::fs::write(dir.join("shapes").join("circle.ak"), "pub fn unit() -> i32 { secret() }\nfn secret() -> i32 { 1 }\n")?;
+
+        let program = load_program(&dir.join("main.ak"))?;
+        let names = program
+            .ast
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::FunctionDeclaration { ident, .. } | Stmt::EnumDeclaration { ident, .. } => Some(ident.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["shapes::circle::unit", "shapes::circle::secret", "shapes::Shape", "shapes::area", USER_DEFINED_ENTRY]);
+        assert_eq!(program.files.len(), 3);
+        assert!(matches!(
+            &program.ast[3],
+            Stmt::FunctionDeclaration { params, body, .. }
+                if params[0].ty == Some(Type::Named("shapes::Shape".to_string()))
+                    && body[0] == Stmt::Expression(Expr::Call("shapes::circle::unit".to_string(), vec![]), Span::default())
+        ));
+        assert!(matches!(
+            &program.ast[4],
+            Stmt::FunctionDeclaration { body, .. }
+                if matches!(&body[0], Stmt::Assignment { expr: Expr::EnumVariant { enum_name, .. }, .. } if enum_name == "shapes::Shape")
+                    && matches!(&body[1], Stmt::Expression(Expr::Call(name, _), _) if name == "shapes::area")
+        ));
+        // spans in module files point into them
+        assert_eq!(program.location(match &program.ast[1] {
+            Stmt::FunctionDeclaration { span, .. } => *span,
+            _ => unreachable!(),
+        }), format!("{}:2:1", dir.join("shapes").join("circle.ak").display()));
+
+        std::fs::write(dir.join("main.ak"), "mod shapes;\nmod missing;\n")?;
+        let err = load_program(&dir.join("main.ak")).err().unwrap().to_string();
+        assert!(err.starts_with("2:1: Cannot find module `missing`"), "{}", err);
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_resolution_errors() -> anyhow::Result<()> {
+        let errors = |src: &str| match load_source(src, std::path::Path::new(".")) {
+            Ok(_) => vec![],
+            Err(err) => err.to_string().lines().skip(1).map(String::from).collect::<Vec<_>>(),
+        };
+        let src = r#"
+mod a {
+    fn hidden() {}
+    pub fn shown() { hidden() }
+    pub mod b { pub fn f() { super::hidden() } }
+    mod c { pub fn g() {} }
+    let x = 1;
+}
+fn main() {
+    a::hidden();
+    a::c::g();
+    a::nothing();
+    a::shown::f();
+    a::b();
+    a::b::f();
+}
+use a::gone;
+"#;
+        assert_eq!(
+            errors(src),
+            vec![
+                "7:5: Only functions, structs, enums, `impl`, `const`, `static`, `mod` and `use` can be declared in module `a`",
+                "10:5: `hidden` is private to module `a`",
+                "11:5: `c` is private to module `a`",
+                "12:5: Module `a` has no item `nothing`",
+                "13:5: `a::shown` is a function, not a module",
+                "14:5: `a::b` is a module, expected a function",
+                "17:1: Module `a` has no item `gone`",
+            ]
+        );
+        // local variables shadow items, `pub use` re-exports an item under the module holding it
+        let program = load_source(
+            "mod m { pub fn f() {} }\nmod n { pub use m::f; }\nfn main() { let f = |x| x; f(1); n::f(); }",
+            std::path::Path::new("."),
+        )?;
+        assert!(matches!(
+            &program.ast[..],
+            [_, Stmt::FunctionDeclaration { body, .. }]
+                if matches!(&body[1], Stmt::Expression(Expr::Call(name, _), _) if name == "f")
+                    && matches!(&body[2], Stmt::Expression(Expr::Call(name, _), _) if name == "m::f")
+        ));
+        assert_eq!(
+            errors("mod m { use nothing::x; }\nmod p { pub use q::y; }\nmod q { pub use p::y; }\nfn main() { p::y(); }"),
+            vec![
+                "1:9: Unresolved import `nothing::x`",
+                "2:9: `use` declarations for `y` refer to each other",
+                "3:9: `use` declarations for `y` refer to each other",
+                "4:13: `use` declarations for `y` refer to each other",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_macros() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_single_expr("println!(\"x = {}\", x);")?,
+            Expr::MacroCall("println".to_string(), vec![Expr::Str("x = {}".to_string()), ident("x")])
+        );
+        assert_eq!(
+            parse_single_expr("format!(\"{}{}\", a, b + 1);")?,
+            Expr::MacroCall(
+                "format".to_string(),
+                vec![Expr::Str("{}{}".to_string()), ident("a"), Expr::Infix(Box::new(ident("b")), BinaryOp::Add, Box::new(Expr::Num(1.into())))]
+            )
+        );
+        // `!=` is still a comparison
+        assert!(matches!(parse_single_expr("a != b;")?, Expr::Infix(_, BinaryOp::NotEqual, _)));
+        assert_eq!(
+            parse_format_string("{{x}} = {}, {}!"),
+            Ok(vec![
+                FormatPiece::Text("{x} = ".to_string()),
+                FormatPiece::Placeholder,
+                FormatPiece::Text(", ".to_string()),
+                FormatPiece::Placeholder,
+                FormatPiece::Text("!".to_string()),
+            ])
+        );
+        assert!(parse_format_string("{x}").is_err());
+        assert!(parse_format_string("}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_globals() -> anyhow::Result<()> {
+        assert!(matches!(
+            &parse_program("const LIMIT: i32 = 10; pub static mut COUNTER: u8 = 0; static NAME: str = \"red\";")?[..],
+            [
+                Stmt::Global { ident: limit, kind: GlobalKind::Const, ty: Type::Int { bits: 32, signed: true }, public: false, .. },
+                Stmt::Global { ident: counter, kind: GlobalKind::StaticMut, ty: Type::Int { bits: 8, signed: false }, public: true, .. },
+                Stmt::Global { ident: name, kind: GlobalKind::Static, ty: Type::Str, expr: Expr::Str(s), .. },
+            ] if limit == "LIMIT" && counter == "COUNTER" && name == "NAME" && s == "red"
+        ));
+        // the type can't be inferred
+        assert!(parse_program("const LIMIT = 10;").is_err());
+        // a capitalised name before a block isn't a struct literal
+        assert!(matches!(
+            &parse_program("while i < LIMIT { i += 1; }")?[..],
+            [Stmt::While { condition: Expr::Infix(_, BinaryOp::LessThan, limit), body, .. }] if **limit == ident("LIMIT") && body.len() == 1
+        ));
+        // globals in a module are named by their path, and resolved like functions
+        let program = load_source(
+            "mod config { pub const LIMIT: i32 = 3; pub static mut HITS: i32 = 0; pub fn hit() { HITS += 1; } }\nfn main() { config::LIMIT; }",
+            std::path::Path::new("."),
+        )?;
+        assert!(matches!(
+            &program.ast[..],
+            [Stmt::Global { ident: limit, .. }, Stmt::Global { ident: hits, .. }, Stmt::FunctionDeclaration { body: hit, .. }, Stmt::FunctionDeclaration { body: main, .. }]
+                if limit == "config::LIMIT" && hits == "config::HITS"
+                    && matches!(&hit[0], Stmt::Reassignment { ident, .. } if ident == "config::HITS")
+                    && matches!(&main[0], Stmt::Expression(Expr::Ident(name), _) if name == "config::LIMIT")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> anyhow::Result<()> {
+        assert!(matches!(
+            &parse_program("impl Point { fn len(self) -> i32 { self.x + self.y } pub fn new(x: i32, y: i32) -> Point { Point { x: x, y: y } } }")?[..],
+            [Stmt::Impl { ty, functions, .. }] if ty == "Point" && matches!(&functions[..], [
+                Stmt::FunctionDeclaration { ident: len, params: len_params, public: false, .. },
+                Stmt::FunctionDeclaration { ident: new, params: new_params, public: true, .. },
+            ] if len == "len" && new == "new"
+                && len_params[..] == [Param { ident: "self".to_string(), ty: Some(Type::Named("Point".to_string())) }]
+                && new_params.len() == 2)
+        ));
+        // only functions can be declared in an impl block
+        assert!(parse_program("impl Point { let x = 1; }").is_err());
+        // the functions of an impl block are named by the type, and `Type::function` calls them
+        let program = load_source(
+            "struct Point { x: i32, y: i32 }\nimpl Point { fn len(self) -> i32 { self.x + self.y } fn new(x: i32, y: i32) -> Point { Point { x: x, y: y } } }\nfn main() { Point::new(1, 2).len(); }",
+            std::path::Path::new("."),
+        )?;
+        assert!(matches!(
+            &program.ast[..],
+            [Stmt::StructDeclaration { .. }, Stmt::FunctionDeclaration { ident: len, .. }, Stmt::FunctionDeclaration { ident: new, .. }, Stmt::FunctionDeclaration { body: main, .. }]
+                if len == "Point::len" && new == "Point::new"
+                    && matches!(&main[0], Stmt::Expression(Expr::MethodCall { receiver, method, .. }, _)
+                        if method == "len" && matches!(&**receiver, Expr::Call(name, args) if name == "Point::new" && args.len() == 2))
+        ));
+        let errors = match load_source("fn len() {}\nimpl len { fn f() {} }\nimpl Missing { fn f() {} }", std::path::Path::new(".")) {
+            Ok(_) => vec![],
+            Err(err) => err.to_string().lines().skip(1).map(String::from).collect::<Vec<_>>(),
+        };
+        assert_eq!(errors, vec!["2:1: `len` is a function, only structs and enums can have an `impl` block", "3:1: Cannot find struct or enum `Missing`"]);
+        Ok(())
+    }
 }
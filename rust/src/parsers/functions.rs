@@ -2,55 +2,70 @@
 use std::fmt;
 
 
+use nom::branch::alt;
 use nom::bytes::complete::tag;
 
 
-use nom::character::complete::multispace0;
-use nom::combinator::opt;
+use nom::combinator::{opt, value};
 
-use nom::multi::{separated_list0, many0};
-use nom::sequence::delimited;
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{delimited, pair, preceded};
 
 
 
 use crate::llvm::USER_DEFINED_ENTRY;
-use crate::llvm::ast::{Expr, Stmt};
-use crate::parsers::tokens::parse_identifier;
+use crate::llvm::ast::{Expr, Param, Span, Stmt, Type};
+use crate::parsers::tokens::{keyword, parse_identifier, parse_name, parse_visibility, skip_space};
 
 
-use super::{ParseResult, parse_statement};
+use super::ParseResult;
 
 use super::error::CustomError;
-use super::expressions::expression;
+use super::expressions::{expression, parse_infix_expr};
 
-use super::statements::space_opt;
+use super::statements::{parse_block, space_opt};
+use super::types::{parse_type, parse_type_annotation};
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Expr::Ident(s) => write!(f, "Identifier({})", s),
             Expr::Num(n) => write!(f, "Number({})", n),
+            Expr::Float(n) => write!(f, "Float({})", n),
             Expr::Str(s) => write!(f, "String({})", s),
             Expr::Char(c) => write!(f, "Char({})", c),
+            Expr::Bool(b) => write!(f, "Bool({})", b),
             Expr::Infix(op, left, right) => write!(f, "Infix({} {} {})", op, left, right),
+            Expr::Unary(op, expr) => write!(f, "Unary({}{})", op, expr),
+            Expr::Cast(expr, ty) => write!(f, "Cast({} as {})", expr, ty),
+            Expr::If { condition, then_branch, else_branch } => write!(f, "If({} {:?} else {:?})", condition, then_branch, else_branch),
             Expr::Call(ident, args) => write!(f, "Call({} {})", ident, args.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
             Expr::QualifiedIdent(idents) => write!(f, "QualifiedIdent({:#?})", idents),
             Expr::ArrayIndexing(array, index) => write!(f, "ArrayIndexing({} {})", array, index),
+            Expr::Slice(array, start, end) => write!(f, "Slice({} {}..{})", array, start.as_ref().map(|e| e.to_string()).unwrap_or_default(), end.as_ref().map(|e| e.to_string()).unwrap_or_default()),
             Expr::Array(array) => write!(f, "Array({})", array.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
+            Expr::StructLiteral(name, fields) => write!(f, "StructLiteral({} {{ {} }})", name, fields.iter().map(|(field, e)| format!("{}: {}", field, e)).collect::<Vec<String>>().join(", ")),
+            Expr::FieldAccess(expr, field) => write!(f, "FieldAccess({}.{})", expr, field),
+            Expr::EnumVariant { enum_name, variant, args } => write!(f, "EnumVariant({}::{}({}))", enum_name, variant, args.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
+            Expr::Match { scrutinee, arms } => write!(f, "Match({} {:?})", scrutinee, arms),
+            Expr::Closure { params, return_type, body } => write!(f, "Closure(|{}| {:?} {:?})", params.iter().map(|param| param.ident.as_str()).collect::<Vec<_>>().join(", "), return_type, body),
+            Expr::Tuple(elements) => write!(f, "Tuple({})", elements.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
+            Expr::MacroCall(name, args) => write!(f, "MacroCall({}!({}))", name, args.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
+            Expr::MethodCall { receiver, method, args } => write!(f, "MethodCall({}.{}({}))", receiver, method, args.iter().map(|e| format!("{}", e)).collect::<Vec<String>>().join(", ")),
         }
     }
 }
 
-fn parse_parameters(input: &str) -> ParseResult<&str, Vec<String>> {
+fn parse_parameters(input: &str) -> ParseResult<&str, Vec<Param>> {
     let (input, params) = space_opt(delimited(
         tag("("),
-        separated_list0(space_opt(tag(",")), parse_identifier),
+        separated_list0(space_opt(tag(",")), pair(parse_identifier, opt(parse_type_annotation))),
         tag(")")
     ))(input)?;
 
-    let parsed_params = params.into_iter().map(|expr| {
-        if let Expr::Ident(s) = expr {
-            Ok(s)
+    let parsed_params = params.into_iter().map(|(expr, ty)| {
+        if let Expr::Ident(ident) = expr {
+            Ok(Param { ident, ty })
         } else {
             Err(nom::Err::Failure(CustomError::UnexpectedToken("Expected identifier in function parameter list")))
         }
@@ -59,16 +74,19 @@ fn parse_parameters(input: &str) -> ParseResult<&str, Vec<String>> {
     Ok((input, parsed_params))
 }
 
-fn parse_function_body(input: &str) -> ParseResult<&str, Vec<Stmt>> {
-    let (input, _) = multispace0(input)?;
-    let (input, _) = tag("{")(input)?;
-    let (input, _) = multispace0(input)?;
-    let (input, body) = many0(ws(parse_statement))(input)?;
-    let (input, _) = opt(tag(";"))(input)?;
-    let (input, _) = opt(multispace0)(input)?;
-    let (input, _) = tag("}")(input)?;
+/// parse the optional `-> T` after the parameter list
+fn parse_return_type(input: &str) -> ParseResult<&str, Option<Type>> {
+    opt(preceded(space_opt(tag("->")), parse_type))(input)
+}
 
-    Ok((input, body))
+/// the optional `<T, U>` after a function's name
+fn parse_type_params(input: &str) -> ParseResult<&str, Vec<String>> {
+    let (input, type_params) = opt(delimited(
+        space_opt(tag("<")),
+        separated_list1(space_opt(tag(",")), space_opt(parse_name)),
+        space_opt(tag(">")),
+    ))(input)?;
+    Ok((input, type_params.unwrap_or_default()))
 }
 
 fn parse_function_name(input: &str) -> ParseResult<&str, String> {
@@ -80,14 +98,16 @@ fn parse_function_name(input: &str) -> ParseResult<&str, String> {
 }
 
 /// parse the required function declaration ()
-fn parse_function(input: &str) -> ParseResult<&str, Stmt> {
+fn parse_function(input: &str, public: bool, span: Span) -> ParseResult<&str, Stmt> {
     let (input, ident) = parse_function_name(input)?;
+    let (input, type_params) = parse_type_params(input)?;
     let (input, params) = parse_parameters(input)?;
     if ident == "main" && !params.is_empty() {
         return Err(nom::Err::Failure(CustomError::MainFunctionWithParams(input)));
     }
-    
-    let (input, body) = parse_function_body(input)?;
+    let (input, return_type) = parse_return_type(input)?;
+
+    let (input, body) = parse_block(input)?;
 
     Ok((
         input, 
@@ -95,20 +115,52 @@ fn parse_function(input: &str) -> ParseResult<&str, Stmt> {
             ident: match ident == "main" {
                 true => USER_DEFINED_ENTRY.to_string(),
                 false => ident,
-            }, params, body 
+            }, type_params, params, return_type, body, public, span
         }
     ))
 }
 
+/// `|a, b: i32| a + b` or `|| -> f64 { 1.5 }`
+pub fn parse_closure(input: &str) -> ParseResult<&str, Expr> {
+    let (input, params) = space_opt(alt((
+        value(vec![], tag("||")),
+        delimited(
+            tag("|"),
+            separated_list0(space_opt(tag(",")), pair(space_opt(parse_name), opt(parse_type_annotation))),
+            space_opt(tag("|")),
+        ),
+    )))(input)?;
+    let params = params.into_iter().map(|(ident, ty)| Param { ident, ty }).collect();
+    let (input, return_type) = parse_return_type(input)?;
+    let (input, body) = parse_block_or_expr(input)?;
+    Ok((input, Expr::Closure { params, return_type, body }))
+}
+
+/// A block, or a single expression standing for a block holding just that expression
+pub fn parse_block_or_expr(input: &str) -> ParseResult<&str, Vec<Stmt>> {
+    alt((
+        parse_block,
+        |input| {
+            let (rest, _) = skip_space(input)?;
+            parse_infix_expr(rest).map(|(input, expr)| (input, vec![Stmt::Expression(expr, Span::new(rest))]))
+        },
+    ))(input)
+}
+
 pub fn parse_function_declaration(input: &str) -> ParseResult<&str, Stmt> {
-    let (input, _) = ws(tag("fn"))(input)?;
-    parse_function(input)
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, _) = space_opt(keyword("fn"))(input)?;
+    parse_function(input, public, span)
 }
 
 
 pub fn parse_expr_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
     let (input, expr) = expression(input)?;
-    Ok((input, Stmt::Expression(expr)))
+    Ok((input, Stmt::Expression(expr, span)))
 }
 
 pub fn ws<'a, T, F>(parser: F) -> impl Fn(&'a str) -> ParseResult<&'a str, T>
@@ -116,9 +168,7 @@ where
     F: Fn(&'a str) -> ParseResult<&'a str, T>,
 {
     move |input: &'a str| {
-        let (input, _) = opt(multispace0)(input)?;
-        let (input, _) = opt(tag("\n"))(input)?;
-        let (input, _) = opt(tag("\t"))(input)?;
+        let (input, _) = skip_space(input)?;
         parser(input)
     }
 }
@@ -1,10 +1,10 @@
 
 
-use nom::{IResult, multi::separated_list0, bytes::complete::tag, branch::alt, character::complete::multispace1, error::context, Finish};
+use nom::{IResult, multi::many0, sequence::{pair, preceded, terminated}, combinator::eof, bytes::complete::tag, branch::alt, error::context, Finish};
 
 use crate::llvm::ast::Stmt;
 
-use self::{ error::CustomError, functions::{parse_function_declaration, parse_expr_statement}, statements::{parse_return_statement, parse_let_statement}};
+use self::{ error::CustomError, functions::{parse_function_declaration, parse_expr_statement}, statements::{parse_return_statement, parse_global_declaration, parse_let_statement, parse_if_statement, parse_while_statement, parse_for_statement, parse_break_statement, parse_continue_statement, parse_reassignment_statement}, tokens::skip_space1, structs::{parse_impl_block, parse_struct_declaration}, enums::parse_enum_declaration, modules::{parse_module_declaration, parse_use_declaration}};
 
 mod functions;
 mod statements;
@@ -13,6 +13,11 @@ mod expressions;
 mod error;
 mod full_test;
 mod array;
+mod structs;
+mod enums;
+mod tuples;
+mod types;
+pub mod modules;
 
 pub type ParseResult<I, O> = IResult<I, O, CustomError<I>>;
 
@@ -20,14 +25,18 @@ fn statement_delimiter(input: &str) -> ParseResult<&str, &str> {
     let (input, _) = context(
         "stripper", alt((
             tag(";"),
-            multispace1
+            skip_space1
         ))
     )(input)?;
     Ok((input, ""))
 }
 
 pub fn parse_program(input: &str) -> anyhow::Result<Vec<Stmt>> {
-    let result = separated_list0(statement_delimiter, parse_statement)(input)
+    // statements that end in `;` or a block swallow the whitespace after them, so delimiters are optional
+    let result = terminated(
+        many0(preceded(many0(statement_delimiter), parse_statement)),
+        pair(many0(statement_delimiter), eof),
+    )(input)
         .finish()
         .map(|(_, stmts)| stmts);
 
@@ -43,8 +52,20 @@ pub fn parse_statement(input: &str) -> ParseResult<&str, Stmt> {
         "statement",
         alt((
             parse_function_declaration,
+            parse_struct_declaration,
+            parse_enum_declaration,
+            parse_impl_block,
+            parse_module_declaration,
+            parse_use_declaration,
+            parse_if_statement,
+            parse_while_statement,
+            parse_for_statement,
+            parse_break_statement,
+            parse_continue_statement,
             parse_return_statement,
+            parse_global_declaration,
             parse_let_statement,
+            parse_reassignment_statement,
             parse_expr_statement,
         )),
     )(input)
@@ -0,0 +1,920 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::combinator::value;
+use nom::Parser;
+
+use crate::llvm::ast::{Ast, Expr, Pattern, Span, Stmt, Type};
+
+use super::statements::{parse_block, space_opt};
+use super::tokens::{keyword, parse_name, parse_path, parse_visibility, skip_space};
+use super::{parse_program, ParseResult};
+
+/// Extension of source files, `mod shapes;` loads `shapes.ak`
+pub const SOURCE_EXTENSION: &str = "ak";
+
+/// `mod shapes;` or `mod shapes { ... }`
+pub fn parse_module_declaration(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, _) = space_opt(keyword("mod"))(input)?;
+    let (input, ident) = space_opt(parse_name)(input)?;
+    let (input, body) = alt((value(None, tag(";")), parse_block.map(Some)))(input)?;
+    Ok((
+        input,
+        Stmt::Module {
+            ident,
+            body,
+            public,
+            span,
+        },
+    ))
+}
+
+/// `use shapes::area;`
+pub fn parse_use_declaration(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, _) = space_opt(keyword("use"))(input)?;
+    let (input, path) = space_opt(parse_path)(input)?;
+    let (input, _) = tag(";")(input)?;
+    Ok((input, Stmt::Use { path, public, span }))
+}
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// A program with the items of its modules flattened into it. An item declared in module
+/// `shapes` is named `shapes::item` everywhere, which is also its symbol in the LLVM module.
+pub struct Program {
+    pub ast: Ast,
+    /// the root file first, then every module file, as indexed by `Span::file`
+    pub files: Vec<SourceFile>,
+}
+
+impl Program {
+    /// `line:column` of a span in the root file, `path:line:column` in a module file
+    pub fn location(&self, span: Span) -> String {
+        let file = &self.files[span.file()];
+        let (line, column) = span.location(&file.source);
+        match span.file() {
+            0 => format!("{}:{}", line, column),
+            _ => format!("{}:{}:{}", file.path.display(), line, column),
+        }
+    }
+}
+
+/// Parses the file at `path` together with the files of the modules it declares
+pub fn load_program(path: &Path) -> anyhow::Result<Program> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    load(
+        SourceFile {
+            path: path.to_owned(),
+            source,
+        },
+        dir,
+    )
+}
+
+/// Parses `source` with the files of its `mod name;` declarations looked up in `dir`
+pub fn load_source(source: &str, dir: &Path) -> anyhow::Result<Program> {
+    let root = SourceFile {
+        path: PathBuf::from("<input>"),
+        source: source.to_owned(),
+    };
+    load(root, dir)
+}
+
+fn load(root: SourceFile, dir: &Path) -> anyhow::Result<Program> {
+    let ast = parse_program(&root.source)?;
+    let mut program = Program {
+        ast: vec![],
+        files: vec![root],
+    };
+    let mut module_files = HashMap::from([(vec![], 0)]);
+    let ast = load_modules(ast, &mut program, &mut module_files, &[], dir, 0)?;
+
+    let mut resolver = Resolver::new(module_files);
+    resolver.declare(&[], &ast);
+    resolver.declare_impls();
+    resolver.flatten(&[], ast, &mut program.ast);
+    if !resolver.errors.is_empty() {
+        let report = resolver
+            .errors
+            .iter()
+            .map(|(span, message)| format!("{}: {}", program.location(*span), message))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(anyhow!(
+            "Found {} module error(s):\n{}",
+            resolver.errors.len(),
+            report
+        ));
+    }
+    Ok(program)
+}
+
+/// Fills in the body of every `mod name;` from `dir/name.ak`. A module's own modules live in
+/// the directory named after it, `mod circle;` in `shapes.ak` loads `shapes/circle.ak`.
+fn load_modules(
+    stmts: Vec<Stmt>,
+    program: &mut Program,
+    module_files: &mut HashMap<Vec<String>, usize>,
+    module: &[String],
+    dir: &Path,
+    file: usize,
+) -> anyhow::Result<Vec<Stmt>> {
+    let mut loaded = Vec::with_capacity(stmts.len());
+    for stmt in stmts {
+        let Stmt::Module {
+            ident,
+            body,
+            public,
+            span,
+        } = stmt
+        else {
+            loaded.push(stmt);
+            continue;
+        };
+        let path = item_path(module, &ident);
+        let (body, file) = match body {
+            Some(body) => (body, file),
+            None => {
+                let file_path = dir.join(format!("{}.{}", ident, SOURCE_EXTENSION));
+                let source = std::fs::read_to_string(&file_path).with_context(|| {
+                    format!(
+                        "{}: Cannot find module `{}`, expected it in {}",
+                        program.location(span.in_file(file)),
+                        ident,
+                        file_path.display()
+                    )
+                })?;
+                let body = parse_program(&source)
+                    .with_context(|| format!("In module file {}", file_path.display()))?;
+                program.files.push(SourceFile {
+                    path: file_path,
+                    source,
+                });
+                (body, program.files.len() - 1)
+            }
+        };
+        module_files.insert(path.clone(), file);
+        let body = load_modules(body, program, module_files, &path, &dir.join(&ident), file)?;
+        loaded.push(Stmt::Module {
+            ident,
+            body: Some(body),
+            public,
+            span,
+        });
+    }
+    Ok(loaded)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemKind {
+    Function,
+    Struct,
+    Enum,
+    Module,
+    Global, // `const` or `static`
+}
+
+impl std::fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemKind::Function => write!(f, "function"),
+            ItemKind::Struct => write!(f, "struct"),
+            ItemKind::Enum => write!(f, "enum"),
+            ItemKind::Module => write!(f, "module"),
+            ItemKind::Global => write!(f, "const or static"),
+        }
+    }
+}
+
+enum Target {
+    Declared(ItemKind),
+    Import(Vec<String>), // the path of a `use`, resolved from the module holding it
+}
+
+struct Item {
+    target: Target,
+    public: bool,
+}
+
+/// An item a path refers to, `path` is where it's declared
+struct Resolved {
+    kind: ItemKind,
+    path: Vec<String>,
+}
+
+/// An `impl` block in `module`, until `declare_impls` resolves its type `ty`. Its functions are
+/// named, with whether they're public.
+struct PendingImpl {
+    module: Vec<String>,
+    span: Span,
+    ty: String,
+    functions: Vec<(String, bool)>,
+}
+
+/// Guards against `use` declarations that refer to each other in a cycle
+const MAX_IMPORT_DEPTH: usize = 32;
+
+/// Path of the item `name` declared in `module`
+fn item_path(module: &[String], name: &str) -> Vec<String> {
+    module.iter().cloned().chain([name.to_owned()]).collect()
+}
+
+fn display_path(path: &[String]) -> String {
+    match path {
+        [] => "crate".to_string(),
+        path => path.join("::"),
+    }
+}
+
+/// Resolves the names in a program's modules to the paths of the items they refer to.
+/// Names are looked up in the current module, then in the root module. Names that aren't
+/// items, like local variables, builtins and `std::` paths, are left for the backends.
+struct Resolver {
+    modules: HashMap<Vec<String>, HashMap<String, Item>>,
+    module_files: HashMap<Vec<String>, usize>,
+    /// module whose items are being flattened
+    module: Vec<String>,
+    /// variables in scope in the function being flattened, they shadow items
+    locals: Vec<HashSet<String>>,
+    /// type parameters of the function being flattened, they shadow items
+    type_params: Vec<String>,
+    /// `impl` blocks, until `declare_impls` resolves their types
+    impls: Vec<PendingImpl>,
+    /// functions of the `impl` blocks by the full name of their type, and whether they're public
+    impl_functions: HashMap<String, HashMap<String, bool>>,
+    /// statement being flattened, errors are reported at its location
+    span: Span,
+    errors: Vec<(Span, String)>,
+}
+
+impl Resolver {
+    fn new(module_files: HashMap<Vec<String>, usize>) -> Self {
+        Self {
+            modules: HashMap::new(),
+            module_files,
+            module: vec![],
+            locals: vec![HashSet::new()],
+            type_params: vec![],
+            impls: vec![],
+            impl_functions: HashMap::new(),
+            span: Span::default(),
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.errors.push((self.span, message));
+    }
+
+    /// Records the items of `module` and its submodules, before any name is resolved
+    fn declare(&mut self, module: &[String], stmts: &[Stmt]) {
+        let file = self.module_files[module];
+        self.modules.entry(module.to_vec()).or_default();
+        for stmt in stmts {
+            let (name, target, public, span) = match stmt {
+                Stmt::FunctionDeclaration {
+                    ident,
+                    public,
+                    span,
+                    ..
+                } => (ident, Target::Declared(ItemKind::Function), *public, span),
+                Stmt::StructDeclaration {
+                    ident,
+                    public,
+                    span,
+                    ..
+                } => (ident, Target::Declared(ItemKind::Struct), *public, span),
+                Stmt::EnumDeclaration {
+                    ident,
+                    public,
+                    span,
+                    ..
+                } => (ident, Target::Declared(ItemKind::Enum), *public, span),
+                Stmt::Global {
+                    ident,
+                    public,
+                    span,
+                    ..
+                } => (ident, Target::Declared(ItemKind::Global), *public, span),
+                Stmt::Module {
+                    ident,
+                    body,
+                    public,
+                    span,
+                } => {
+                    let path = item_path(module, ident);
+                    self.declare(&path, body.as_deref().unwrap_or_default());
+                    (ident, Target::Declared(ItemKind::Module), *public, span)
+                }
+                Stmt::Impl {
+                    ty,
+                    functions,
+                    span,
+                } => {
+                    let functions = functions.iter().filter_map(|function| match function {
+                        Stmt::FunctionDeclaration { ident, public, .. } => {
+                            Some((ident.clone(), *public))
+                        }
+                        _ => None,
+                    });
+                    self.impls.push(PendingImpl {
+                        module: module.to_vec(),
+                        span: span.in_file(file),
+                        ty: ty.clone(),
+                        functions: functions.collect(),
+                    });
+                    continue;
+                }
+                Stmt::Use { path, public, span } => (
+                    path.last().unwrap(),
+                    Target::Import(path.clone()),
+                    *public,
+                    span,
+                ),
+                _ => continue,
+            };
+            let items = self.modules.get_mut(module).unwrap();
+            match items.get(name) {
+                // two declarations of the same kind are left for the type checker to report
+                Some(Item {
+                    target: Target::Declared(kind),
+                    ..
+                }) if matches!(&target, Target::Declared(new_kind) if new_kind == kind && *kind != ItemKind::Module) =>
+                    {}
+                Some(_) => self.errors.push((
+                    span.in_file(file),
+                    format!(
+                        "`{}` is defined more than once in module `{}`",
+                        name,
+                        display_path(module)
+                    ),
+                )),
+                None => {
+                    items.insert(name.clone(), Item { target, public });
+                }
+            }
+        }
+    }
+
+    /// Records the functions of every `impl` block under the full name of its type, once every
+    /// type is declared
+    fn declare_impls(&mut self) {
+        for PendingImpl {
+            module,
+            span,
+            ty,
+            functions,
+        } in std::mem::take(&mut self.impls)
+        {
+            self.span = span;
+            let Some(path) = self.impl_type(&module, &ty) else {
+                continue;
+            };
+            self.impl_functions
+                .entry(path)
+                .or_default()
+                .extend(functions);
+        }
+    }
+
+    /// Full name of the struct or enum an `impl` block in `module` is for, errors are reported
+    fn impl_type(&mut self, module: &[String], ty: &str) -> Option<String> {
+        match self.resolve_path(module, &[ty.to_owned()], 0) {
+            Ok(Some(Resolved {
+                kind: ItemKind::Struct | ItemKind::Enum,
+                path,
+            })) => Some(path.join("::")),
+            Ok(Some(resolved)) => {
+                self.error(format!(
+                    "`{}` is a {}, only structs and enums can have an `impl` block",
+                    ty, resolved.kind
+                ));
+                None
+            }
+            Ok(None) => {
+                self.error(format!("Cannot find struct or enum `{}`", ty));
+                None
+            }
+            Err(message) => {
+                self.error(message);
+                None
+            }
+        }
+    }
+
+    /// The item `name` refers to in `module`, following `use` declarations
+    fn lookup(
+        &self,
+        module: &[String],
+        name: &str,
+        depth: usize,
+    ) -> Result<Option<Resolved>, String> {
+        let Some(item) = self.modules.get(module).and_then(|items| items.get(name)) else {
+            return Ok(None);
+        };
+        match &item.target {
+            Target::Declared(kind) => Ok(Some(Resolved {
+                kind: *kind,
+                path: item_path(module, name),
+            })),
+            Target::Import(_) if depth > MAX_IMPORT_DEPTH => Err(format!(
+                "`use` declarations for `{}` refer to each other",
+                name
+            )),
+            Target::Import(path) => match self.resolve_path(module, path, depth + 1)? {
+                Some(resolved) => Ok(Some(resolved)),
+                None => Err(format!("Unresolved import `{}`", path.join("::"))),
+            },
+        }
+    }
+
+    /// The item `path` refers to from `from`, `None` when its first name isn't an item.
+    /// Every item after the first has to be visible from `from`: public, or declared in
+    /// `from` or one of the modules enclosing it.
+    fn resolve_path(
+        &self,
+        from: &[String],
+        path: &[String],
+        depth: usize,
+    ) -> Result<Option<Resolved>, String> {
+        let module = |path: &[String]| Resolved {
+            kind: ItemKind::Module,
+            path: path.to_vec(),
+        };
+        let (mut current, rest) = match path[0].as_str() {
+            "crate" => (module(&[]), &path[1..]),
+            "self" => (module(from), &path[1..]),
+            "super" => match from.split_last() {
+                Some((_, parent)) => (module(parent), &path[1..]),
+                None => return Err("`super` can't be used in the root module".to_string()),
+            },
+            first => {
+                let found = match self.lookup(from, first, depth)? {
+                    Some(found) => Some(found),
+                    None if !from.is_empty() => self.lookup(&[], first, depth)?,
+                    None => None,
+                };
+                match found {
+                    Some(found) => (found, &path[1..]),
+                    None => return Ok(None),
+                }
+            }
+        };
+        for name in rest {
+            if current.kind != ItemKind::Module {
+                return Err(format!(
+                    "`{}` is a {}, not a module",
+                    display_path(&current.path),
+                    current.kind
+                ));
+            }
+            let item = self.modules[&current.path].get(name).ok_or_else(|| {
+                format!(
+                    "Module `{}` has no item `{}`",
+                    display_path(&current.path),
+                    name
+                )
+            })?;
+            if !item.public && !from.starts_with(&current.path) {
+                return Err(format!(
+                    "`{}` is private to module `{}`",
+                    name,
+                    display_path(&current.path)
+                ));
+            }
+            current = self
+                .lookup(&current.path, name, depth)?
+                .expect("the item was just found");
+        }
+        Ok(Some(current))
+    }
+
+    /// Resolves `name` to the full name of an item of one of the `expected` kinds, or `None`
+    /// when it isn't the name of an item. Errors are reported and leave the name as it is.
+    fn resolve_name(&mut self, name: &str, expected: &[ItemKind]) -> Option<String> {
+        let path = name.split("::").map(String::from).collect::<Vec<_>>();
+        match self.resolve_path(&self.module, &path, 0) {
+            Ok(Some(resolved)) if expected.contains(&resolved.kind) => {
+                Some(resolved.path.join("::"))
+            }
+            Ok(Some(resolved)) => {
+                let expected = expected.iter().map(ItemKind::to_string).collect::<Vec<_>>();
+                self.error(format!(
+                    "`{}` is a {}, expected a {}",
+                    name,
+                    resolved.kind,
+                    expected.join(" or ")
+                ));
+                None
+            }
+            Ok(None) => None,
+            Err(message) => {
+                self.error(message);
+                None
+            }
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().any(|scope| scope.contains(name))
+    }
+
+    fn bind_local(&mut self, name: &str) {
+        self.locals.last_mut().unwrap().insert(name.to_owned());
+    }
+
+    /// Moves the items of `module` and its submodules into `out`, named by their full path
+    fn flatten(&mut self, module: &[String], stmts: Vec<Stmt>, out: &mut Vec<Stmt>) {
+        let file = self.module_files[module];
+        for mut stmt in stmts {
+            self.module = module.to_vec();
+            match stmt {
+                Stmt::Module { ident, body, .. } => {
+                    let path = item_path(module, &ident);
+                    self.flatten(&path, body.unwrap_or_default(), out);
+                }
+                Stmt::Use { ref path, span, .. } => {
+                    self.span = span.in_file(file);
+                    match self.resolve_path(module, path, 0) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => self.error(format!("Unresolved import `{}`", path.join("::"))),
+                        Err(message) => self.error(message),
+                    }
+                }
+                Stmt::FunctionDeclaration { .. }
+                | Stmt::StructDeclaration { .. }
+                | Stmt::EnumDeclaration { .. }
+                | Stmt::Global { .. } => {
+                    self.stmt(&mut stmt, file);
+                    if let Stmt::FunctionDeclaration { ident, .. }
+                    | Stmt::StructDeclaration { ident, .. }
+                    | Stmt::EnumDeclaration { ident, .. }
+                    | Stmt::Global { ident, .. } = &mut stmt
+                    {
+                        *ident = item_path(module, ident).join("::");
+                    }
+                    out.push(stmt);
+                }
+                // errors about the type were reported by `declare_impls`
+                Stmt::Impl { ty, functions, .. } => {
+                    let Ok(Some(Resolved { path, .. })) = self.resolve_path(module, &[ty], 0)
+                    else {
+                        continue;
+                    };
+                    for mut function in functions {
+                        self.stmt(&mut function, file);
+                        if let Stmt::FunctionDeclaration { ident, .. } = &mut function {
+                            *ident = item_path(&path, ident).join("::");
+                        }
+                        out.push(function);
+                    }
+                }
+                _ if module.is_empty() => {
+                    self.stmt(&mut stmt, file);
+                    out.push(stmt);
+                }
+                _ => {
+                    self.span = span_of_mut(&mut stmt).in_file(file);
+                    self.error(format!(
+                        "Only functions, structs, enums, `impl`, `const`, `static`, `mod` and `use` can be declared in module `{}`",
+                        display_path(module)
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Resolves the names used in a statement and moves its spans into `file`
+    fn stmt(&mut self, stmt: &mut Stmt, file: usize) {
+        let span = span_of_mut(stmt);
+        *span = span.in_file(file);
+        self.span = *span;
+        match stmt {
+            Stmt::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                let outer_type_params =
+                    std::mem::replace(&mut self.type_params, type_params.clone());
+                for param in params.iter_mut() {
+                    param.ty.iter_mut().for_each(|ty| self.ty(ty));
+                }
+                return_type.iter_mut().for_each(|ty| self.ty(ty));
+                // a function only sees its parameters, top-level variables aren't in scope
+                let outer = std::mem::replace(
+                    &mut self.locals,
+                    vec![params.iter().map(|param| param.ident.clone()).collect()],
+                );
+                self.block(body, file);
+                self.locals = outer;
+                self.type_params = outer_type_params;
+            }
+            Stmt::StructDeclaration { fields, .. } => {
+                fields.iter_mut().for_each(|field| self.ty(&mut field.ty))
+            }
+            Stmt::EnumDeclaration { variants, .. } => variants
+                .iter_mut()
+                .flat_map(|variant| &mut variant.fields)
+                .for_each(|ty| self.ty(ty)),
+            Stmt::Module { .. } | Stmt::Use { .. } | Stmt::Impl { .. } => self.error(
+                "`mod`, `use` and `impl` can only be declared at the top level of a module"
+                    .to_string(),
+            ),
+            Stmt::Assignment {
+                ident, ty, expr, ..
+            } => {
+                self.expr(expr, file);
+                ty.iter_mut().for_each(|ty| self.ty(ty));
+                self.bind_local(ident);
+            }
+            Stmt::Global { ty, expr, .. } => {
+                self.ty(ty);
+                self.expr(expr, file);
+            }
+            Stmt::Destructure {
+                pattern, ty, expr, ..
+            } => {
+                self.expr(expr, file);
+                ty.iter_mut().for_each(|ty| self.ty(ty));
+                self.pattern(pattern);
+            }
+            Stmt::Reassignment { ident, expr, .. } | Stmt::FieldAssignment { ident, expr, .. } => {
+                self.assigned(ident);
+                self.expr(expr, file)
+            }
+            Stmt::IndexAssignment {
+                ident,
+                indices,
+                expr,
+                ..
+            } => {
+                self.assigned(ident);
+                indices.iter_mut().for_each(|index| self.expr(index, file));
+                self.expr(expr, file);
+            }
+            Stmt::Expression(expr, _) | Stmt::Return(expr, _) => self.expr(expr, file),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expr(condition, file);
+                self.block(then_branch, file);
+                else_branch
+                    .iter_mut()
+                    .for_each(|branch| self.block(branch, file));
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expr(condition, file);
+                self.block(body, file);
+            }
+            Stmt::For {
+                ident,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.expr(start, file);
+                self.expr(end, file);
+                self.locals.push(HashSet::from([ident.clone()]));
+                self.block(body, file);
+                self.locals.pop();
+            }
+            Stmt::Break(..) | Stmt::Continue(..) => {}
+        }
+    }
+
+    /// An assignment to a name that isn't a local assigns to a `static mut`
+    fn assigned(&mut self, ident: &mut String) {
+        if !self.is_local(ident) {
+            if let Some(resolved) = self.resolve_name(ident, &[ItemKind::Global]) {
+                *ident = resolved;
+            }
+        }
+    }
+
+    fn block(&mut self, stmts: &mut [Stmt], file: usize) {
+        let span = self.span;
+        self.locals.push(HashSet::new());
+        stmts.iter_mut().for_each(|stmt| self.stmt(stmt, file));
+        self.locals.pop();
+        self.span = span;
+    }
+
+    fn expr(&mut self, expr: &mut Expr, file: usize) {
+        match expr {
+            Expr::Ident(name) if !self.is_local(name) => {
+                if let Some(resolved) =
+                    self.resolve_name(name, &[ItemKind::Function, ItemKind::Global])
+                {
+                    *name = resolved;
+                }
+            }
+            Expr::QualifiedIdent(path) => {
+                if let Some(resolved) =
+                    self.resolve_name(&path.join("::"), &[ItemKind::Function, ItemKind::Global])
+                {
+                    *expr = Expr::Ident(resolved);
+                }
+            }
+            Expr::Call(name, args) => {
+                if name.contains("::") || !self.is_local(name) {
+                    if let Some(resolved) = self.resolve_name(name, &[ItemKind::Function]) {
+                        *name = resolved;
+                    }
+                }
+                args.iter_mut().for_each(|arg| self.expr(arg, file));
+            }
+            Expr::StructLiteral(name, fields) => {
+                if let Some(resolved) = self.resolve_name(name, &[ItemKind::Struct]) {
+                    *name = resolved;
+                }
+                fields
+                    .iter_mut()
+                    .for_each(|(_, expr)| self.expr(expr, file));
+            }
+            // `Point::new(1, 2)` parses like a variant, it calls the function of an `impl` block
+            Expr::EnumVariant {
+                enum_name,
+                variant,
+                args,
+            } => {
+                args.iter_mut().for_each(|arg| self.expr(arg, file));
+                if let Some(resolved) =
+                    self.resolve_name(enum_name, &[ItemKind::Enum, ItemKind::Struct])
+                {
+                    let function = self
+                        .impl_functions
+                        .get(&resolved)
+                        .and_then(|functions| functions.get(variant).copied());
+                    match function {
+                        Some(public) => {
+                            let path = resolved.split("::").map(String::from).collect::<Vec<_>>();
+                            let module = &path[..path.len() - 1];
+                            if !public && !self.module.starts_with(module) {
+                                self.error(format!(
+                                    "`{}::{}` is private to module `{}`",
+                                    resolved,
+                                    variant,
+                                    display_path(module)
+                                ));
+                            }
+                            let function = format!("{}::{}", resolved, variant);
+                            *expr = Expr::Call(function, std::mem::take(args));
+                        }
+                        None => *enum_name = resolved,
+                    }
+                }
+            }
+            Expr::Cast(operand, ty) => {
+                self.expr(operand, file);
+                self.ty(ty);
+            }
+            Expr::Infix(left, _, right) | Expr::ArrayIndexing(left, right) => {
+                self.expr(left, file);
+                self.expr(right, file);
+            }
+            Expr::Unary(_, operand) | Expr::FieldAccess(operand, _) => self.expr(operand, file),
+            Expr::Slice(array, start, end) => {
+                self.expr(array, file);
+                start
+                    .iter_mut()
+                    .chain(end)
+                    .for_each(|bound| self.expr(bound, file));
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                self.expr(receiver, file);
+                args.iter_mut().for_each(|arg| self.expr(arg, file));
+            }
+            Expr::Array(elements) | Expr::Tuple(elements) | Expr::MacroCall(_, elements) => {
+                elements
+                    .iter_mut()
+                    .for_each(|element| self.expr(element, file))
+            }
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(condition, file);
+                self.block(then_branch, file);
+                self.block(else_branch, file);
+            }
+            Expr::Match { scrutinee, arms } => {
+                self.expr(scrutinee, file);
+                for arm in arms {
+                    self.locals.push(HashSet::new());
+                    self.pattern(&mut arm.pattern);
+                    arm.guard
+                        .iter_mut()
+                        .for_each(|guard| self.expr(guard, file));
+                    self.block(&mut arm.body, file);
+                    self.locals.pop();
+                }
+            }
+            Expr::Closure {
+                params,
+                return_type,
+                body,
+            } => {
+                for param in params.iter_mut() {
+                    param.ty.iter_mut().for_each(|ty| self.ty(ty));
+                }
+                return_type.iter_mut().for_each(|ty| self.ty(ty));
+                self.locals
+                    .push(params.iter().map(|param| param.ident.clone()).collect());
+                self.block(body, file);
+                self.locals.pop();
+            }
+            Expr::Ident(_)
+            | Expr::Num(_)
+            | Expr::Float(_)
+            | Expr::Str(_)
+            | Expr::Char(_)
+            | Expr::Bool(_) => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern {
+            Pattern::Binding(name) => self.bind_local(name),
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                if let Some(resolved) = self.resolve_name(enum_name, &[ItemKind::Enum]) {
+                    *enum_name = resolved;
+                }
+                fields.iter_mut().for_each(|field| self.pattern(field));
+            }
+            Pattern::Tuple(elements) => elements
+                .iter_mut()
+                .for_each(|element| self.pattern(element)),
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn ty(&mut self, ty: &mut Type) {
+        match ty {
+            Type::Named(name) if !self.type_params.contains(name) => {
+                if let Some(resolved) = self.resolve_name(name, &[ItemKind::Struct, ItemKind::Enum])
+                {
+                    *name = resolved;
+                }
+            }
+            Type::Ptr(inner) | Type::Array(inner, _) | Type::Vec(inner) | Type::Slice(inner) => {
+                self.ty(inner)
+            }
+            Type::Function(params, return_type) => {
+                params.iter_mut().for_each(|param| self.ty(param));
+                self.ty(return_type);
+            }
+            Type::Tuple(types) => types.iter_mut().for_each(|ty| self.ty(ty)),
+            _ => {}
+        }
+    }
+}
+
+fn span_of_mut(stmt: &mut Stmt) -> &mut Span {
+    match stmt {
+        Stmt::FunctionDeclaration { span, .. }
+        | Stmt::Assignment { span, .. }
+        | Stmt::Global { span, .. }
+        | Stmt::Destructure { span, .. }
+        | Stmt::Reassignment { span, .. }
+        | Stmt::StructDeclaration { span, .. }
+        | Stmt::EnumDeclaration { span, .. }
+        | Stmt::Module { span, .. }
+        | Stmt::Impl { span, .. }
+        | Stmt::Use { span, .. }
+        | Stmt::FieldAssignment { span, .. }
+        | Stmt::IndexAssignment { span, .. }
+        | Stmt::If { span, .. }
+        | Stmt::While { span, .. }
+        | Stmt::For { span, .. }
+        | Stmt::Expression(_, span)
+        | Stmt::Return(_, span)
+        | Stmt::Break(_, span)
+        | Stmt::Continue(_, span) => span,
+    }
+}
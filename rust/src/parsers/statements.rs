@@ -1,56 +1,204 @@
-use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::multispace0;
-use nom::combinator::opt;
+use nom::combinator::{not, opt};
 
 
-use nom::sequence::delimited;
-use nom::{InputTakeAtPosition, AsChar, Parser};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, separated_pair, terminated};
+use nom::branch::alt;
+use nom::Parser;
 
 
-use crate::llvm::ast::{Stmt, Expr};
-use crate::parsers::expressions::{parse_primary_expr, parse_function_call, parse_infix_expr};
+use crate::llvm::ast::{BinaryOp, GlobalKind, Pattern, Span, Stmt, Expr};
+use crate::parsers::expressions::parse_infix_expr;
+use crate::parsers::functions::ws;
 
-use super::ParseResult;
+use super::{ParseResult, parse_statement};
 use super::error::CustomError;
 use super::expressions::expression;
-use super::tokens::parse_identifier;
+use super::enums::parse_pattern;
+use super::structs::parse_field_path;
+use super::tuples::tuple_list;
+use super::tokens::{get_identifier, keyword, parse_identifier, parse_label, parse_name, parse_visibility, skip_space};
+use super::types::parse_type_annotation;
 
 
-pub fn space_opt<I, O, F>(parser: F) -> impl FnMut(I) -> ParseResult<I, O>
+pub fn space_opt<'a, O, F>(parser: F) -> impl FnMut(&'a str) -> ParseResult<&'a str, O>
 where
-    I: InputTakeAtPosition + Clone + PartialEq,
-    <I as InputTakeAtPosition>::Item: AsChar + Clone,
-    F: Parser<I, O, CustomError<I>>,
+    F: Parser<&'a str, O, CustomError<&'a str>>,
 {
-    delimited(opt(multispace0), parser, opt(multispace0))
+    delimited(skip_space, parser, skip_space)
 }
 
 pub fn parse_let_statement(input: &str) -> ParseResult<&str, Stmt> {
     //println!("let stmnt input: {:?}", input);
-    let (input, _) = space_opt(tag("let"))(input)?;
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("let"))(input)?;
     //println!("its a let stmnt");
+    // `let (q, r) = divmod(a, b);` destructures a tuple
+    if let (input, Some(elements)) = opt(tuple_list(parse_pattern))(input)? {
+        let (input, ty) = opt(parse_type_annotation)(input)?;
+        let (input, _) = space_opt(tag("="))(input)?;
+        let (input, expr) = space_opt(parse_infix_expr)(input)?;
+        let (input, _) = space_opt(tag(";"))(input)?;
+        return Ok((input, Stmt::Destructure { pattern: Pattern::Tuple(elements), ty, expr, span }));
+    }
+    let (input, mutable) = opt(space_opt(keyword("mut")))(input)?;
     let (input, ident) = space_opt(parse_identifier)(input)?;
     //println!("let after ident: {}", input);
     //println!("let stmnt IDENT: {:?}", ident);
+    let (input, ty) = opt(parse_type_annotation)(input)?;
     let (input, _) = space_opt(tag("="))(input)?;
     //println!("let after EQUALS: {}", input);
-    let (input, expr) = space_opt(alt((parse_function_call, parse_infix_expr, parse_primary_expr)))(input)?;
+    let (input, expr) = space_opt(parse_infix_expr)(input)?;
     //println!("let after expr: {}", input);
     let (input, _) = space_opt(tag(";"))(input)?;
 
     //println!("stmnt PARSED: {}", input);
 
     match ident {
-        Expr::Ident(id) => Ok((input, Stmt::Assignment { ident: id, expr })),
+        Expr::Ident(id) => Ok((input, Stmt::Assignment { ident: id, mutable: mutable.is_some(), ty, expr, span })),
         _ => Err(nom::Err::Failure(CustomError::UnexpectedToken("Expected identifier"))),
     }
 }
 
+/// `const LIMIT: i32 = 10;`, `static NAME: str = "red";` or `static mut COUNTER: i32 = 0;`, the type can't be left out
+pub fn parse_global_declaration(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, kind) = alt((
+        keyword("const").map(|_| GlobalKind::Const),
+        preceded(keyword("static"), opt(preceded(skip_space, keyword("mut")))).map(|mutable| match mutable {
+            Some(_) => GlobalKind::StaticMut,
+            None => GlobalKind::Static,
+        }),
+    ))(input)?;
+    let (input, ident) = space_opt(parse_name)(input)?;
+    let (input, ty) = match opt(parse_type_annotation)(input)? {
+        (input, Some(ty)) => (input, ty),
+        (_, None) => return Err(nom::Err::Failure(CustomError::UnexpectedToken("Expected a type annotation on the const or static"))),
+    };
+    let (input, _) = space_opt(tag("="))(input)?;
+    let (input, expr) = space_opt(parse_infix_expr)(input)?;
+    let (input, _) = space_opt(tag(";"))(input)?;
+    Ok((input, Stmt::Global { ident, kind, ty, expr, public, span }))
+}
+
+/// `=` or a compound assignment operator, yielding the operator a compound assignment applies
+fn parse_assignment_operator(input: &str) -> ParseResult<&str, Option<BinaryOp>> {
+    alt((
+        tag("+=").map(|_| Some(BinaryOp::Add)),
+        tag("-=").map(|_| Some(BinaryOp::Subtract)),
+        tag("*=").map(|_| Some(BinaryOp::Multiply)),
+        tag("/=").map(|_| Some(BinaryOp::Divide)),
+        // `==` is a comparison, not an assignment
+        terminated(tag("="), not(tag("="))).map(|_| None),
+    ))(input)
+}
+
+/// `x = expr;` or `x += expr;` on an existing binding, `p.x = expr;` on one of its fields and `a[i] = expr;` on one of its elements
+pub fn parse_reassignment_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, ident) = space_opt(parse_identifier.map(get_identifier))(input)?;
+    let (input, indices) = many0(delimited(space_opt(tag("[")), parse_infix_expr, space_opt(tag("]"))))(input)?;
+    let (input, fields) = parse_field_path(input)?;
+    let (input, op) = space_opt(parse_assignment_operator)(input)?;
+    let (input, expr) = space_opt(parse_infix_expr)(input)?;
+    let (input, _) = opt(space_opt(tag(";")))(input)?;
+    match ident {
+        Some(ident) if indices.is_empty() && fields.is_empty() => Ok((input, Stmt::Reassignment { ident, op, expr, span })),
+        Some(ident) if indices.is_empty() => Ok((input, Stmt::FieldAssignment { ident, fields, op, expr, span })),
+        Some(ident) if fields.is_empty() => Ok((input, Stmt::IndexAssignment { ident, indices, op, expr, span })),
+        Some(_) => Err(nom::Err::Failure(CustomError::UnexpectedToken("Expected an assignment to an element or to a field, not both"))),
+        None => Err(nom::Err::Failure(CustomError::UnexpectedToken("Expected identifier"))),
+    }
+}
+
 pub fn parse_return_statement(input: &str) -> ParseResult<&str, Stmt> {
-    let (input, _) = space_opt(tag("return"))(input)?;
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("return"))(input)?;
     let (input, expr) = space_opt(expression)(input)?;
     //let (input, _) = space_opt(tag(";"))(input)?;
-    Ok((input, Stmt::Return(expr)))
+    Ok((input, Stmt::Return(expr, span)))
+}
+
+/// parse a `{ ... }` delimited list of statements
+pub fn parse_block(input: &str) -> ParseResult<&str, Vec<Stmt>> {
+    let (input, _) = skip_space(input)?;
+    let (input, _) = tag("{")(input)?;
+    let (input, _) = skip_space(input)?;
+    let (input, body) = many0(ws(parse_statement))(input)?;
+    let (input, _) = opt(tag(";"))(input)?;
+    let (input, _) = skip_space(input)?;
+    let (input, _) = tag("}")(input)?;
+
+    Ok((input, body))
 }
 
+pub fn parse_if_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("if"))(input)?;
+    let (input, condition) = parse_infix_expr(input)?;
+    let (input, then_branch) = parse_block(input)?;
+    let (input, else_branch) = opt(preceded(
+        space_opt(keyword("else")),
+        alt((
+            // `else if` becomes an else branch holding the nested if
+            |input| parse_if_statement(input).map(|(input, stmt)| (input, vec![stmt])),
+            parse_block,
+        )),
+    ))(input)?;
+
+    Ok((input, Stmt::If { condition, then_branch, else_branch, span }))
+}
+
+/// parse the optional `'label:` in front of a loop
+fn parse_loop_label(input: &str) -> ParseResult<&str, Option<String>> {
+    opt(terminated(parse_label, space_opt(tag(":"))))(input)
+}
+
+pub fn parse_while_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, label) = parse_loop_label(input)?;
+    let (input, _) = space_opt(keyword("while"))(input)?;
+    let (input, condition) = parse_infix_expr(input)?;
+    let (input, body) = parse_block(input)?;
+
+    Ok((input, Stmt::While { label, condition, body, span }))
+}
+
+pub fn parse_for_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, label) = parse_loop_label(input)?;
+    let (input, _) = space_opt(keyword("for"))(input)?;
+    let (input, ident) = space_opt(parse_identifier)(input)?;
+    let (input, _) = space_opt(keyword("in"))(input)?;
+    let (input, (start, end)) = separated_pair(parse_infix_expr, tag(".."), parse_infix_expr)(input)?;
+    let (input, body) = parse_block(input)?;
+
+    Ok((input, Stmt::For { label, ident: get_identifier(ident).unwrap(), start, end, body, span }))
+}
+
+pub fn parse_break_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("break"))(input)?;
+    let (input, label) = opt(parse_label)(input)?;
+    let (input, _) = opt(space_opt(tag(";")))(input)?;
+    Ok((input, Stmt::Break(label, span)))
+}
+
+pub fn parse_continue_statement(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("continue"))(input)?;
+    let (input, label) = opt(parse_label)(input)?;
+    let (input, _) = opt(space_opt(tag(";")))(input)?;
+    Ok((input, Stmt::Continue(label, span)))
+}
@@ -0,0 +1,86 @@
+use nom::bytes::complete::tag;
+use nom::branch::alt;
+use nom::character::complete::{char, digit1};
+use nom::combinator::{not, opt, verify};
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated};
+use nom::Parser;
+
+use crate::llvm::ast::{Expr, Field, Span, Stmt, Type};
+
+use super::expressions::parse_infix_expr;
+use super::functions::parse_function_declaration;
+use super::statements::space_opt;
+use super::tokens::{keyword, parse_name, parse_path, parse_visibility, skip_space};
+use super::types::parse_type_annotation;
+use super::ParseResult;
+
+/// `{ a, b, }` style list, a trailing comma is allowed
+pub fn braced_list<'a, O, F>(item: F) -> impl FnMut(&'a str) -> ParseResult<&'a str, Vec<O>>
+where
+    F: FnMut(&'a str) -> ParseResult<&'a str, O>,
+{
+    delimited(
+        space_opt(tag("{")),
+        terminated(separated_list0(space_opt(tag(",")), item), opt(space_opt(tag(",")))),
+        space_opt(tag("}")),
+    )
+}
+
+/// `struct Point { x: i32, y: i32 }`
+pub fn parse_struct_declaration(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, public) = parse_visibility(input)?;
+    let (input, _) = space_opt(keyword("struct"))(input)?;
+    let (input, ident) = space_opt(parse_name)(input)?;
+    let (input, fields) = braced_list(|input| {
+        pair(space_opt(parse_name), parse_type_annotation)(input)
+            .map(|(input, (ident, ty))| (input, Field { ident, ty }))
+    })(input)?;
+    Ok((input, Stmt::StructDeclaration { ident, fields, public, span }))
+}
+
+/// `impl Point { fn len(self) -> i32 { ... } }`, a `self` parameter has the type the block is for
+pub fn parse_impl_block(input: &str) -> ParseResult<&str, Stmt> {
+    let (input, _) = skip_space(input)?;
+    let span = Span::new(input);
+    let (input, _) = space_opt(keyword("impl"))(input)?;
+    let (input, ty) = space_opt(parse_name)(input)?;
+    let (input, mut functions) = delimited(space_opt(tag("{")), many0(parse_function_declaration), space_opt(tag("}")))(input)?;
+    for function in &mut functions {
+        if let Stmt::FunctionDeclaration { params, .. } = function {
+            if let Some(receiver) = params.first_mut().filter(|param| param.ident == "self" && param.ty.is_none()) {
+                receiver.ty = Some(Type::Named(ty.clone()));
+            }
+        }
+    }
+    Ok((input, Stmt::Impl { ty, functions, span }))
+}
+
+/// `Point { x: 1, y: 2 }` or `shapes::Point { ... }`. Only capitalised names start a literal,
+/// so the block after `if done { ... }` isn't mistaken for one
+pub fn parse_struct_literal(input: &str) -> ParseResult<&str, Expr> {
+    let (input, path) = space_opt(verify(parse_path, |path: &[String]| {
+        path.last().is_some_and(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+    }))(input)?;
+    let name = path.join("::");
+    let (input, fields) = braced_list(separated_pair(
+        space_opt(parse_name),
+        // `:` but not the start of a `::` path
+        terminated(char(':'), not(char(':'))),
+        parse_infix_expr,
+    ))(input)?;
+    Ok((input, Expr::StructLiteral(name, fields)))
+}
+
+/// the `.x.y` field path following a struct expression or an assigned variable, tuple elements are numbered fields like `.0`
+pub fn parse_field_path(input: &str) -> ParseResult<&str, Vec<String>> {
+    many0(parse_field)(input)
+}
+
+/// a single `.x` or `.0`
+pub fn parse_field(input: &str) -> ParseResult<&str, String> {
+    // `..` is a range, not a field access
+    preceded(terminated(space_opt(char('.')), not(char('.'))), alt((parse_name, digit1.map(String::from))))(input)
+}
@@ -1,38 +1,89 @@
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until};
-use nom::character::complete::{alpha1, char, alphanumeric0, anychar, digit1, multispace0};
-use nom::combinator::{recognize, map};
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{alpha1, char, alphanumeric0, alphanumeric1, digit1, multispace1, none_of, one_of};
+use nom::combinator::{recognize, map, not, opt, value, verify};
 
-use nom::multi::separated_list1;
-use nom::sequence::{delimited, tuple};
+use nom::multi::{many0, many1, separated_list1};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::Parser;
 
 
-use crate::llvm::ast::Expr;
+use crate::llvm::ast::{Expr, Type};
 
 
-use crate::types::integer::{parse_large_integer, Integer, try_parse_number};
+use crate::types::integer::Integer;
 
 use super::ParseResult;
+use super::error::CustomError;
+use super::types::{parse_float_type, parse_int_type};
+
+/// Words reserved by the grammar that can't be used as identifiers
+pub const KEYWORDS: &[&str] = &[
+    "fn", "let", "return", "if", "else", "true", "false", "while", "for", "in", "break", "continue",
+    "mut", "as", "struct", "enum", "match", "mod", "use", "pub", "const", "static", "impl",
+];
+
+/// Matches `word` only when it isn't the prefix of a longer identifier (`if` but not `iffy`)
+pub fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> ParseResult<&'a str, &'a str> {
+    terminated(tag(word), not(alt((alphanumeric1, tag("_")))))
+}
+
+/// `// ...` up to (but not including) the end of the line
+fn line_comment(input: &str) -> ParseResult<&str, &str> {
+    recognize(pair(tag("//"), take_while(|c| c != '\n')))(input)
+}
+
+/// `/* ... */`, where nested `/* */` pairs have to balance
+fn block_comment(input: &str) -> ParseResult<&str, &str> {
+    let (mut rest, _) = tag("/*")(input)?;
+    let mut depth = 1;
+    while depth > 0 {
+        if let Some(after) = rest.strip_prefix("/*") {
+            depth += 1;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("*/") {
+            depth -= 1;
+            rest = after;
+        } else if let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+        } else {
+            return Err(nom::Err::Failure(CustomError::UnterminatedComment(input)));
+        }
+    }
+    Ok((rest, &input[..input.len() - rest.len()]))
+}
+
+/// Skips any mix of whitespace and comments, the parsers' replacement for `multispace0`
+pub fn skip_space(input: &str) -> ParseResult<&str, &str> {
+    recognize(many0(alt((multispace1, line_comment, block_comment))))(input)
+}
+
+/// Like [`skip_space`] but has to consume something
+pub fn skip_space1(input: &str) -> ParseResult<&str, &str> {
+    recognize(many1(alt((multispace1, line_comment, block_comment))))(input)
+}
 
 pub fn parse_identifier(input: &str) -> ParseResult<&str, Expr> {
-    let (input, _) = multispace0(input)?; // consume whitespace
+    let (input, _) = skip_space(input)?; // consume whitespace
     map(
-recognize(
-    tuple((
-                alt((
-                    alpha1, 
-                    tag("_")
-                )),
-                alphanumeric0,
-            ))
+        verify(
+            recognize(
+                tuple((
+                    alt((
+                        alpha1, 
+                        tag("_")
+                    )),
+                    alphanumeric0,
+                ))
+            ),
+            |s: &str| !KEYWORDS.contains(&s)
         ),
         |s: &str| Expr::Ident(s.to_string())
     )(input)
 }
 
 pub fn parse_qualified_identifier(input: &str) -> ParseResult<&str, Expr> {
-    let (input, _) = multispace0(input)?;
+    let (input, _) = skip_space(input)?;
 
     let mut identifier = recognize(
         tuple((
@@ -49,11 +100,30 @@ pub fn parse_qualified_identifier(input: &str) -> ParseResult<&str, Expr> {
     let mut idents = vec![first_part.to_string()];
     idents.extend(remaining_parts.into_iter().map(|s: &str| s.to_string()));
     //println!("idents: {:?}", idents);
-    let (input, _) = multispace0(input)?;
+    let (input, _) = skip_space(input)?;
     //println!("input: {:#?}", input);
     Ok((input, Expr::QualifiedIdent(idents)))
 }
 
+/// A path of names such as `shapes::Shape`, a single name is a path too
+pub fn parse_path(input: &str) -> ParseResult<&str, Vec<String>> {
+    separated_list1(tag("::"), parse_name)(input)
+}
+
+/// An optional `pub` in front of an item, `true` when it's there
+pub fn parse_visibility(input: &str) -> ParseResult<&str, bool> {
+    let (input, public) = opt(terminated(keyword("pub"), skip_space))(input)?;
+    Ok((input, public.is_some()))
+}
+
+/// parse a loop label such as `'outer`, returning the name without the quote
+pub fn parse_label(input: &str) -> ParseResult<&str, String> {
+    let (input, _) = skip_space(input)?;
+    let (input, _) = char('\'')(input)?;
+    let (input, label) = parse_identifier(input)?;
+    Ok((input, get_identifier(label).unwrap()))
+}
+
 pub fn get_identifier(e: Expr) -> Option<String> {
     match e {
         Expr::Ident(s) => Some(s),
@@ -61,47 +131,139 @@ pub fn get_identifier(e: Expr) -> Option<String> {
     }
 }
 
+/// An identifier as a plain name, e.g. a struct or field name
+pub fn parse_name(input: &str) -> ParseResult<&str, String> {
+    let (input, ident) = parse_identifier(input)?;
+    Ok((input, get_identifier(ident).unwrap()))
+}
+
+/// Integer literal in decimal, hex (`0xFF`), octal (`0o17`) or binary (`0b1010`), `_` separators allowed.
+/// An optional type suffix (`10u8`, `0xFFi64`) makes the literal a cast to that type, it has to fit in it.
 pub fn parse_number(input: &str) -> ParseResult<&str, Expr> {
-    let (input, num_str) = digit1(input)?;
-    let _number = match num_str {
-        s if try_parse_number::<i8>(s).is_some() => Integer::Int8(try_parse_number::<i8>(s).unwrap()),
-        s if try_parse_number::<i32>(s).is_some() => Integer::Int(try_parse_number::<i32>(s).unwrap()),
-        s if try_parse_number::<i64>(s).is_some() => Integer::Int64(try_parse_number::<i64>(s).unwrap()),
-        s if try_parse_number::<i128>(s).is_some() => Integer::Int128(try_parse_number::<i128>(s).unwrap()),
-        s => {
-            // handle integer overflow
-            match parse_large_integer(s) {
-                Ok(i) => Integer::Int128(i),
-                Err(_) => panic!("Integer overflow"),
-            }
+    let (rest, (value, suffix, literal)) = parse_integer(input)?;
+    integer_literal(rest, value, suffix, literal)
+}
+
+/// A suffixed integer literal after a `-`, e.g. `-128i8`, whose range is checked after applying the sign.
+/// Anything else is left to the unary minus, so this fails without consuming input.
+pub fn parse_negative_number(input: &str) -> ParseResult<&str, Expr> {
+    let (rest, (value, suffix, _)) = preceded(pair(tag("-"), skip_space), parse_integer)(input)?;
+    if suffix.is_none() {
+        return Err(nom::Err::Error(CustomError::InvalidNumber(input)));
+    }
+    integer_literal(rest, -value, suffix, &input[..input.len() - rest.len()])
+}
+
+/// The value, type suffix and source text of an integer literal, without checking the suffix range
+fn parse_integer(input: &str) -> ParseResult<&str, (i128, Option<Type>, &str)> {
+    let (rest, (radix, digits)) = alt((
+        preceded(tag("0x"), take_while1(|c: char| c.is_ascii_hexdigit() || c == '_')).map(|d| (16, d)),
+        preceded(tag("0o"), take_while1(|c: char| c.is_digit(8) || c == '_')).map(|d| (8, d)),
+        preceded(tag("0b"), take_while1(|c: char| c.is_digit(2) || c == '_')).map(|d| (2, d)),
+        recognize(pair(digit1, take_while(|c: char| c.is_ascii_digit() || c == '_'))).map(|d| (10, d)),
+    ))(input)?;
+    let (rest, suffix) = opt(parse_int_type)(rest)?;
+    let literal = &input[..input.len() - rest.len()];
+    let digits = digits.replace('_', "");
+    if digits.is_empty() {
+        return Err(nom::Err::Failure(CustomError::InvalidNumber(literal)));
+    }
+    let value = i128::from_str_radix(&digits, radix)
+        .map_err(|_| nom::Err::Failure(CustomError::IntegerOverflow(literal)))?;
+    Ok((rest, (value, suffix, literal)))
+}
+
+fn integer_literal<'a>(rest: &'a str, value: i128, suffix: Option<Type>, literal: &'a str) -> ParseResult<&'a str, Expr> {
+    match suffix {
+        None => Ok((rest, Expr::Num(Integer::from(value)))),
+        Some(Type::Int { bits, signed }) if Integer::with_width(value, bits, signed).is_some() => {
+            Ok((rest, Expr::Cast(Box::new(Expr::Num(Integer::from(value))), Type::Int { bits, signed })))
         }
-    };
-    let i_value = try_parse_number::<i32>(num_str).unwrap();
-    Ok((input, Expr::Num(i_value)))
-} 
+        Some(_) => Err(nom::Err::Failure(CustomError::IntegerOverflow(literal))),
+    }
+}
 
 
+/// Float literal with a fraction (`1.5`), an exponent (`6.02e23`, `1e-3`) or a float suffix (`2f32`).
+/// Unsuffixed literals are `f64`, a suffix makes the literal a cast to that type.
+pub fn parse_float(input: &str) -> ParseResult<&str, Expr> {
+    let mut digits = recognize(pair(digit1, take_while(|c: char| c.is_ascii_digit() || c == '_')));
+    let (rest, integral) = digits(input)?;
+    // `1..5` is a range, not a float followed by `.5`
+    let (rest, fraction) = opt(recognize(preceded(char('.'), &mut digits)))(rest)?;
+    let (rest, exponent) = opt(recognize(tuple((one_of("eE"), opt(one_of("+-")), &mut digits))))(rest)?;
+    let (rest, suffix) = opt(parse_float_type)(rest)?;
+    if fraction.is_none() && exponent.is_none() && suffix.is_none() {
+        return Err(nom::Err::Error(CustomError::UnexpectedToken(input)));
+    }
+    let literal = &input[..integral.len() + fraction.map_or(0, str::len) + exponent.map_or(0, str::len)];
+    let value = literal
+        .replace('_', "")
+        .parse::<f64>()
+        .map_err(|_| nom::Err::Failure(CustomError::InvalidNumber(literal)))?;
+    match suffix {
+        Some(ty) => Ok((rest, Expr::Cast(Box::new(Expr::Float(value)), ty))),
+        None => Ok((rest, Expr::Float(value))),
+    }
+}
+
+pub fn parse_boolean(input: &str) -> ParseResult<&str, Expr> {
+    let (input, _) = skip_space(input)?;
+    let (input, b) = alt((
+        value(true, keyword("true")),
+        value(false, keyword("false")),
+    ))(input)?;
+    Ok((input, Expr::Bool(b)))
+}
+
+/// Decodes a `\`-escape inside a string or char literal, failing on anything unknown
+fn parse_escape(input: &str) -> ParseResult<&str, char> {
+    let (rest, _) = char('\\')(input)?;
+    let invalid = |len: usize| {
+        let end = input.char_indices().nth(len).map_or(input.len(), |(i, _)| i);
+        nom::Err::Failure(CustomError::InvalidEscape(&input[..end]))
+    };
+    let mut chars = rest.chars();
+    let simple = match chars.next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('\\') => '\\',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('0') => '\0',
+        Some('x') => {
+            let digits = rest[1..].chars().take(2).take_while(char::is_ascii_hexdigit).count();
+            return match u8::from_str_radix(&rest[1..1 + digits], 16) {
+                Ok(byte) if digits == 2 && byte <= 0x7F => Ok((&rest[3..], byte as char)),
+                _ => Err(invalid(2 + digits)),
+            };
+        }
+        Some('u') => {
+            let body = rest[1..].strip_prefix('{').ok_or_else(|| invalid(2))?;
+            let digits = body.chars().take_while(char::is_ascii_hexdigit).count();
+            if !body[digits..].starts_with('}') {
+                return Err(invalid(3 + digits));
+            }
+            let len = 4 + digits;
+            return match u32::from_str_radix(&body[..digits], 16).ok().filter(|_| digits <= 6).and_then(char::from_u32) {
+                Some(c) => Ok((&body[digits + 1..], c)),
+                None => Err(invalid(len)),
+            };
+        }
+        _ => return Err(invalid(2)),
+    };
+    Ok((chars.as_str(), simple))
+}
 
 pub fn parse_string(input: &str) -> ParseResult<&str, Expr> {
-    let (input, s) = delimited(char('"'), take_until("\""), char('"'))(input)?;
-    Ok((input, Expr::Str(s.to_string())))
+    let (input, chars) = delimited(char('"'), many0(alt((parse_escape, none_of("\"\\")))), char('"'))(input)?;
+    Ok((input, Expr::Str(chars.into_iter().collect())))
 }
 
 
 pub fn parse_char(input: &str) -> ParseResult<&str, Expr> {
-    delimited(tag("'"), anychar, tag("'"))
+    delimited(tag("'"), alt((parse_escape, none_of("'\\"))), tag("'"))
         .map(|c| Expr::Char(c))
         .parse(input)
 }
-
-pub fn parse_variable(input: &str) -> ParseResult<&str, Expr> {
-    alt((
-        //parse_qualified_identifier,
-        parse_identifier,
-        parse_number,
-        parse_string,
-        parse_char,
-        //parse_qualified_identifier,
-        //delimited(tag("("), expr, tag(")")),
-    ))(input)
-}
\ No newline at end of file
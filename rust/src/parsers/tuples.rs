@@ -0,0 +1,32 @@
+use nom::bytes::complete::tag;
+use nom::combinator::opt;
+use nom::multi::separated_list0;
+
+use crate::llvm::ast::Expr;
+
+use super::expressions::parse_infix_expr;
+use super::statements::space_opt;
+use super::ParseResult;
+
+/// `(a, b)` or `(a,)`, the comma tells a tuple from a parenthesised item.
+/// Used for tuple literals, tuple types and tuple patterns
+pub fn tuple_list<'a, O, F>(mut item: F) -> impl FnMut(&'a str) -> ParseResult<&'a str, Vec<O>>
+where
+    F: FnMut(&'a str) -> ParseResult<&'a str, O>,
+{
+    move |input| {
+        let (input, _) = space_opt(tag("("))(input)?;
+        let (input, first) = item(input)?;
+        let (input, _) = space_opt(tag(","))(input)?;
+        let (input, rest) = separated_list0(space_opt(tag(",")), &mut item)(input)?;
+        let (input, _) = opt(space_opt(tag(",")))(input)?;
+        let (input, _) = space_opt(tag(")"))(input)?;
+        Ok((input, std::iter::once(first).chain(rest).collect()))
+    }
+}
+
+/// `(1, true)`
+pub fn parse_tuple(input: &str) -> ParseResult<&str, Expr> {
+    let (input, elements) = tuple_list(parse_infix_expr)(input)?;
+    Ok((input, Expr::Tuple(elements)))
+}
@@ -0,0 +1,73 @@
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::character::complete::digit1;
+use nom::combinator::{map_opt, opt, value};
+use nom::sequence::{delimited, pair, preceded, separated_pair};
+use nom::Parser;
+
+use crate::llvm::ast::Type;
+
+use super::enums::parenthesised;
+use super::statements::space_opt;
+use super::tokens::{keyword, parse_path};
+use super::tuples::tuple_list;
+use super::ParseResult;
+
+fn int(bits: u32, signed: bool) -> Type {
+    Type::Int { bits, signed }
+}
+
+/// parse one of the sized integer types, also used for literal suffixes like `10u8`
+pub fn parse_int_type(input: &str) -> ParseResult<&str, Type> {
+    alt((
+        value(int(8, true), keyword("i8")),
+        value(int(16, true), keyword("i16")),
+        value(int(32, true), keyword("i32")),
+        value(int(64, true), keyword("i64")),
+        value(int(128, true), keyword("i128")),
+        value(int(8, false), keyword("u8")),
+        value(int(16, false), keyword("u16")),
+        value(int(32, false), keyword("u32")),
+        value(int(64, false), keyword("u64")),
+    ))(input)
+}
+
+/// parse `f32` or `f64`, also used for literal suffixes like `2.5f32`
+pub fn parse_float_type(input: &str) -> ParseResult<&str, Type> {
+    alt((
+        value(Type::Float { bits: 32 }, keyword("f32")),
+        value(Type::Float { bits: 64 }, keyword("f64")),
+    ))(input)
+}
+
+/// parse a type annotation such as `i64`, `u8`, `f64`, `str`, `*i8`, `fn(i32) -> i32`, `(i32, bool)`, `[i32; 3]`, `&[i32]`, `Vec<i32>` or the path of a struct or enum
+pub fn parse_type(input: &str) -> ParseResult<&str, Type> {
+    space_opt(alt((
+        parse_int_type,
+        parse_float_type,
+        value(Type::Bool, keyword("bool")),
+        value(Type::Char, keyword("char")),
+        value(Type::Str, keyword("str")),
+        value(Type::Unit, tag("()")),
+        tuple_list(parse_type).map(Type::Tuple),
+        preceded(tag("*"), parse_type).map(|ty| Type::Ptr(Box::new(ty))),
+        delimited(
+            tag("["),
+            separated_pair(parse_type, tag(";"), space_opt(map_opt(digit1, |len: &str| len.parse().ok()))),
+            tag("]"),
+        ).map(|(ty, len)| Type::Array(Box::new(ty), len)),
+        preceded(tag("&"), delimited(space_opt(tag("[")), parse_type, tag("]"))).map(|ty| Type::Slice(Box::new(ty))),
+        preceded(keyword("Vec"), delimited(space_opt(tag("<")), parse_type, tag(">")))
+            .map(|ty| Type::Vec(Box::new(ty))),
+        preceded(
+            keyword("fn"),
+            pair(parenthesised(parse_type), opt(preceded(space_opt(tag("->")), parse_type))),
+        ).map(|(params, return_type)| Type::Function(params, Box::new(return_type.unwrap_or(Type::I32)))),
+        parse_path.map(|path| Type::Named(path.join("::"))),
+    )))(input)
+}
+
+/// parse the `: T` annotation following a binding or parameter name
+pub fn parse_type_annotation(input: &str) -> ParseResult<&str, Type> {
+    preceded(space_opt(tag(":")), parse_type)(input)
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use super::interpreter::Value;
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    value: Value,
+    mutable: bool,
+    /// a `const` or `static`, which closures use directly instead of capturing a copy
+    global: bool,
+}
+
+/// Lexically scoped variables. The first scope holds the globals (top-level bindings and functions),
+/// every block and function call pushes a scope on top of it.
+#[derive(Debug)]
+pub struct Environment {
+    scopes: Vec<HashMap<String, Binding>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Detaches every scope but the globals, so a function body only sees globals and its own locals
+    pub fn enter_function(&mut self) -> Vec<HashMap<String, Binding>> {
+        let locals = self.scopes.split_off(1);
+        self.push_scope();
+        locals
+    }
+
+    /// Restores the caller's scopes detached by `enter_function`
+    pub fn leave_function(&mut self, locals: Vec<HashMap<String, Binding>>) {
+        self.scopes.truncate(1);
+        self.scopes.extend(locals);
+    }
+
+    /// Copies of the variables in scope, which a closure created here captures.
+    /// Declared functions, `const`s and `static`s stay globals so they're looked up when used,
+    /// a `static mut` is shared with the rest of the program. Inner bindings shadow outer ones.
+    pub fn capture(&self) -> HashMap<String, Value> {
+        let globals = self.scopes[0].iter().filter(|(_, binding)| {
+            !binding.global && !matches!(binding.value, Value::Function(..))
+        });
+        globals
+            .chain(self.scopes[1..].iter().flatten())
+            .map(|(name, binding)| (name.clone(), binding.value.clone()))
+            .collect()
+    }
+
+    /// Binds `name` in the innermost scope, shadowing any previous binding
+    pub fn define(&mut self, name: &str, value: Value, mutable: bool) {
+        self.scopes
+            .last_mut()
+            .expect("the global scope is never popped")
+            .insert(
+                name.to_owned(),
+                Binding {
+                    value,
+                    mutable,
+                    global: false,
+                },
+            );
+    }
+
+    /// Binds `name` in the global scope, where every function sees it
+    pub fn define_global(&mut self, name: &str, value: Value, mutable: bool) {
+        self.scopes[0].insert(
+            name.to_owned(),
+            Binding {
+                value,
+                mutable,
+                global: true,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.lookup(name).map(|binding| &binding.value)
+    }
+
+    /// Updates the closest binding of `name`, which has to be declared with `let mut`
+    pub fn assign(&mut self, name: &str, value: Value) -> anyhow::Result<()> {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))?;
+        if !binding.mutable {
+            return Err(anyhow::anyhow!(
+                "Cannot assign twice to immutable variable `{}`",
+                name
+            ));
+        }
+        binding.value = value;
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
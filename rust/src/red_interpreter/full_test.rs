@@ -0,0 +1,694 @@
+#[cfg(test)]
+mod tests {
+    use crate::red_interpreter::interpreter::Interpreter;
+
+    /// Evaluates `src` and returns what the last statement evaluated to
+    fn eval_last(interpreter: &mut Interpreter, src: &str) -> anyhow::Result<String> {
+        let output = interpreter.eval_str(src)?;
+        output
+            .lines()
+            .last()
+            .map(|line| line.trim_start_matches("=> ").to_string())
+            .ok_or_else(|| anyhow::anyhow!("no output for: {}", src))
+    }
+
+    #[test]
+    fn test_nested_arithmetic() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval_last(&mut interpreter, "1 + 2 * 3;")?, "7");
+        assert_eq!(eval_last(&mut interpreter, "(1 + 2) * 3;")?, "9");
+        assert_eq!(eval_last(&mut interpreter, "100 - 20 - 30 / 3;")?, "70");
+        assert_eq!(eval_last(&mut interpreter, "200 * 200 - 1;")?, "39999");
+        Ok(())
+    }
+
+    #[test]
+    fn test_calls_as_operands() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn hello(num) {
+                return num + 5;
+                num;
+            }
+            fn sub(a, b) {
+                return a - b;
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "hello(3) + 1;")?, "9");
+        assert_eq!(eval_last(&mut interpreter, "hello(hello(1) * 2) - 1;")?, "16");
+        // arguments are evaluated before any parameter is bound
+        assert_eq!(eval_last(&mut interpreter, "let a = 10;")?, "10");
+        assert_eq!(eval_last(&mut interpreter, "let b = 3;")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "sub(b, a);")?, "-7");
+        Ok(())
+    }
+
+    #[test]
+    fn test_division_by_zero_is_an_error() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.eval_str("1 / (2 - 2);").is_err());
+    }
+
+    #[test]
+    fn test_comparisons() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval_last(&mut interpreter, "1 + 2 == 3;")?, "true");
+        assert_eq!(eval_last(&mut interpreter, "3 > 4;")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "2 * 3 <= 6;")?, "true");
+        assert_eq!(eval_last(&mut interpreter, "1000 >= 999 + 2;")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "'a' < 'b';")?, "true");
+        assert_eq!(eval_last(&mut interpreter, r#""abc" != "abd";"#)?, "true");
+        assert_eq!(eval_last(&mut interpreter, "true == false;")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "let big = 100000i64 * 100000;")?, "10000000000");
+        assert_eq!(eval_last(&mut interpreter, "big > 5;")?, "true");
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparing_mismatched_values_is_an_error() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.eval_str(r#"1 == "1";"#).is_err());
+    }
+
+    #[test]
+    fn test_if_statements() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn classify(n) {
+                if n < 0 {
+                    return 0 - 1;
+                } else if n == 0 {
+                    return 0;
+                } else {
+                    let big = n > 100;
+                    if big {
+                        return 2;
+                    }
+                }
+                return 1;
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "classify(0 - 5);")?, "-1");
+        assert_eq!(eval_last(&mut interpreter, "classify(0);")?, "0");
+        assert_eq!(eval_last(&mut interpreter, "classify(7);")?, "1");
+        assert_eq!(eval_last(&mut interpreter, "classify(700);")?, "2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_expressions() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn max(a, b) {
+                if a > b { a } else { b }
+            }
+            fn grade(score) {
+                let letter = if score >= 90 { 'A' } else if score >= 80 { 'B' } else { 'C' };
+                letter
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "max(3, 9);")?, "9");
+        assert_eq!(eval_last(&mut interpreter, "max(12, 9) * 2;")?, "24");
+        assert_eq!(eval_last(&mut interpreter, "grade(95);")?, "A");
+        assert_eq!(eval_last(&mut interpreter, "grade(85);")?, "B");
+        assert_eq!(eval_last(&mut interpreter, "grade(10);")?, "C");
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_branches_have_their_own_scope() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("if true { let inner = 1; }")?;
+        assert!(interpreter.eval_str("inner;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_condition_must_be_bool() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.eval_str("if 1 { 2; }").is_err());
+    }
+
+    #[test]
+    fn test_loops() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn first(limit) {
+                while true {
+                    for i in 0..limit {
+                        if i * i > 10 {
+                            return i;
+                        }
+                    }
+                    break;
+                }
+                return 0 - 1;
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "first(10);")?, "4");
+        assert_eq!(eval_last(&mut interpreter, "first(3);")?, "-1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_labelled_break_and_continue() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn find(target) {
+                'outer: for i in 0..5 {
+                    for j in 0..5 {
+                        if i * j == target {
+                            return i * 10 + j;
+                        }
+                    }
+                }
+                return 0 - 1;
+            }
+            fn stop() {
+                'outer: for i in 0..5 {
+                    for j in 0..5 {
+                        if j == 2 {
+                            break 'outer;
+                        }
+                    }
+                    return 99;
+                }
+                5
+            }
+            fn skip() {
+                'rows: for i in 0..3 {
+                    for j in 0..3 {
+                        if j == 1 {
+                            continue 'rows;
+                        }
+                        if j == 2 {
+                            return 99;
+                        }
+                    }
+                }
+                7
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "find(6);")?, "23");
+        assert_eq!(eval_last(&mut interpreter, "find(17);")?, "-1");
+        assert_eq!(eval_last(&mut interpreter, "stop();")?, "5");
+        assert_eq!(eval_last(&mut interpreter, "skip();")?, "7");
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_outside_loop() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("fn bad() { break; }")?;
+        assert!(interpreter.eval_str("bad();").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutable_variables() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn sum(n) {
+                let mut total = 0;
+                let mut i = 0;
+                while i < n {
+                    i += 1;
+                    if i == 3 {
+                        continue;
+                    }
+                    total = total + i;
+                }
+                total *= 2;
+                total
+            }
+            "#,
+        )?;
+        // 2 * (1 + 2 + 4 + 5)
+        assert_eq!(eval_last(&mut interpreter, "sum(5);")?, "24");
+        Ok(())
+    }
+
+    #[test]
+    fn test_shadowing_in_blocks() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn shadow() {
+                let mut x = 1;
+                if true {
+                    let mut x = 10;
+                    x += 1;
+                }
+                x
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "shadow();")?, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reassigning_immutable_binding_is_an_error() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("let x = 1;")?;
+        let err = interpreter.eval_str("x = 2;").unwrap_err();
+        assert!(err.to_string().contains("immutable"), "{}", err);
+        assert!(interpreter.eval_str("x += 2;").is_err());
+        assert!(interpreter.eval_str("missing = 2;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions_do_not_see_caller_locals() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn peek() { local }
+            fn caller() {
+                let local = 1;
+                peek()
+            }
+            "#,
+        )?;
+        assert!(interpreter.eval_str("caller();").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_logical_operators() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval_last(&mut interpreter, "1 < 2 && 2 < 3;")?, "true");
+        assert_eq!(eval_last(&mut interpreter, "1 > 2 || 2 > 3;")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "!(1 > 2) && !false;")?, "true");
+        assert!(interpreter.eval_str("!1;").is_err());
+        assert!(interpreter.eval_str("true && 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_logical_operators_short_circuit() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        // the right operands would fail if they were evaluated
+        assert_eq!(eval_last(&mut interpreter, "false && missing();")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "true || 1 / 0 == 0;")?, "true");
+        assert!(interpreter.eval_str("true && missing();").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sized_integers() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        // literals take the type of the other operand, arithmetic stays in that type
+        assert_eq!(eval_last(&mut interpreter, "let x: u8 = 200; x + 55;")?, "255");
+        assert!(interpreter.eval_str("x + 56;").unwrap_err().to_string().contains("overflow"));
+        assert!(interpreter.eval_str("100000 * 100000;").unwrap_err().to_string().contains("overflow"));
+        assert!(interpreter.eval_str("let y: u8 = 256;").is_err());
+        assert!(interpreter.eval_str("let z: i8 = -128; z - 1;").is_err());
+        // `as` truncates and reinterprets like compiled code does
+        assert_eq!(eval_last(&mut interpreter, "300 as u8;")?, "44");
+        assert_eq!(eval_last(&mut interpreter, "-1 as u8;")?, "255");
+        assert_eq!(eval_last(&mut interpreter, "255u8 as i8;")?, "-1");
+        assert_eq!(eval_last(&mut interpreter, "'a' as u8;")?, "97");
+        assert_eq!(eval_last(&mut interpreter, "fn half(n: u64) -> u64 { n / 2 } half(18_000_000_000_000_000_000);")?, "9000000000000000000");
+        Ok(())
+    }
+
+    #[test]
+    fn test_floats() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval_last(&mut interpreter, "1.5 + 2.25;")?, "3.75");
+        assert_eq!(eval_last(&mut interpreter, "3.0 * 2.0;")?, "6.0");
+        assert_eq!(eval_last(&mut interpreter, "-1e3 / 8.0;")?, "-125.0");
+        assert_eq!(eval_last(&mut interpreter, "0.1 + 0.2 > 0.3;")?, "true");
+        assert_eq!(eval_last(&mut interpreter, "let nan = 0.0 / 0.0; nan == nan;")?, "false");
+        assert_eq!(eval_last(&mut interpreter, "nan != nan;")?, "true");
+        // int <-> float casts, float to int truncates and saturates
+        assert_eq!(eval_last(&mut interpreter, "7 as f64 / 2.0;")?, "3.5");
+        assert_eq!(eval_last(&mut interpreter, "3.99 as i32;")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "-1.5 as u8;")?, "0");
+        assert_eq!(eval_last(&mut interpreter, "1e10 as i32;")?, "2147483647");
+        assert_eq!(eval_last(&mut interpreter, "let x: f32 = 0.1; x as f64;")?, "0.10000000149011612");
+        assert!(interpreter.eval_str("1.5 + 1;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_negation_and_wide_literals() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(eval_last(&mut interpreter, "-5 + 3;")?, "-2");
+        assert_eq!(eval_last(&mut interpreter, "let x = 4; -x * 2;")?, "-8");
+        assert_eq!(eval_last(&mut interpreter, "0xFF + 0b1;")?, "256");
+        assert_eq!(eval_last(&mut interpreter, "9_000_000_000 * 2;")?, "18000000000");
+        assert!(interpreter.eval_str("-true;").is_err());
+        // the signed minimums fit their suffixed type
+        assert_eq!(eval_last(&mut interpreter, "-128i8;")?, "-128");
+        assert_eq!(eval_last(&mut interpreter, "-9223372036854775808i64;")?, "-9223372036854775808");
+        assert_eq!(eval_last(&mut interpreter, "match -128i8 { -128i8 => 1, _ => 0 };")?, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_structs() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            struct Point { x: i32, y: i32 }
+            struct Line { from: Point, to: Point }
+            fn squared(line: Line) -> i32 {
+                let dx = line.to.x - line.from.x;
+                let dy = line.to.y - line.from.y;
+                dx * dx + dy * dy
+            }
+            fn shifted(p: Point, by: i32) -> Point {
+                Point { x: p.x + by, y: p.y + by }
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "let p = Point { y: 2, x: 1 }; p;")?, "Point { x: 1, y: 2 }");
+        assert_eq!(eval_last(&mut interpreter, "shifted(p, 3).y;")?, "5");
+        assert_eq!(
+            eval_last(&mut interpreter, "squared(Line { from: p, to: shifted(p, 3) });")?,
+            "18"
+        );
+        // structs are values, writing to a copy leaves the original alone
+        assert_eq!(
+            eval_last(&mut interpreter, "let mut line = Line { from: p, to: p }; line.to.x += 10; line.from.y = 7; line;")?,
+            "Line { from: Point { x: 1, y: 7 }, to: Point { x: 11, y: 2 } }"
+        );
+        assert_eq!(eval_last(&mut interpreter, "p.x;")?, "1");
+        assert!(interpreter.eval_str("p.x = 5;").is_err());
+        assert!(interpreter.eval_str("p.z;").is_err());
+        assert!(interpreter.eval_str("Point { x: 1 };").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_enums_and_match() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            enum Shape { Circle(i32), Rect(i32, i32), Empty }
+            enum Wrapped { Some(Shape), None }
+            fn area(shape: Shape) -> i32 {
+                match shape {
+                    Shape::Circle(r) => 3 * r * r,
+                    Shape::Rect(w, h) if w == h => { w * w }
+                    Shape::Rect(w, h) => w * h,
+                    Shape::Empty => 0,
+                }
+            }
+            fn describe(n: i32) -> str {
+                match n { 0 => "zero", -1 => "minus one", _ => "many" }
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "Shape::Rect(2, 3);")?, "Shape::Rect(2, 3)");
+        assert_eq!(eval_last(&mut interpreter, "Shape::Empty;")?, "Shape::Empty");
+        assert_eq!(eval_last(&mut interpreter, "area(Shape::Circle(2));")?, "12");
+        assert_eq!(eval_last(&mut interpreter, "area(Shape::Rect(4, 4)) + area(Shape::Rect(2, 5));")?, "26");
+        assert_eq!(eval_last(&mut interpreter, "area(Shape::Empty);")?, "0");
+        assert_eq!(eval_last(&mut interpreter, "describe(-1);")?, "minus one");
+        assert_eq!(eval_last(&mut interpreter, "describe(7);")?, "many");
+        assert_eq!(
+            eval_last(&mut interpreter, "match Wrapped::Some(Shape::Rect(1, 2)) { Wrapped::Some(Shape::Rect(_, h)) => h, other => 0 };")?,
+            "2"
+        );
+        // bindings only live in their arm
+        assert!(interpreter.eval_str("match Shape::Circle(1) { Shape::Circle(r) => r, _ => 0 }; r;").is_err());
+        assert!(interpreter.eval_str("match 5 { 1 => 1 };").is_err());
+        assert!(interpreter.eval_str("Shape::Rect(1);").is_err());
+        assert!(interpreter.eval_str("Shape::Square(1);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_closures() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn double(x: i32) -> i32 { x * 2 }
+            fn apply(f: fn(i32) -> i32, x: i32) -> i32 { f(x) }
+            fn adder(n: i32) -> fn(i32) -> i32 { |x| x + n }
+            fn twice(f: fn(i32) -> i32) -> fn(i32) -> i32 { |x| f(f(x)) }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "let inc = |x| x + 1; inc(41);")?, "42");
+        assert_eq!(eval_last(&mut interpreter, "apply(double, 5);")?, "10");
+        assert_eq!(eval_last(&mut interpreter, "apply(|x| x - 1, 5);")?, "4");
+        assert_eq!(eval_last(&mut interpreter, "let add5 = adder(5); add5(1);")?, "6");
+        assert_eq!(eval_last(&mut interpreter, "let quad = twice(double); quad(3);")?, "12");
+        // captures are copies taken when the closure is created
+        assert_eq!(eval_last(&mut interpreter, "let mut n = 1; let getn = || n; n = 2; getn();")?, "1");
+        assert_eq!(eval_last(&mut interpreter, "let f: fn(i64) -> bool = |x| x > 3; f(4);")?, "true");
+        assert!(interpreter.eval_str("inc(1, 2);").is_err());
+        // calling a variable that doesn't hold a function is an error, not a crash
+        assert!(interpreter.eval_str("let x = 5; x(1);").is_err());
+        // globals aren't captured, a closure reads and updates the `static mut` the program sees
+        interpreter.eval_str("static mut HITS: i32 = 0; const STEP: i32 = 2; let hit = || { HITS += STEP; HITS };")?;
+        assert_eq!(eval_last(&mut interpreter, "HITS = 10; hit();")?, "12");
+        assert_eq!(eval_last(&mut interpreter, "hit(); HITS;")?, "14");
+        Ok(())
+    }
+
+    #[test]
+    fn test_modules() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        let src = r#"
+            mod geometry {
+                pub struct Point { x: i32, y: i32 }
+                pub enum Shape { Square(i32), Dot }
+                pub fn origin() -> Point { Point { x: 0, y: 0 } }
+                pub fn area(s: Shape) -> i32 { match s { Shape::Square(w) => w * secret(w), Shape::Dot => 0 } }
+                fn secret(x: i32) -> i32 { x }
+                pub mod units { pub fn scale() -> i32 { super::secret(10) } }
+            }
+            use geometry::units::scale;
+            fn secret(x: i32) -> i32 { x + 1 }
+            "#;
+        assert_eq!(eval_last(&mut interpreter, &format!("{} geometry::origin();", src))?, "geometry::Point { x: 0, y: 0 }");
+        // the module's `secret` and the root's are different functions
+        assert_eq!(
+            eval_last(&mut interpreter, &format!("{} geometry::area(geometry::Shape::Square(3)) + scale() + secret(1);", src))?,
+            "21"
+        );
+        // functions are values under their full name too
+        assert_eq!(eval_last(&mut interpreter, &format!("{} let f = geometry::units::scale; f();", src))?, "10");
+        assert!(interpreter.eval_str(&format!("{} geometry::secret(1);", src)).is_err());
+        assert!(interpreter.eval_str("mod nowhere;").is_err());
+        // `std::args` are the script's path and its arguments, any other path left unresolved is an error
+        let mut script = Interpreter::with_args(vec!["script.ak".to_string(), "hello".to_string()]);
+        assert_eq!(eval_last(&mut script, "std::args[1];")?, "hello");
+        assert_eq!(eval_last(&mut script, "std::args.len();")?, "2");
+        assert!(script.eval_str("std::args[2];").unwrap_err().to_string().contains("out of bounds"));
+        assert_eq!(eval_last(&mut script, "let args = std::args; args[1..].len();")?, "1");
+        assert!(script.eval_str("args[1..3];").unwrap_err().to_string().contains("out of range"));
+        assert!(script.eval_str("foo::bar;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generic_functions() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn max<T>(a: T, b: T) -> T { if a > b { a } else { b } }
+            fn first<A, B>(a: A, b: B) -> A { a }
+            fn apply<T>(f: fn(T) -> T, x: T) -> T { f(x) }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "max(3, 7);")?, "7");
+        assert_eq!(eval_last(&mut interpreter, "max('a', 'z');")?, "z");
+        assert_eq!(eval_last(&mut interpreter, "first(true, 1);")?, "true");
+        assert_eq!(eval_last(&mut interpreter, "apply(|x| x * 2, 21);")?, "42");
+        // a literal takes the type the other arguments decide
+        assert_eq!(eval_last(&mut interpreter, "let x: u8 = 200; max(x, 100);")?, "200");
+        assert!(interpreter.eval_str("let y: u8 = 1; max(y, 300);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuples() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn divmod(a: i32, b: i32) -> (i32, i32) { (a / b, a - a / b * b) }
+            fn swap(p: (i32, bool)) -> (bool, i32) { (p.1, p.0) }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "let (q, r) = divmod(17, 5); q * 10 + r;")?, "32");
+        assert_eq!(eval_last(&mut interpreter, "divmod(7, 2).1;")?, "1");
+        assert_eq!(eval_last(&mut interpreter, "swap((1, true));")?, "(true, 1)");
+        assert_eq!(eval_last(&mut interpreter, "(5,);")?, "(5,)");
+        assert_eq!(eval_last(&mut interpreter, "let nested = (1, (2, 3)); nested.1.0;")?, "2");
+        assert_eq!(eval_last(&mut interpreter, "let mut t = (1, 2); t.0 = 5; t.1 += 1; t;")?, "(5, 3)");
+        assert_eq!(eval_last(&mut interpreter, "let (a, (b, _)) = (1, (true, 'c')); b;")?, "true");
+        assert_eq!(
+            eval_last(&mut interpreter, "match divmod(9, 3) { (q, 0) => q, (_, r) => r };")?,
+            "3"
+        );
+        assert!(interpreter.eval_str("let t = (1, 2); t.2;").is_err());
+        // destructured variables are immutable
+        assert!(interpreter.eval_str("let (x, y) = (1, 2); x = 3;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays_and_vecs() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn sum(v: Vec<i32>) -> i32 {
+                let mut total = 0;
+                for i in 0..v.len() { total += v[i]; }
+                total
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "[1, 2, 3];")?, "[1, 2, 3]");
+        assert_eq!(eval_last(&mut interpreter, "let mut a = [1, 2, 3]; a[1] = 5; a[2] += 1; a;")?, "[1, 5, 4]");
+        assert_eq!(eval_last(&mut interpreter, "let mut grid = [[0, 0], [0, 0]]; grid[1][0] = 7; grid;")?, "[[0, 0], [7, 0]]");
+        assert_eq!(eval_last(&mut interpreter, "let mut v: Vec<i32> = Vec::new(); v.push(1); v.push(2); v.push(3); v.len();")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "v.pop();")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "v[0] = 10; sum(v);")?, "12");
+        assert_eq!(eval_last(&mut interpreter, "[true, false].len();")?, "2");
+        assert!(interpreter.eval_str("let mut e: Vec<i32> = Vec::new(); e.pop();").is_err());
+        assert!(interpreter.eval_str("let b = [1, 2]; b[2];").is_err());
+        assert!(interpreter.eval_str("let mut c = [1, 2]; c[5] = 1;").is_err());
+        assert!(interpreter.eval_str("let w: Vec<i32> = Vec::new(); w.push(1);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_slices() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn sum(xs: &[i32]) -> i32 {
+                let mut total = 0;
+                for i in 0..xs.len() { total += xs[i]; }
+                total
+            }
+            let a = [1, 2, 3, 4, 5];
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "a[1..3];")?, "[2, 3]");
+        assert_eq!(eval_last(&mut interpreter, "a[..2].len();")?, "2");
+        assert_eq!(eval_last(&mut interpreter, "sum(a[2..]);")?, "12");
+        assert_eq!(eval_last(&mut interpreter, "sum(a[..]);")?, "15");
+        assert_eq!(eval_last(&mut interpreter, "let middle = a[1..4]; middle[1..][0];")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "let mut v: Vec<i32> = Vec::new(); v.push(7); v.push(8); sum(v[0..2]);")?, "15");
+        // a string is sliced by its UTF-8 bytes
+        assert_eq!(eval_last(&mut interpreter, "let s = \"hello world\"; s[0..5];")?, "[104, 101, 108, 108, 111]");
+        assert_eq!(eval_last(&mut interpreter, "let word = \"héllo\"; word[0..3];")?, "[104, 195, 169]");
+        assert_eq!(eval_last(&mut interpreter, "word[..].len();")?, "6");
+        assert_eq!(eval_last(&mut interpreter, "s[6..].len();")?, "5");
+        assert_eq!(eval_last(&mut interpreter, "a[2..2].len();")?, "0");
+        assert!(interpreter.eval_str("a[3..6];").is_err());
+        assert!(interpreter.eval_str("a[3..1];").is_err());
+        assert!(interpreter.eval_str("s[6..12];").unwrap_err().to_string().contains("out of range"));
+        assert!(interpreter.eval_str("let n = 6; a[n..];").unwrap_err().to_string().contains("out of range"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_macros() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("let x: u64 = 42; let s = \"hi\"; let c = 'z';")?;
+        assert_eq!(eval_last(&mut interpreter, "format!(\"x = {}, s = {}\", x, s);")?, "x = 42, s = hi");
+        assert_eq!(eval_last(&mut interpreter, "format!(\"{} {} {{}}\", c, 1 < 2);")?, "z true {}");
+        // a char prints as its UTF-8 encoding, whatever its width
+        assert_eq!(eval_last(&mut interpreter, "format!(\"{}{}{}\", 'é', '€', '\\u{1F600}');")?, "é€\u{1F600}");
+        // floats print like C's `%g`
+        assert_eq!(eval_last(&mut interpreter, "format!(\"{} {} {}\", 1.5, 2.0, 0.1);")?, "1.5 2 0.1");
+        assert_eq!(eval_last(&mut interpreter, "format!(\"{} {} {}\", 1234567.0, 0.00001, 3.14159265);")?, "1.23457e+06 1e-05 3.14159");
+        assert_eq!(eval_last(&mut interpreter, "println!(\"x = {}\", x);")?, "");
+        assert!(interpreter.eval_str("format!(\"{}\");").is_err());
+        assert!(interpreter.eval_str("format!(\"{}\", [1, 2]);").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_globals() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            fn bump() -> i32 {
+                COUNTER += 1;
+                COUNTER
+            }
+            const LIMIT: i32 = 10;
+            const DOUBLE: i64 = LIMIT as i64 * 2;
+            static mut COUNTER: i32 = 0;
+            static NAME: str = "red";
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "bump(); bump();")?, "2");
+        assert_eq!(eval_last(&mut interpreter, "COUNTER;")?, "2");
+        assert_eq!(eval_last(&mut interpreter, "DOUBLE + 1;")?, "21");
+        assert_eq!(eval_last(&mut interpreter, "format!(\"{} {}\", NAME, LIMIT);")?, "red 10");
+        assert!(interpreter.eval_str("LIMIT = 3;").is_err());
+        assert!(interpreter.eval_str("NAME = \"blue\";").is_err());
+        // globals are defined before the rest of the program runs, in the order their values need
+        assert_eq!(eval_last(&mut interpreter, "let early = B; const A: i32 = B * 2; const B: i32 = 3; A + early;")?, "9");
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str(
+            r#"
+            struct Point { x: i32, y: i32 }
+            enum Shape { Square(i32), Dot }
+            impl Point {
+                fn new(x: i32, y: i32) -> Point { Point { x: x, y: y } }
+                fn len(self) -> i32 { self.x + self.y }
+                fn scaled(self, by: i32) -> i32 { self.len() * by }
+            }
+            impl Shape {
+                fn area(self) -> i32 { match self { Shape::Square(w) => w * w, Shape::Dot => 0 } }
+            }
+            "#,
+        )?;
+        assert_eq!(eval_last(&mut interpreter, "let p = Point::new(1, 2); p.len();")?, "3");
+        assert_eq!(eval_last(&mut interpreter, "p.scaled(10);")?, "30");
+        assert_eq!(eval_last(&mut interpreter, "Shape::Square(3).area();")?, "9");
+        // `Shape::Dot` is still a variant, not a function
+        assert_eq!(eval_last(&mut interpreter, "Shape::Dot.area();")?, "0");
+        assert!(interpreter.eval_str("p.scaled();").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_chars() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        // a `char` is a whole code point, not a byte
+        assert_eq!(eval_last(&mut interpreter, r"'\u{1F600}' as u32;")?, "128512");
+        assert_eq!(eval_last(&mut interpreter, "'é' as i32;")?, "233");
+        assert_eq!(eval_last(&mut interpreter, "let c: char = 'é'; c == '\\u{e9}';")?, "true");
+        // only a `u8` casts to a `char`
+        assert_eq!(eval_last(&mut interpreter, "233u8 as char;")?, "é");
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_types() -> anyhow::Result<()> {
+        let mut interpreter = Interpreter::new();
+        // the loop variable has the type of the bounds, an unsuffixed literal takes the other's
+        assert_eq!(eval_last(&mut interpreter, "let mut last = 0u8; for i in 0..3u8 { last = i; } last;")?, "2");
+        // bounds past `i32::MAX` keep their width
+        assert_eq!(eval_last(&mut interpreter, "let mut count = 0; for i in 2147483646..2147483649i64 { count += 1; } count;")?, "3");
+        Ok(())
+    }
+}
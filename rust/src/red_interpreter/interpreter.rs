@@ -1,54 +1,76 @@
-use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
-};
+use std::{cmp::Ordering, collections::HashMap, path::Path};
 
 use tracing::debug;
 
+use super::environment::Environment;
 use crate::{
-    llvm::ast::{BinaryOp, Expr, Stmt},
-    parsers::parse_program,
+    llvm::ast::{
+        bind_type_params, globals_in_dependency_order, parse_format_string, substitute_types,
+        BinaryOp, Expr, Field, FormatPiece, GlobalKind, MatchArm, Param, Pattern, Stmt, Type,
+        UnaryOp, Variant,
+    },
+    parsers::modules::load_source,
     types::integer::Integer,
 };
 
 pub struct Interpreter {
-    symbol_table: HashMap<String, Value>, // assuming all variables are i32 for simplicity
+    env: Environment,
+    /// declared structs and their fields in declaration order
+    structs: HashMap<String, Vec<Field>>,
+    enums: HashMap<String, Vec<Variant>>,
+    /// `std::args`, the script's path followed by its arguments
+    args: Vec<String>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self {
-            symbol_table: HashMap::new(),
+            env: Environment::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            args: vec![],
         }
     }
 
-    pub fn eval_source(&mut self, script: &str) {
-        match parse_program(&script) {
-            Ok(parsed_program) => {
-                parsed_program
-                    .iter()
-                    .for_each(|stmt| match self.visit_stmt(stmt) {
-                        Ok(value) => {
-                            println!("=> {}", value);
-                        }
-                        Err(err) => eprintln!("Interpreter error: {}", err),
-                    });
-            }
-            Err(err) => {
-                println!("{}", "=".repeat(80));
-                eprintln!("[Parser::parse] error: {}", err);
-                println!("{}", "=".repeat(80));
-            }
+    /// An interpreter whose `std::args` are `args`, the script's path first like `argv`
+    pub fn with_args(args: Vec<String>) -> Self {
+        Self {
+            args,
+            ..Self::new()
+        }
+    }
+
+    /// Runs a program loaded with its modules, printing the value of every top-level statement
+    pub fn eval_program(&mut self, program: &[Stmt]) {
+        if let Err(err) = self.define_globals(program) {
+            eprintln!("Interpreter error: {}", err);
+            return;
         }
+        let stmts = program
+            .iter()
+            .filter(|stmt| !matches!(stmt, Stmt::Global { .. }));
+        stmts.for_each(|stmt| match self.visit_stmt(stmt) {
+            Ok(value) => {
+                println!("=> {}", value);
+            }
+            Err(err) => eprintln!("Interpreter error: {}", err),
+        });
     }
 
     #[allow(dead_code)]
+    /// Runs `input`, whose `mod name;` declarations are loaded from the working directory
     pub fn eval_str(&mut self, input: &str) -> anyhow::Result<String> {
-        match parse_program(&input) {
+        match load_source(input, Path::new(".")).map(|program| program.ast) {
             Ok(parsed_program) => {
                 let mut result = String::new();
-                for stmt in parsed_program {
-                    match self.visit_stmt(&stmt) {
+                if let Err(err) = self.define_globals(&parsed_program) {
+                    return Err(anyhow::anyhow!("Interpreter error: {}", err));
+                }
+                let stmts = parsed_program
+                    .iter()
+                    .filter(|stmt| !matches!(stmt, Stmt::Global { .. }));
+                for stmt in stmts {
+                    match self.visit_stmt(stmt) {
                         Ok(value) => result.push_str(&format!("=> {}\n", value)),
                         Err(err) => return Err(anyhow::anyhow!("Interpreter error: {}", err)),
                     }
@@ -64,15 +86,25 @@ impl Interpreter {
         }
     }
 
+    /// Defines the `const` and `static` items before anything runs, like the compiler does, so
+    /// code and `const`s can refer to the ones declared after them
+    fn define_globals(&mut self, program: &[Stmt]) -> anyhow::Result<()> {
+        for stmt in globals_in_dependency_order(program) {
+            self.visit_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
     fn visit_expr(&mut self, expr: &Expr) -> anyhow::Result<Value> {
         match expr {
-            Expr::Num(n) => Ok(Value::Number(Integer::Int(n.clone()))),
+            Expr::Num(n) => Ok(Value::Number(n.clone())),
+            Expr::Float(n) => Ok(Value::Float(*n)),
             Expr::Ident(ident) => {
-                let value = self.symbol_table.get(ident);
+                let value = self.env.get(ident);
                 match value {
                     Some(value) => Ok(value.to_owned()),
                     None => {
-                        debug!("Symbol table: {:?}", self.symbol_table);
+                        debug!("Environment: {:?}", self.env);
                         Err(anyhow::anyhow!("Undefined variable: {}", ident))
                     }
                 }
@@ -84,16 +116,21 @@ impl Interpreter {
                 }
                 Ok(Value::Array(values))
             }
+            Expr::Tuple(elements) => elements
+                .iter()
+                .map(|element| self.visit_expr(element))
+                .collect::<anyhow::Result<_>>()
+                .map(Value::Tuple),
             Expr::ArrayIndexing(array, index) => {
                 let array = self.visit_expr(array)?;
                 let index = self.visit_expr(index)?;
                 match (&array, &index) {
-                    (Value::Array(array), Value::Number(Integer::Int(index))) => {
-                        let index = *index as usize;
-                        if index >= array.len() {
+                    (Value::Array(array) | Value::Slice(array), Value::Number(index)) => {
+                        let index = index.to_i128();
+                        if index < 0 || index >= array.len() as i128 {
                             return Err(anyhow::anyhow!("Index out of bounds: {}", index));
                         }
-                        Ok(array[index].to_owned())
+                        Ok(array[index as usize].to_owned())
                     }
                     _ => Err(anyhow::anyhow!(
                         "Invalid array indexing: {}[{}]",
@@ -102,45 +139,100 @@ impl Interpreter {
                     )),
                 }
             }
-            Expr::QualifiedIdent(_) => todo!(),
+            Expr::Slice(array, start, end) => {
+                let array = self.visit_expr(array)?;
+                let start = start.as_deref().map(|start| self.visit_expr(start));
+                let end = end.as_deref().map(|end| self.visit_expr(end));
+                slice(array, start.transpose()?, end.transpose()?)
+            }
+            Expr::QualifiedIdent(path) if path == &["std", "args"] => Ok(Value::Array(
+                self.args
+                    .iter()
+                    .map(|arg| Value::Str(arg.as_str().into()))
+                    .collect(),
+            )),
+            // paths to items are resolved by `parsers::modules`, anything left is unknown
+            Expr::QualifiedIdent(path) => {
+                Err(anyhow::anyhow!("Unknown path `{}`", path.join("::")))
+            }
+            Expr::StructLiteral(name, fields) => self.eval_struct_literal(name, fields),
+            Expr::EnumVariant { .. } if expr.is_vec_new() => Ok(Value::Array(vec![])),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.eval_method_call(receiver, method, args),
+            Expr::MacroCall(name, args) => self.eval_macro_call(name, args),
+            Expr::EnumVariant {
+                enum_name,
+                variant,
+                args,
+            } => self.eval_enum_variant(enum_name, variant, args),
+            Expr::Match { scrutinee, arms } => self.eval_match(scrutinee, arms),
+            Expr::Closure {
+                params,
+                return_type,
+                body,
+            } => Ok(Value::Closure(
+                params.clone(),
+                return_type.clone(),
+                body.clone(),
+                self.env.capture(),
+            )),
+            Expr::FieldAccess(expr, field) => match self.visit_expr(expr)? {
+                Value::Struct(name, mut values) => {
+                    field_mut(&name, &mut values, field).map(|value| value.clone())
+                }
+                Value::Tuple(mut elements) => {
+                    element_mut(&mut elements, field).map(|value| value.clone())
+                }
+                other => Err(anyhow::anyhow!(
+                    "Cannot access field `{}` on {}",
+                    field,
+                    other.type_name()
+                )),
+            },
 
+            Expr::Infix(left, op, right) if op.is_logical() => self.eval_logical(op, left, right),
+            Expr::Unary(UnaryOp::Negate, expr) => match self.visit_expr(expr)? {
+                Value::Number(n) => n
+                    .clone()
+                    .checked_neg()
+                    .map(Value::Number)
+                    .ok_or_else(|| anyhow::anyhow!("Integer overflow: -{}", n)),
+                Value::Float(n) => Ok(Value::Float(-n)),
+                other => Err(anyhow::anyhow!("Cannot negate {}", other.type_name())),
+            },
+            Expr::Unary(UnaryOp::Not, expr) => match self.visit_expr(expr)? {
+                Value::Bool(b) => Ok(Value::Bool(!b)),
+                other => Err(anyhow::anyhow!("Cannot apply `!` to {}", other.type_name())),
+            },
             Expr::Infix(left, op, right) => {
-                let left_value;
-                let right_value;
-                {
-                    left_value = self.visit_expr(left)?;
-                    right_value = self.visit_expr(right)?;
-                    match (&left_value, &right_value) {
-                        (Value::Number(left), Value::Number(right)) => {
-                            let (left, right) = (left.clone(), right.clone());
-                            return match op {
-                                BinaryOp::Add => Ok(Value::Number(left + right)),
-                                BinaryOp::Subtract => Ok(Value::Number(left - right)),
-                                BinaryOp::Multiply => Ok(Value::Number(left * right)),
-                                BinaryOp::Divide => Ok(Value::Number(left / right)),
-                            };
-                        }
-                        (Value::Str(left), Value::Str(right)) => {
-                            return match op {
-                                BinaryOp::Add => {
-                                    Ok(Value::Str(format!("{}{}", left, right).into()))
-                                }
-                                _ => Err(anyhow::anyhow!("Unexpected operator: {}", op)),
-                            };
-                        }
-                        _ => {
-                            return Err(anyhow::anyhow!(
-                                "Invalid operation: {} {} {}",
-                                left_value,
-                                op,
-                                right_value
-                            ))
-                        }
+                let left_value = self.visit_expr(left)?;
+                let right_value = self.visit_expr(right)?;
+                let (left_value, right_value) = match (&**left, &**right) {
+                    // two literals keep their own widths, the wider one wins
+                    (Expr::Num(_), Expr::Num(_)) => (left_value, right_value),
+                    _ => {
+                        let left_value = adopt_literal_width(left, left_value, &right_value)?;
+                        let right_value = adopt_literal_width(right, right_value, &left_value)?;
+                        (left_value, right_value)
                     }
-                }
+                };
+                eval_binary_op(op, left_value, right_value)
+            }
+            Expr::Cast(expr, ty) => {
+                let value = self.visit_expr(expr)?;
+                cast_value(value, ty)
             }
 
             Expr::Char(c) => Ok(Value::Char(*c)),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.eval_if(condition, then_branch, Some(else_branch)),
             Expr::Str(s) => Ok(Value::Str(s.clone().into())),
             Expr::Call(name, args) => match self.call_function(name.as_str(), args) {
                 Ok(value) => Ok(value),
@@ -151,145 +243,1180 @@ impl Interpreter {
 
     fn call_function(&mut self, name: &str, args: &[Expr]) -> anyhow::Result<Value> {
         //println!("calling function: {}", name);
+        let symbol = self.env.get(name);
+        match symbol {
+            Some(value) => match value.clone() {
+                Value::Function(type_params, params, return_type, body) => self.call_value(
+                    (&type_params, &params, return_type.as_ref(), &body),
+                    &HashMap::new(),
+                    Some(&Type::I32),
+                    args,
+                ),
+                Value::Closure(params, return_type, body, captured) => self.call_value(
+                    (&[], &params, return_type.as_ref(), &body),
+                    &captured,
+                    None,
+                    args,
+                ),
+                Value::None => Ok(Value::None),
+                _ => Err(anyhow::anyhow!("`{}` is not a function", name)),
+            },
+            None => Err(anyhow::anyhow!("Undefined function: {}", name)),
+        }
+    }
+
+    /// Calls a function or closure with `args` evaluated in the caller's scope. The body only sees
+    /// the globals, the variables the closure captured and the parameters. Parameters and the
+    /// result without an annotation take `default_type`, a closure leaves them as they are.
+    /// The type parameters of a generic function are bound to the types of the arguments.
+    fn call_value(
+        &mut self,
+        (type_params, params, return_type, body): (&[String], &[Param], Option<&Type>, &[Stmt]),
+        captured: &HashMap<String, Value>,
+        default_type: Option<&Type>,
+        args: &[Expr],
+    ) -> anyhow::Result<Value> {
+        if params.len() != args.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} arguments, got {}",
+                params.len(),
+                args.len()
+            ));
+        }
+        // Evaluate the arguments in the caller's scope before binding them
+        let arg_values = args
+            .iter()
+            .map(|arg| self.visit_expr(arg))
+            .collect::<anyhow::Result<Vec<Value>>>()?;
+        self.call_with_values(
+            (type_params, params, return_type, body),
+            captured,
+            default_type,
+            args,
+            arg_values,
+        )
+    }
+
+    /// Like `call_value`, with the arguments `args` already evaluated to `arg_values`
+    fn call_with_values(
+        &mut self,
+        (type_params, params, return_type, body): (&[String], &[Param], Option<&Type>, &[Stmt]),
+        captured: &HashMap<String, Value>,
+        default_type: Option<&Type>,
+        args: &[Expr],
+        arg_values: Vec<Value>,
+    ) -> anyhow::Result<Value> {
+        let instance;
+        let (params, return_type, body) = if type_params.is_empty() {
+            (params, return_type, body)
+        } else {
+            let bindings = infer_type_args(type_params, params, args, &arg_values);
+            let mut instance_body = body.to_vec();
+            substitute_types(&mut instance_body, &bindings);
+            instance = (
+                params
+                    .iter()
+                    .map(|param| param.substitute(&bindings))
+                    .collect::<Vec<_>>(),
+                return_type.map(|ty| ty.substitute(&bindings)),
+                instance_body,
+            );
+            (&instance.0[..], instance.1.as_ref(), &instance.2[..])
+        };
+
+        // Create a new scope
+        let caller_scopes = self.env.enter_function();
+        for (name, value) in captured {
+            self.env.define(name, value.clone(), false);
+        }
+        let mut bound = Ok(());
+        for (param, value) in params.iter().zip(arg_values) {
+            let value = match param.ty.as_ref().or(default_type) {
+                Some(ty) => conform(value, ty),
+                None => Ok(value),
+            };
+            match value {
+                Ok(value) => self.env.define(&param.ident, value, false),
+                Err(err) => bound = Err(err),
+            }
+        }
+
+        let ret = bound.and_then(|_| self.eval_function_body(body));
+        self.env.leave_function(caller_scopes);
+
+        match return_type.or(default_type) {
+            Some(ty) => conform(ret?, ty),
+            None => ret,
+        }
+    }
+
+    /// Builds a struct with its fields in declaration order, converted to their declared types
+    fn eval_struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[(String, Expr)],
+    ) -> anyhow::Result<Value> {
+        let declared = self
+            .structs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Undefined struct: {}", name))?;
+        let mut values = HashMap::new();
+        for (field, expr) in fields {
+            let ty = &declared
+                .iter()
+                .find(|declared| declared.ident == *field)
+                .ok_or_else(|| anyhow::anyhow!("Struct `{}` has no field `{}`", name, field))?
+                .ty;
+            let value = conform(self.visit_expr(expr)?, ty)?;
+            if values.insert(field.as_str(), value).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Field `{}` is specified more than once",
+                    field
+                ));
+            }
+        }
+        let values = declared
+            .iter()
+            .map(|field| match values.remove(field.ident.as_str()) {
+                Some(value) => Ok((field.ident.clone(), value)),
+                None => Err(anyhow::anyhow!(
+                    "Missing field `{}` in `{}` literal",
+                    field.ident,
+                    name
+                )),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Value::Struct(name.to_owned(), values))
+    }
+
+    /// `println!` prints its formatted arguments and a newline, `format!` yields them as a string.
+    /// Values print like the compiled `printf` does.
+    fn eval_macro_call(&mut self, name: &str, args: &[Expr]) -> anyhow::Result<Value> {
+        let Some((Expr::Str(format), args)) = args.split_first() else {
+            return Err(anyhow::anyhow!(
+                "`{}!` expects a string literal first",
+                name
+            ));
+        };
+        let pieces = parse_format_string(format)
+            .map_err(|err| anyhow::anyhow!("Invalid format string in `{}!`: {}", name, err))?;
+        let mut args = args.iter();
+        let mut formatted = String::new();
+        for piece in pieces {
+            match piece {
+                FormatPiece::Text(text) => formatted.push_str(&text),
+                FormatPiece::Placeholder => {
+                    let arg = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("Missing argument for `{}!`", name))?;
+                    formatted.push_str(&format_value(self.visit_expr(arg)?)?);
+                }
+            }
+        }
+        if args.next().is_some() {
+            return Err(anyhow::anyhow!("Too many arguments for `{}!`", name));
+        }
         match name {
-            "println!" => {
-                //println!("calling println!");
-                if let Some(arg) = args.get(0) {
-                    let value = self.visit_expr(arg)?;
-                    println!("about to print: {:?}", value);
-                    match value {
-                        Value::Str(s) => {
-                            println!("{:?}", s);
-                            Ok(Value::None)
-                        }
-                        Value::Number(n) => {
-                            // Handle numbers
-                            println!("{}", n);
-                            Ok(Value::None)
-                        }
-                        x => Err(anyhow::anyhow!(
-                            "Expected string argument to print. Got: {:?}",
-                            x
-                        )),
-                    }
-                } else {
-                    Err(anyhow::anyhow!(
-                        "print function expects at least one argument"
-                    ))
+            "println" => {
+                println!("{}", formatted);
+                Ok(Value::None)
+            }
+            "format" => Ok(Value::Str(formatted.into())),
+            _ => Err(anyhow::anyhow!("Unknown macro: {}!", name)),
+        }
+    }
+
+    /// Calls a method of an `impl` block with the receiver as its `self` argument, or one of the
+    /// builtin methods: `len` of an array, `Vec` or slice, and `push` and `pop`, which modify the
+    /// `Vec` in the variable they're called on. Arrays and `Vec`s are both `Value::Array`.
+    fn eval_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<Value> {
+        let mut receiver_value = self.visit_expr(receiver)?;
+        if let Value::Struct(name, _) | Value::Enum(name, ..) = &receiver_value {
+            let function = format!("{}::{}", name, method);
+            if let Some(Value::Function(type_params, params, return_type, body)) =
+                self.env.get(&function).cloned()
+            {
+                if params.len() != args.len() + 1 {
+                    return Err(anyhow::anyhow!(
+                        "Expected {} arguments, got {}",
+                        params.len(),
+                        args.len() + 1
+                    ));
+                }
+                let mut values = vec![receiver_value];
+                for arg in args {
+                    values.push(self.visit_expr(arg)?);
                 }
+                let args = [std::slice::from_ref(receiver), args].concat();
+                return self.call_with_values(
+                    (&type_params, &params, return_type.as_ref(), &body),
+                    &HashMap::new(),
+                    Some(&Type::I32),
+                    &args,
+                    values,
+                );
             }
+        }
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.visit_expr(arg)?);
+        }
+        let type_name = receiver_value.type_name();
+        let elements = match &mut receiver_value {
+            Value::Array(elements) => elements,
+            Value::Slice(elements) if method == "len" => elements,
+            _ => return Err(anyhow::anyhow!("No method `{}` on {}", method, type_name)),
+        };
+        let result = match (method, values.pop(), values.is_empty()) {
+            ("len", None, _) => {
+                let len = Integer::with_width(elements.len() as i128, 64, false)
+                    .expect("a length fits in a u64");
+                return Ok(Value::Number(len));
+            }
+            ("push", Some(value), true) => {
+                let value = match elements.first() {
+                    Some(first) => adopt_literal_width(&args[0], value, first)?,
+                    None => value,
+                };
+                elements.push(value);
+                Value::None
+            }
+            ("pop", None, _) => elements
+                .pop()
+                .ok_or_else(|| anyhow::anyhow!("Cannot pop from an empty Vec"))?,
             _ => {
-                let symbol = self.symbol_table.get(name);
-                match symbol {
-                    Some(value) => {
-                        match value.clone() {
-                            Value::Function(params, body) => {
-                                if params.len() != args.len() {
-                                    return Err(anyhow::anyhow!(
-                                        "Expected {} arguments, got {}",
-                                        params.len(),
-                                        args.len()
-                                    ));
-                                }
-                                // Create a new scope
-                                let old_env = self.symbol_table.clone();
-
-                                let ret = {
-                                    // Bind arguments to parameters
-                                    params.iter().zip(args.iter().cloned()).for_each(|x| {
-                                        let (param, arg) = x;
-                                        let arg_value = self.visit_expr(&arg);
-                                        match arg_value {
-                                            Ok(value) => {
-                                                self.symbol_table.insert(param.to_owned(), value);
-                                            }
-                                            Err(err) => {
-                                                println!("Error: {}", err);
-                                            }
-                                        }
-                                    });
-                                    // Execute the body
-                                    let mut value = Value::Number(0.into()); // Default value
-                                    body.iter().cloned().for_each(|stmt| {
-                                        value = self.visit_stmt(&stmt).unwrap();
-                                    });
-                                    // Return the last value
-                                    value
-                                };
-                                //println!("ret: {:?} ({})", ret, ret.type_name());
-
-                                self.symbol_table = old_env;
-
-                                return Ok(Value::Return(Box::new(ret)));
-                            }
-                            Value::None => Ok(Value::None),
-                            _ => todo!("Not a function: {}", name),
-                        }
+                return Err(anyhow::anyhow!(
+                    "No method `{}` taking {} argument(s) on {}",
+                    method,
+                    args.len(),
+                    type_name
+                ))
+            }
+        };
+        let Expr::Ident(ident) = receiver else {
+            return Err(anyhow::anyhow!(
+                "`{}` can only be called on a variable",
+                method
+            ));
+        };
+        self.env.assign(ident, receiver_value)?;
+        Ok(result)
+    }
+
+    /// Builds an enum value, converting the payload to the variant's declared types
+    fn eval_enum_variant(
+        &mut self,
+        enum_name: &str,
+        variant: &str,
+        args: &[Expr],
+    ) -> anyhow::Result<Value> {
+        // `Point::new(1, 2)` of an `impl` block that an earlier `eval_str` declared
+        let function = format!("{}::{}", enum_name, variant);
+        if let Some(Value::Function(..)) = self.env.get(&function) {
+            return self.call_function(&function, args);
+        }
+        let fields = self
+            .enums
+            .get(enum_name)
+            .and_then(|variants| variants.iter().find(|declared| declared.ident == variant))
+            .map(|declared| declared.fields.clone())
+            .ok_or_else(|| anyhow::anyhow!("Undefined variant: {}::{}", enum_name, variant))?;
+        if fields.len() != args.len() {
+            return Err(anyhow::anyhow!(
+                "{}::{} expects {} value(s), got {}",
+                enum_name,
+                variant,
+                fields.len(),
+                args.len()
+            ));
+        }
+        let mut payload = Vec::new();
+        for (arg, ty) in args.iter().zip(&fields) {
+            payload.push(conform(self.visit_expr(arg)?, ty)?);
+        }
+        Ok(Value::Enum(
+            enum_name.to_owned(),
+            variant.to_owned(),
+            payload,
+        ))
+    }
+
+    /// Runs the first arm whose pattern matches and whose guard holds, with the pattern's bindings in scope
+    fn eval_match(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> anyhow::Result<Value> {
+        let value = self.visit_expr(scrutinee)?;
+        if value.interrupts() {
+            return Ok(value);
+        }
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !self.match_pattern(&arm.pattern, &value, &mut bindings)? {
+                continue;
+            }
+            self.env.push_scope();
+            for (name, value) in bindings {
+                self.env.define(&name, value, false);
+            }
+            let guard = match &arm.guard {
+                Some(guard) => self.visit_expr(guard),
+                None => Ok(Value::Bool(true)),
+            };
+            let result = match guard {
+                Ok(Value::Bool(true)) => Some(self.eval_statements(&arm.body)),
+                Ok(Value::Bool(false)) => None,
+                Ok(other) => Some(Err(anyhow::anyhow!(
+                    "Expected a boolean guard, got {}",
+                    other.type_name()
+                ))),
+                Err(err) => Some(Err(err)),
+            };
+            self.env.pop_scope();
+            if let Some(result) = result {
+                return result;
+            }
+        }
+        Err(anyhow::anyhow!("No match arm matched {}", value))
+    }
+
+    /// Whether `value` matches `pattern`, collecting the values its bindings capture
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> anyhow::Result<bool> {
+        match (pattern, value) {
+            (Pattern::Wildcard, _) => Ok(true),
+            (Pattern::Binding(name), value) => {
+                bindings.push((name.clone(), value.clone()));
+                Ok(true)
+            }
+            (Pattern::Literal(literal), value) => {
+                let literal = self.visit_expr(literal)?;
+                compare_values(&BinaryOp::Equal, value, &literal)
+            }
+            (
+                Pattern::Variant {
+                    enum_name,
+                    variant,
+                    fields,
+                },
+                Value::Enum(value_enum, value_variant, payload),
+            ) if enum_name == value_enum => {
+                if variant != value_variant {
+                    return Ok(false);
+                }
+                for (field, value) in fields.iter().zip(payload) {
+                    if !self.match_pattern(field, value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (Pattern::Variant { enum_name, .. }, value) => Err(anyhow::anyhow!(
+                "Cannot match {} against a `{}` pattern",
+                value.type_name(),
+                enum_name
+            )),
+            (Pattern::Tuple(patterns), Value::Tuple(elements))
+                if patterns.len() == elements.len() =>
+            {
+                for (pattern, element) in patterns.iter().zip(elements) {
+                    if !self.match_pattern(pattern, element, bindings)? {
+                        return Ok(false);
                     }
-                    None => Err(anyhow::anyhow!("Undefined function: {}", name)),
                 }
+                Ok(true)
+            }
+            (Pattern::Tuple(patterns), value) => Err(anyhow::anyhow!(
+                "Cannot match {} against a tuple pattern of {} element(s)",
+                value.type_name(),
+                patterns.len()
+            )),
+        }
+    }
+
+    /// Runs a function body until it finishes or hits a `return`, yielding the returned value
+    fn eval_function_body(&mut self, body: &[Stmt]) -> anyhow::Result<Value> {
+        let mut value = Value::Number(0.into()); // Default value
+        for stmt in body {
+            value = self.visit_stmt(stmt)?;
+            match value {
+                Value::Return(ret) => return Ok(*ret),
+                Value::Break(_) => return Err(anyhow::anyhow!("`break` outside of a loop")),
+                Value::Continue(_) => return Err(anyhow::anyhow!("`continue` outside of a loop")),
+                _ => {}
+            }
+        }
+        // Return the last value
+        Ok(value)
+    }
+
+    /// Runs a nested block in its own scope
+    fn eval_block(&mut self, stmts: &[Stmt]) -> anyhow::Result<Value> {
+        self.env.push_scope();
+        let value = self.eval_statements(stmts);
+        self.env.pop_scope();
+        value
+    }
+
+    /// Yields the value of the last statement, or the `return`/`break`/`continue` that cut the statements short
+    fn eval_statements(&mut self, stmts: &[Stmt]) -> anyhow::Result<Value> {
+        let mut value = Value::None;
+        for stmt in stmts {
+            value = self.visit_stmt(stmt)?;
+            if value.interrupts() {
+                break;
+            }
+        }
+        Ok(value)
+    }
+
+    fn eval_while(
+        &mut self,
+        label: &Option<String>,
+        condition: &Expr,
+        body: &[Stmt],
+    ) -> anyhow::Result<Value> {
+        loop {
+            match self.visit_expr(condition)? {
+                Value::Bool(true) => {}
+                Value::Bool(false) => break,
+                other => {
+                    return Err(anyhow::anyhow!(
+                        "Expected a boolean condition, got {}",
+                        other.type_name()
+                    ))
+                }
+            }
+            match self.eval_block(body)? {
+                Value::Break(target) if targets_loop(&target, label) => break,
+                Value::Continue(target) if targets_loop(&target, label) => continue,
+                value if value.interrupts() => return Ok(value),
+                _ => {}
+            }
+        }
+        Ok(Value::None)
+    }
+
+    fn eval_for(
+        &mut self,
+        label: &Option<String>,
+        ident: &str,
+        start: &Expr,
+        end: &Expr,
+        body: &[Stmt],
+    ) -> anyhow::Result<Value> {
+        let literal_start = matches!(start, Expr::Num(_));
+        let (mut current, end) = match (self.visit_expr(start)?, self.visit_expr(end)?) {
+            // the loop variable has the type of the bounds, an unsuffixed literal takes the other's
+            (Value::Number(start), Value::Number(end)) if literal_start => {
+                let (bits, signed) = end.width();
+                (start.cast(bits, signed), end)
+            }
+            (Value::Number(start), Value::Number(end)) => (start, end),
+            (start, end) => {
+                return Err(anyhow::anyhow!(
+                    "Range bounds must be numbers, got {}..{}",
+                    start.type_name(),
+                    end.type_name()
+                ))
             }
+        };
+        while current < end {
+            self.env.push_scope();
+            self.env
+                .define(ident, Value::Number(current.clone()), false);
+            let value = self.eval_statements(body);
+            self.env.pop_scope();
+            match value? {
+                Value::Break(target) if targets_loop(&target, label) => break,
+                Value::Continue(target) if targets_loop(&target, label) => {}
+                value if value.interrupts() => return Ok(value),
+                _ => {}
+            }
+            let (bits, signed) = current.width();
+            current = current
+                .clone()
+                .checked_add(Integer::from(1).cast(bits, signed))
+                .ok_or_else(|| anyhow::anyhow!("Integer overflow in range {}..{}", current, end))?;
+        }
+        Ok(Value::None)
+    }
+
+    /// `&&`/`||` skip the right operand once the left one decides the result
+    fn eval_logical(&mut self, op: &BinaryOp, left: &Expr, right: &Expr) -> anyhow::Result<Value> {
+        let left = match self.visit_expr(left)? {
+            Value::Bool(b) => b,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Expected bool operands for {}, got {}",
+                    op,
+                    other.type_name()
+                ))
+            }
+        };
+        match (op, left) {
+            (BinaryOp::And, false) => return Ok(Value::Bool(false)),
+            (BinaryOp::Or, true) => return Ok(Value::Bool(true)),
+            _ => {}
+        }
+        match self.visit_expr(right)? {
+            Value::Bool(b) => Ok(Value::Bool(b)),
+            other => Err(anyhow::anyhow!(
+                "Expected bool operands for {}, got {}",
+                op,
+                other.type_name()
+            )),
+        }
+    }
+
+    fn eval_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &[Stmt],
+        else_branch: Option<&[Stmt]>,
+    ) -> anyhow::Result<Value> {
+        match self.visit_expr(condition)? {
+            Value::Bool(true) => self.eval_block(then_branch),
+            Value::Bool(false) => match else_branch {
+                Some(else_branch) => self.eval_block(else_branch),
+                None => Ok(Value::None),
+            },
+            other => Err(anyhow::anyhow!(
+                "Expected a boolean condition, got {}",
+                other.type_name()
+            )),
         }
     }
 
     fn visit_stmt(&mut self, stmt: &Stmt) -> anyhow::Result<Value> {
         match stmt {
-            Stmt::Assignment { ident, expr } => {
+            Stmt::Assignment {
+                ident,
+                mutable,
+                ty,
+                expr,
+                ..
+            } => {
                 let value = self.visit_expr(&expr)?;
-                self.symbol_table.insert(ident.clone(), value.clone());
+                // a `return` inside an `if` expression leaves the function instead of binding
+                if value.interrupts() {
+                    return Ok(value);
+                }
+                let value = match ty {
+                    Some(ty) => conform(value, ty)?,
+                    None => value,
+                };
+                self.env.define(ident, value.clone(), *mutable);
+                Ok(value)
+            }
+            Stmt::Destructure {
+                pattern, ty, expr, ..
+            } => {
+                let value = self.visit_expr(expr)?;
+                if value.interrupts() {
+                    return Ok(value);
+                }
+                let value = match ty {
+                    Some(ty) => conform(value, ty)?,
+                    None => value,
+                };
+                let mut bindings = Vec::new();
+                if !self.match_pattern(pattern, &value, &mut bindings)? {
+                    return Err(anyhow::anyhow!("`let` pattern doesn't match {}", value));
+                }
+                for (name, value) in bindings {
+                    self.env.define(&name, value, false);
+                }
                 Ok(value)
             }
-            Stmt::Expression(expr) | Stmt::Return(expr) => Ok(self.visit_expr(&expr)?),
+            Stmt::Reassignment {
+                ident, op, expr, ..
+            } => {
+                let value = self.visit_expr(&expr)?;
+                if value.interrupts() {
+                    return Ok(value);
+                }
+                let current = self
+                    .env
+                    .get(ident)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", ident))?;
+                let value = adopt_literal_width(expr, value, &current)?;
+                let value = match op {
+                    Some(op) => eval_binary_op(op, current, value)?,
+                    None => value,
+                };
+                self.env.assign(ident, value)?;
+                Ok(Value::None)
+            }
+            Stmt::FieldAssignment {
+                ident,
+                fields,
+                op,
+                expr,
+                ..
+            } => {
+                let value = self.visit_expr(expr)?;
+                if value.interrupts() {
+                    return Ok(value);
+                }
+                let mut root = self
+                    .env
+                    .get(ident)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", ident))?;
+                let mut target = &mut root;
+                for field in fields {
+                    target = match target {
+                        Value::Struct(name, values) => field_mut(name, values, field)?,
+                        Value::Tuple(elements) => element_mut(elements, field)?,
+                        other => {
+                            return Err(anyhow::anyhow!(
+                                "Cannot access field `{}` on {}",
+                                field,
+                                other.type_name()
+                            ))
+                        }
+                    };
+                }
+                let value = adopt_literal_width(expr, value, target)?;
+                *target = match op {
+                    Some(op) => eval_binary_op(op, target.clone(), value)?,
+                    None => value,
+                };
+                self.env.assign(ident, root)?;
+                Ok(Value::None)
+            }
+            Stmt::IndexAssignment {
+                ident,
+                indices,
+                op,
+                expr,
+                ..
+            } => {
+                let value = self.visit_expr(expr)?;
+                if value.interrupts() {
+                    return Ok(value);
+                }
+                let mut positions = Vec::new();
+                for index in indices {
+                    positions.push(self.visit_expr(index)?);
+                }
+                let mut root = self
+                    .env
+                    .get(ident)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", ident))?;
+                let mut target = &mut root;
+                for index in positions {
+                    target = index_mut(target, index)?;
+                }
+                let value = adopt_literal_width(expr, value, target)?;
+                *target = match op {
+                    Some(op) => eval_binary_op(op, target.clone(), value)?,
+                    None => value,
+                };
+                self.env.assign(ident, root)?;
+                Ok(Value::None)
+            }
+            Stmt::StructDeclaration { ident, fields, .. } => {
+                self.structs.insert(ident.clone(), fields.clone());
+                Ok(Value::None)
+            }
+            // `const` and `static` items are globals, only a `static mut` can be assigned
+            Stmt::Global {
+                ident,
+                kind,
+                ty,
+                expr,
+                ..
+            } => {
+                let value = conform(self.visit_expr(expr)?, ty)?;
+                self.env
+                    .define_global(ident, value, *kind == GlobalKind::StaticMut);
+                Ok(Value::None)
+            }
+            Stmt::EnumDeclaration {
+                ident, variants, ..
+            } => {
+                self.enums.insert(ident.clone(), variants.clone());
+                Ok(Value::None)
+            }
+            // `eval_str` flattens modules before running anything
+            Stmt::Module { ident, .. } => Err(anyhow::anyhow!(
+                "Module `{}` has to be loaded before it's run",
+                ident
+            )),
+            Stmt::Use { path, .. } => Err(anyhow::anyhow!(
+                "`use {}` has to be resolved before it's run",
+                path.join("::")
+            )),
+            Stmt::Impl { ty, .. } => Err(anyhow::anyhow!(
+                "`impl {}` has to be resolved before it's run",
+                ty
+            )),
+            Stmt::Expression(expr, _) => Ok(self.visit_expr(&expr)?),
+            Stmt::Return(expr, _) => Ok(Value::Return(Box::new(self.visit_expr(&expr)?))),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => self.eval_if(condition, then_branch, else_branch.as_deref()),
+            Stmt::While {
+                label,
+                condition,
+                body,
+                ..
+            } => self.eval_while(label, condition, body),
+            Stmt::For {
+                label,
+                ident,
+                start,
+                end,
+                body,
+                ..
+            } => self.eval_for(label, ident, start, end, body),
+            Stmt::Break(label, _) => Ok(Value::Break(label.clone())),
+            Stmt::Continue(label, _) => Ok(Value::Continue(label.clone())),
             Stmt::FunctionDeclaration {
                 ident,
+                type_params,
                 params,
+                return_type,
                 body,
+                ..
             } => {
-                let value: Value = Value::Function(params.to_owned(), body.to_owned());
-                self.symbol_table.insert(ident.to_owned(), value.to_owned());
+                let value: Value = Value::Function(
+                    type_params.to_owned(),
+                    params.to_owned(),
+                    return_type.to_owned(),
+                    body.to_owned(),
+                );
+                self.env.define(ident, value, false);
                 Ok(Value::None)
             }
         }
     }
 }
 
+/// The value of `field` among the fields of a `name` struct
+fn field_mut<'v>(
+    name: &str,
+    values: &'v mut [(String, Value)],
+    field: &str,
+) -> anyhow::Result<&'v mut Value> {
+    values
+        .iter_mut()
+        .find(|(ident, _)| ident == field)
+        .map(|(_, value)| value)
+        .ok_or_else(|| anyhow::anyhow!("Struct `{}` has no field `{}`", name, field))
+}
+
+/// The element of a tuple at the index `field`, e.g. `0` for `t.0`
+fn element_mut<'v>(elements: &'v mut [Value], field: &str) -> anyhow::Result<&'v mut Value> {
+    let len = elements.len();
+    field
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| elements.get_mut(index))
+        .ok_or_else(|| anyhow::anyhow!("Tuple of {} element(s) has no field `{}`", len, field))
+}
+
+/// The element of an array or `Vec` at `index`, e.g. `a[2]` in `a[2] = 1`
+fn index_mut(array: &mut Value, index: Value) -> anyhow::Result<&mut Value> {
+    match (array, index) {
+        (Value::Array(elements), Value::Number(index)) => usize::try_from(index.to_i128())
+            .ok()
+            .and_then(|i| elements.get_mut(i))
+            .ok_or_else(|| anyhow::anyhow!("Index out of bounds: {}", index)),
+        (array, index) => Err(anyhow::anyhow!(
+            "Invalid array indexing: {}[{}]",
+            array,
+            index
+        )),
+    }
+}
+
+/// The elements of `array` from `start` up to, but not including, `end`. Slicing a string
+/// gives its UTF-8 bytes as `u8`s, like compiled code.
+fn slice(array: Value, start: Option<Value>, end: Option<Value>) -> anyhow::Result<Value> {
+    let elements = match array {
+        Value::Array(elements) | Value::Slice(elements) => elements,
+        Value::Str(s) => s
+            .bytes()
+            .map(|byte| Value::Number(Integer::from(byte as i32).cast(8, false)))
+            .collect(),
+        array => return Err(anyhow::anyhow!("Cannot slice {}", array)),
+    };
+    let bound = |bound: Option<Value>, default: usize| match bound {
+        None => Ok(default),
+        Some(Value::Number(n)) => {
+            usize::try_from(n.to_i128()).map_err(|_| anyhow::anyhow!("Invalid slice bound: {}", n))
+        }
+        Some(bound) => Err(anyhow::anyhow!("Invalid slice bound: {}", bound)),
+    };
+    let (start, end) = (bound(start, 0)?, bound(end, elements.len())?);
+    if start > end || end > elements.len() {
+        return Err(anyhow::anyhow!(
+            "Slice {}..{} is out of range for length {}",
+            start,
+            end,
+            elements.len()
+        ));
+    }
+    Ok(Value::Slice(elements[start..end].to_vec()))
+}
+
+/// A `println!` or `format!` argument as it is printed
+fn format_value(value: Value) -> anyhow::Result<String> {
+    match value {
+        Value::Number(_) | Value::Str(_) | Value::Char(_) | Value::Bool(_) => Ok(value.to_string()),
+        Value::Float(n) => Ok(format_float(n)),
+        value => Err(anyhow::anyhow!("Cannot format {}", value)),
+    }
+}
+
+/// A float the way `printf("%g")` prints it in compiled programs: six significant digits without
+/// trailing zeros, and an exponent below 1e-4 or from 1e6 on
+fn format_float(n: f64) -> String {
+    if !n.is_finite() {
+        return match (n.is_nan(), n < 0.0) {
+            (true, _) => "nan".to_string(),
+            (false, true) => "-inf".to_string(),
+            (false, false) => "inf".to_string(),
+        };
+    }
+    let trim = |digits: &str| match digits.contains('.') {
+        true => digits
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string(),
+        false => digits.to_string(),
+    };
+    // the exponent after rounding to six significant digits decides the notation
+    let scientific = format!("{:.5e}", n);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    if (-4..6).contains(&exponent) {
+        trim(&format!("{:.*}", (5 - exponent) as usize, n))
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+    }
+}
+
+/// Whether a `break`/`continue` with the `target` label applies to a loop labelled `label`
+fn targets_loop(target: &Option<String>, label: &Option<String>) -> bool {
+    target.is_none() || target == label
+}
+
+/// Applies a binary operator to two evaluated operands
+fn eval_binary_op(op: &BinaryOp, left: Value, right: Value) -> anyhow::Result<Value> {
+    if op.is_comparison() {
+        return compare_values(op, &left, &right).map(Value::Bool);
+    }
+    match (&left, &right) {
+        (Value::Number(l), Value::Number(r)) => {
+            let (l, r) = (l.clone(), r.clone());
+            let result = match op {
+                BinaryOp::Add => l.checked_add(r),
+                BinaryOp::Subtract => l.checked_sub(r),
+                BinaryOp::Multiply => l.checked_mul(r),
+                BinaryOp::Divide => {
+                    if r.is_zero() {
+                        return Err(anyhow::anyhow!("Division by zero"));
+                    }
+                    l.checked_div(r)
+                }
+                _ => return Err(anyhow::anyhow!("Unexpected operator: {}", op)),
+            };
+            result
+                .map(Value::Number)
+                .ok_or_else(|| anyhow::anyhow!("Integer overflow: {} {} {}", left, op, right))
+        }
+        (Value::Float(l), Value::Float(r)) => match op {
+            BinaryOp::Add => Ok(Value::Float(l + r)),
+            BinaryOp::Subtract => Ok(Value::Float(l - r)),
+            BinaryOp::Multiply => Ok(Value::Float(l * r)),
+            BinaryOp::Divide => Ok(Value::Float(l / r)),
+            _ => Err(anyhow::anyhow!("Unexpected operator: {}", op)),
+        },
+        (Value::Str(l), Value::Str(r)) => match op {
+            BinaryOp::Add => Ok(Value::Str(format!("{}{}", l, r).into())),
+            _ => Err(anyhow::anyhow!("Unexpected operator: {}", op)),
+        },
+        _ => Err(anyhow::anyhow!(
+            "Invalid operation: {} {} {}",
+            left,
+            op,
+            right
+        )),
+    }
+}
+
+/// Binds the type parameters of a generic function to the types of the arguments passed for
+/// them. Unsuffixed literals only decide a type parameter no other argument is passed for.
+fn infer_type_args(
+    type_params: &[String],
+    params: &[Param],
+    args: &[Expr],
+    arg_values: &[Value],
+) -> HashMap<String, Type> {
+    let mut bindings = HashMap::new();
+    let (literals, others): (Vec<_>, Vec<_>) = params
+        .iter()
+        .zip(args.iter().zip(arg_values))
+        .partition(|(_, (arg, _))| matches!(arg, Expr::Num(_) | Expr::Float(_)));
+    for (param, (_, value)) in others.into_iter().chain(literals) {
+        let ty = match (&param.ty, value_type(value)) {
+            // arrays and `Vec`s are both `Value::Array`
+            (Some(Type::Vec(_)), Some(Type::Array(element, _))) => Some(Type::Vec(element)),
+            (_, ty) => ty,
+        };
+        if let (Some(param), Some(ty)) = (&param.ty, ty) {
+            bind_type_params(param, &ty, type_params, &mut bindings);
+        }
+    }
+    bindings
+}
+
+/// The type of a value, when it can be told from the value alone
+fn value_type(value: &Value) -> Option<Type> {
+    match value {
+        Value::Number(n) => {
+            let (bits, signed) = n.width();
+            Some(Type::Int { bits, signed })
+        }
+        Value::Str(_) => Some(Type::Str),
+        Value::Char(_) => Some(Type::Char),
+        Value::Bool(_) => Some(Type::Bool),
+        Value::Struct(name, _) | Value::Enum(name, ..) => Some(Type::Named(name.clone())),
+        Value::Array(elements) => {
+            let element = value_type(elements.first()?)?;
+            Some(Type::Array(Box::new(element), elements.len()))
+        }
+        Value::Slice(elements) => {
+            let element = value_type(elements.first()?)?;
+            Some(Type::Slice(Box::new(element)))
+        }
+        // floats don't keep their width, `f32` values are only rounded like one
+        _ => None,
+    }
+}
+
+/// An unsuffixed literal takes the integer type of the value it is combined with, `x + 1` stays a `u8` for a `u8` x
+fn adopt_literal_width(expr: &Expr, value: Value, other: &Value) -> anyhow::Result<Value> {
+    match (expr, &other) {
+        (Expr::Num(_), Value::Number(other)) => {
+            let (bits, signed) = other.width();
+            conform(value, &Type::Int { bits, signed })
+        }
+        _ => Ok(value),
+    }
+}
+
+/// Checks that a number fits the integer type it is bound to and converts it, tuples and arrays element by element, other values pass through
+fn conform(value: Value, ty: &Type) -> anyhow::Result<Value> {
+    match (&value, ty) {
+        (Value::Number(n), Type::Int { bits, signed }) => {
+            Integer::with_width(n.to_i128(), *bits, *signed)
+                .map(Value::Number)
+                .ok_or_else(|| anyhow::anyhow!("{} is out of range for {}", n, ty))
+        }
+        (Value::Float(n), Type::Float { bits }) => Ok(Value::Float(round_float(*n, *bits))),
+        (Value::Tuple(elements), Type::Tuple(types)) if elements.len() == types.len() => elements
+            .iter()
+            .zip(types)
+            .map(|(element, ty)| conform(element.clone(), ty))
+            .collect::<anyhow::Result<_>>()
+            .map(Value::Tuple),
+        (Value::Array(elements), Type::Array(ty, _) | Type::Vec(ty)) => elements
+            .iter()
+            .map(|element| conform(element.clone(), ty))
+            .collect::<anyhow::Result<_>>()
+            .map(Value::Array),
+        (Value::Slice(elements), Type::Slice(ty)) => elements
+            .iter()
+            .map(|element| conform(element.clone(), ty))
+            .collect::<anyhow::Result<_>>()
+            .map(Value::Slice),
+        _ => Ok(value),
+    }
+}
+
+/// Floats are kept as `f64`, an `f32` is rounded to the precision it has in compiled code
+fn round_float(value: f64, bits: u32) -> f64 {
+    match bits {
+        32 => value as f32 as f64,
+        _ => value,
+    }
+}
+
+/// `value as ty`, integers truncate or extend like they do in compiled code, floats saturate when cast to integers
+fn cast_value(value: Value, ty: &Type) -> anyhow::Result<Value> {
+    match (&value, ty) {
+        (Value::Number(n), Type::Int { bits, signed }) => Ok(Value::Number(n.cast(*bits, *signed))),
+        (Value::Bool(b), Type::Int { bits, signed }) => {
+            Ok(Value::Number(Integer::from(*b as i32).cast(*bits, *signed)))
+        }
+        (Value::Char(c), Type::Int { bits, signed }) => {
+            Ok(Value::Number(Integer::from(*c as i32).cast(*bits, *signed)))
+        }
+        (Value::Number(n), Type::Float { bits }) => {
+            Ok(Value::Float(round_float(n.to_i128() as f64, *bits)))
+        }
+        (Value::Float(n), Type::Int { bits, signed }) => {
+            Ok(Value::Number(Integer::from_f64(*n, *bits, *signed)))
+        }
+        (Value::Float(n), Type::Float { bits }) => Ok(Value::Float(round_float(*n, *bits))),
+        (Value::Number(n), Type::Char) if n.width() == (8, false) => {
+            Ok(Value::Char(n.to_i128() as u8 as char))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Cannot cast {} as {}",
+            value.type_name(),
+            ty
+        )),
+    }
+}
+
+/// Evaluates a comparison operator between two values of the same kind
+fn compare_values(op: &BinaryOp, left: &Value, right: &Value) -> anyhow::Result<bool> {
+    let ordering = match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.cmp(r),
+        (Value::Float(l), Value::Float(r)) => match l.partial_cmp(r) {
+            Some(ordering) => ordering,
+            // NaN is unordered, only `!=` holds
+            None => return Ok(*op == BinaryOp::NotEqual),
+        },
+        (Value::Str(l), Value::Str(r)) => l.cmp(r),
+        (Value::Char(l), Value::Char(r)) => l.cmp(r),
+        (Value::Bool(l), Value::Bool(r)) => l.cmp(r),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Cannot compare {} {} {}",
+                left.type_name(),
+                op,
+                right.type_name()
+            ))
+        }
+    };
+    match op {
+        BinaryOp::Equal => Ok(ordering == Ordering::Equal),
+        BinaryOp::NotEqual => Ok(ordering != Ordering::Equal),
+        BinaryOp::LessThan => Ok(ordering == Ordering::Less),
+        BinaryOp::GreaterThan => Ok(ordering == Ordering::Greater),
+        BinaryOp::LessOrEqual => Ok(ordering != Ordering::Greater),
+        BinaryOp::GreaterOrEqual => Ok(ordering != Ordering::Less),
+        _ => Err(anyhow::anyhow!("Not a comparison operator: {}", op)),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(Integer),
+    Float(f64),
     Str(Box<str>),
     Char(char),
-    Function(Vec<String>, Vec<Stmt>),
+    Bool(bool),
+    /// type parameters, parameters, return type and body of a declared function
+    Function(Vec<String>, Vec<Param>, Option<Type>, Vec<Stmt>),
+    /// parameters, return type and body of a closure, with copies of the variables in scope where it was created
+    Closure(Vec<Param>, Option<Type>, Vec<Stmt>, HashMap<String, Value>),
     Return(Box<Value>),
+    Break(Option<String>),
+    Continue(Option<String>),
     Array(Vec<Value>),
+    /// `a[1..3]`, a copy of the elements it views. Slices can't be assigned through, so a copy
+    /// behaves the same as a view.
+    Slice(Vec<Value>),
+    Tuple(Vec<Value>),
+    Struct(String, Vec<(String, Value)>), // name and fields in declaration order
+    Enum(String, String, Vec<Value>),     // enum name, variant and payload
     None,
     // You can add more types here in future.
 }
 
+impl Value {
+    /// `return`, `break` and `continue` stop the enclosing statements from running
+    pub fn interrupts(&self) -> bool {
+        matches!(
+            self,
+            Value::Return(_) | Value::Break(_) | Value::Continue(_)
+        )
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Float(_) => "float",
+            Value::Str(_) => "string",
+            Value::Char(_) => "char",
+            Value::Bool(_) => "bool",
+            Value::Function(..) => "function",
+            Value::Closure(..) => "closure",
+            Value::Return(_) => "return",
+            Value::Break(_) => "break",
+            Value::Continue(_) => "continue",
+            Value::Array(_) => "array",
+            Value::Slice(_) => "slice",
+            Value::Tuple(_) => "tuple",
+            Value::Struct(..) => "struct",
+            Value::Enum(..) => "enum",
+            Value::None => "none",
+        }
+    }
+}
+
+fn param_list(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(|param| param.ident.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn body_lines(body: &[Stmt]) -> String {
+    body.iter()
+        .map(|stmt| {
+            if let Stmt::Expression(expr, _) = stmt {
+                format!("{:?}", expr)
+            } else {
+                format!("{:?}", stmt)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 // implement display for Value
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
+            // `{:?}` keeps the `.0` of whole numbers, so floats don't print like integers
+            Value::Float(n) => write!(f, "{:?}", n),
             Value::Str(s) => write!(f, "{}", s),
             Value::Char(c) => write!(f, "{}", c),
-            Value::Function(params, body) => write!(
-                f,
-                "fn({}) {{\n{}\n}}",
-                params.join(", "),
-                body.iter()
-                    .map(|stmt| {
-                        if let Stmt::Expression(expr) = stmt {
-                            format!("{:?}", expr)
-                        } else {
-                            format!("{:?}", stmt)
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n")
-            ),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Function(_, params, _, body) => {
+                write!(f, "fn({}) {{\n{}\n}}", param_list(params), body_lines(body))
+            }
+            Value::Closure(params, _, body, _) => {
+                write!(f, "|{}| {{\n{}\n}}", param_list(params), body_lines(body))
+            }
             Value::None => Ok(()),
             Value::Return(boxed_value) => write!(f, "{}", boxed_value),
-            Value::Array(values) => {
+            Value::Break(_) | Value::Continue(_) => Ok(()),
+            Value::Array(values) | Value::Slice(values) => {
                 write!(f, "[")?;
                 for (i, value) in values.iter().enumerate() {
                     if i != 0 {
@@ -299,6 +1426,35 @@ impl std::fmt::Display for Value {
                 }
                 write!(f, "]")
             }
+            Value::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            Value::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+            Value::Struct(name, fields) => {
+                write!(f, "{} {{ ", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", field, value)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Enum(name, variant, payload) => {
+                write!(f, "{}::{}", name, variant)?;
+                if !payload.is_empty() {
+                    let payload = payload
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<Vec<_>>();
+                    write!(f, "({})", payload.join(", "))?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1,17 +1,23 @@
-use std::path::PathBuf;
+use std::path::Path;
 
-use crate::{llvm::ast::Ast, parsers::parse_program};
+use crate::parsers::modules::load_program;
 
+mod environment;
+mod full_test;
 pub mod interpreter;
 pub mod repl;
 
-/// Takes a path to a source file and executes it in the interpreter
-pub fn run_script(script: &PathBuf) {
-    let mut interpreter = interpreter::Interpreter::new();
-    let src = std::fs::read_to_string(script).expect("Could not read file");
-    interpreter.eval_source(&src);
-}
-
-pub fn parse(script: &str) -> anyhow::Result<Ast> {
-    Ok(parse_program(&script)?)
+/// Takes a path to a source file and executes it in the interpreter, along with the modules it declares.
+/// `args` are passed to the script as `std::args`, after its path.
+pub fn run_script(script: &Path, args: &[String]) {
+    let args = [vec![script.display().to_string()], args.to_vec()].concat();
+    let mut interpreter = interpreter::Interpreter::with_args(args);
+    match load_program(script) {
+        Ok(program) => interpreter.eval_program(&program.ast),
+        Err(err) => {
+            println!("{}", "=".repeat(80));
+            eprintln!("[Parser::parse] error: {}", err);
+            println!("{}", "=".repeat(80));
+        }
+    }
 }
@@ -0,0 +1,1889 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+
+use crate::llvm::ast::{
+    bind_type_params, globals_in_dependency_order, instance_name, parse_format_string,
+    substitute_types, BinaryOp, Expr, Field, FormatPiece, GlobalKind, MatchArm, Param, Pattern,
+    Span, Stmt, Type, UnaryOp, Variant,
+};
+use crate::parsers::modules::Program;
+use crate::types::exhaustiveness::missing_pattern;
+use crate::types::integer::Integer;
+
+/// A type error and the statement it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl TypeError {
+    /// Formats the error as `line:column: message`
+    pub fn display(&self, source: &str) -> String {
+        let (line, column) = self.span.location(source);
+        format!("{}:{}: {}", line, column, self.message)
+    }
+}
+
+/// Type checks a parsed program before it's handed to the compiler, reporting every error at once
+pub fn check_program(ast: &[Stmt], source: &str) -> anyhow::Result<()> {
+    report(TypeChecker::new().check(ast), |error| error.display(source))
+}
+
+/// Like `check_program` for a program loaded with its modules, errors in a module file start with its path
+pub fn check_modules(program: &Program) -> anyhow::Result<()> {
+    report(TypeChecker::new().check(&program.ast), |error| {
+        format!("{}: {}", program.location(error.span), error.message)
+    })
+}
+
+fn report(errors: Vec<TypeError>, display: impl Fn(&TypeError) -> String) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let report = errors
+        .iter()
+        .map(display)
+        .collect::<Vec<String>>()
+        .join("\n");
+    Err(anyhow!("Found {} type error(s):\n{}", errors.len(), report))
+}
+
+/// Generic functions instantiated past this are assumed to recurse with ever larger types
+const MAX_INSTANCES: usize = 256;
+
+/// C functions compiled code links against without exposing them, a function with one of these
+/// names would take the place of the C one: the ones `Compiler::add_stdlib` declares, `malloc` for
+/// closure environments and the memory routines LLVM lowers large copies to
+const RUNTIME_FUNCTIONS: [&str; 10] = [
+    "snprintf", "strcmp", "strlen", "realloc", "dprintf", "exit", "malloc", "memcpy", "memmove",
+    "memset",
+];
+
+#[derive(Clone)]
+struct Signature {
+    type_params: Vec<String>,
+    params: Vec<Type>,
+    return_type: Type,
+    variadic: bool,
+}
+
+/// A generic function, its body is checked once for every set of type arguments it's called with
+struct GenericFunction {
+    params: Vec<Param>,
+    return_type: Option<Type>,
+    body: Vec<Stmt>,
+    span: Span,
+}
+
+struct Binding {
+    ty: Option<Type>, // `None` when the type can't be inferred yet, e.g. array elements
+    mutable: bool,
+}
+
+pub struct TypeChecker {
+    functions: HashMap<String, Signature>,
+    structs: HashMap<String, Vec<Field>>,
+    enums: HashMap<String, Vec<Variant>>,
+    scopes: Vec<HashMap<String, Binding>>,
+    /// `const` items and their value if it's an integer, the values of other globals can refer to them
+    constants: HashMap<String, Option<Integer>>,
+    /// return type of the function being checked, `None` at the top level
+    return_type: Option<Type>,
+    loop_labels: Vec<Option<String>>,
+    /// number of scopes outside each closure being checked, bindings in them are captured copies
+    closure_scopes: Vec<usize>,
+    generics: HashMap<String, GenericFunction>,
+    /// instances of generic functions that have been called, by name like `max<i32>`
+    instances: HashSet<String>,
+    /// instances still to be checked, with the types their type parameters are bound to
+    pending_instances: Vec<(String, String, HashMap<String, Type>)>,
+    /// type parameters in scope of the signature being declared
+    type_params: Vec<String>,
+    /// instance being checked, errors in its body name it
+    instance: Option<String>,
+    /// statement currently being checked, errors are reported at its location
+    span: Span,
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        let mut functions = HashMap::new();
+        // the stdlib functions `Compiler::add_stdlib` links in
+        functions.insert(
+            "print".to_string(),
+            Signature {
+                type_params: vec![],
+                params: vec![Type::Str],
+                return_type: Type::Unit,
+                variadic: false,
+            },
+        );
+        functions.insert(
+            "printd".to_string(),
+            Signature {
+                type_params: vec![],
+                params: vec![Type::I32],
+                return_type: Type::I32,
+                variadic: false,
+            },
+        );
+        functions.insert(
+            "printf64".to_string(),
+            Signature {
+                type_params: vec![],
+                params: vec![Type::F64],
+                return_type: Type::F64,
+                variadic: false,
+            },
+        );
+        functions.insert(
+            "printf".to_string(),
+            Signature {
+                type_params: vec![],
+                params: vec![Type::Str],
+                return_type: Type::I32,
+                variadic: true,
+            },
+        );
+        TypeChecker {
+            functions,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            constants: HashMap::new(),
+            return_type: None,
+            loop_labels: Vec::new(),
+            closure_scopes: Vec::new(),
+            generics: HashMap::new(),
+            instances: HashSet::new(),
+            pending_instances: Vec::new(),
+            type_params: Vec::new(),
+            instance: None,
+            span: Span::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Checks every statement of the program and returns the errors found
+    pub fn check(mut self, ast: &[Stmt]) -> Vec<TypeError> {
+        // structs, enums and functions can be used before they're declared
+        for stmt in ast {
+            match stmt {
+                Stmt::StructDeclaration {
+                    ident,
+                    fields,
+                    span,
+                    ..
+                } => {
+                    self.span = *span;
+                    if self.is_declared_type(ident) {
+                        self.error(format!("Struct `{}` is defined more than once", ident));
+                    } else {
+                        self.structs.insert(ident.clone(), fields.clone());
+                    }
+                }
+                Stmt::EnumDeclaration {
+                    ident,
+                    variants,
+                    span,
+                    ..
+                } => {
+                    self.span = *span;
+                    if self.is_declared_type(ident) {
+                        self.error(format!("Enum `{}` is defined more than once", ident));
+                    } else {
+                        self.enums.insert(ident.clone(), variants.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        for stmt in ast {
+            if let Stmt::FunctionDeclaration {
+                ident,
+                type_params,
+                params,
+                return_type,
+                body,
+                span,
+                ..
+            } = stmt
+            {
+                self.span = *span;
+                self.declare_function(ident, type_params, params, return_type.as_ref());
+                if !type_params.is_empty() {
+                    let generic = GenericFunction {
+                        params: params.clone(),
+                        return_type: return_type.clone(),
+                        body: body.clone(),
+                        span: *span,
+                    };
+                    self.generics.entry(ident.clone()).or_insert(generic);
+                }
+            }
+        }
+        // `const` and `static` items are in scope in every function, and can refer to the
+        // `const`s declared after them
+        for stmt in globals_in_dependency_order(ast) {
+            if let Stmt::Global {
+                ident,
+                kind,
+                ty,
+                expr,
+                span,
+                ..
+            } = stmt
+            {
+                self.span = *span;
+                self.check_global(ident, *kind, ty, expr);
+            }
+        }
+        for stmt in ast {
+            match stmt {
+                // checked with the other globals above
+                Stmt::Global { .. } => {}
+                Stmt::StructDeclaration { fields, span, .. } => {
+                    self.span = *span;
+                    for (i, field) in fields.iter().enumerate() {
+                        self.expect_known_type(&field.ty);
+                        if fields[..i].iter().any(|other| other.ident == field.ident) {
+                            self.error(format!(
+                                "Field `{}` is declared more than once",
+                                field.ident
+                            ));
+                        }
+                    }
+                }
+                Stmt::EnumDeclaration { variants, span, .. } => {
+                    self.span = *span;
+                    for (i, variant) in variants.iter().enumerate() {
+                        for ty in &variant.fields {
+                            self.expect_known_type(ty);
+                        }
+                        if variants[..i]
+                            .iter()
+                            .any(|other| other.ident == variant.ident)
+                        {
+                            self.error(format!(
+                                "Variant `{}` is declared more than once",
+                                variant.ident
+                            ));
+                        }
+                    }
+                }
+                // generic functions are checked for each instance instead
+                Stmt::FunctionDeclaration { type_params, .. } if !type_params.is_empty() => {}
+                Stmt::FunctionDeclaration {
+                    params,
+                    return_type,
+                    body,
+                    span,
+                    ..
+                } => {
+                    self.span = *span;
+                    self.check_function(params, return_type.as_ref(), body);
+                }
+                _ => {
+                    self.check_stmt(stmt);
+                }
+            }
+        }
+        self.check_instances();
+        self.errors
+    }
+
+    /// Checks the body of every instance of a generic function, with its type parameters
+    /// replaced by the types it was called with. Instances can call further instances.
+    fn check_instances(&mut self) {
+        while let Some((name, instance, bindings)) = self.pending_instances.pop() {
+            let generic = &self.generics[&name];
+            let params = generic
+                .params
+                .iter()
+                .map(|param| param.substitute(&bindings))
+                .collect::<Vec<_>>();
+            let return_type = generic
+                .return_type
+                .as_ref()
+                .map(|ty| ty.substitute(&bindings));
+            let mut body = generic.body.clone();
+            substitute_types(&mut body, &bindings);
+            self.span = generic.span;
+            self.instance = Some(instance);
+            self.check_function(&params, return_type.as_ref(), &body);
+            self.instance = None;
+        }
+    }
+
+    fn declare_function(
+        &mut self,
+        ident: &str,
+        type_params: &[String],
+        params: &[Param],
+        return_type: Option<&Type>,
+    ) {
+        if self.functions.contains_key(ident) {
+            self.error(format!("Function `{}` is defined more than once", ident));
+            return;
+        }
+        if RUNTIME_FUNCTIONS.contains(&ident) {
+            self.error(format!("`{}` is reserved for the runtime", ident));
+            return;
+        }
+        for (i, type_param) in type_params.iter().enumerate() {
+            if type_params[..i].contains(type_param) {
+                self.error(format!(
+                    "Type parameter `{}` is declared more than once",
+                    type_param
+                ));
+            }
+        }
+        self.type_params = type_params.to_vec();
+        for ty in params
+            .iter()
+            .filter_map(|param| param.ty.as_ref())
+            .chain(return_type)
+        {
+            self.expect_known_type(ty);
+        }
+        self.type_params.clear();
+        let signature = Signature {
+            type_params: type_params.to_vec(),
+            params: params
+                .iter()
+                .map(|param| param.ty.clone().unwrap_or(Type::I32))
+                .collect(),
+            return_type: return_type.cloned().unwrap_or(Type::I32),
+            variadic: false,
+        };
+        self.functions.insert(ident.to_string(), signature);
+    }
+
+    /// A `const` or `static` is a number, `bool`, `char` or `str` whose value is known at compile time
+    fn check_global(&mut self, ident: &str, kind: GlobalKind, ty: &Type, expr: &Expr) {
+        self.expect_known_type(ty);
+        let mut value = None;
+        let actual = self.expr_type_expecting(expr, Some(ty));
+        if let Some(value) = &actual {
+            if !accepts(ty, expr, value) {
+                self.error(format!(
+                    "Mismatched types: `{}` is declared as {} but the value is {}",
+                    ident, ty, value
+                ));
+            }
+        }
+        if !matches!(
+            ty,
+            Type::Int { .. } | Type::Float { .. } | Type::Bool | Type::Char | Type::Str
+        ) {
+            self.error(format!(
+                "`{}` can't be {}, a const or static has to be a number, bool, char or str",
+                ident, ty
+            ));
+        } else if !self.is_constant(expr) {
+            self.error(format!(
+                "The value of `{}` has to be known at compile time",
+                ident
+            ));
+        } else if is_int(ty) && actual.is_some_and(|actual| accepts(ty, expr, &actual)) {
+            match self.const_value(expr, Some(ty)) {
+                Ok(computed) => value = computed,
+                Err(reason) => self.error(format!("{} in the value of `{}`", reason, ident)),
+            }
+        }
+        if self.scopes[0].contains_key(ident) {
+            self.error(format!("`{}` is defined more than once", ident));
+        }
+        self.define(ident, Some(ty.clone()), kind == GlobalKind::StaticMut);
+        if kind == GlobalKind::Const {
+            self.constants.insert(ident.to_owned(), value);
+        }
+    }
+
+    /// Computes an integer constant like the interpreter would, so overflow and division by zero
+    /// are reported instead of folding to a wrapped value. An unsuffixed literal takes the type of
+    /// the other operand, or else `ty`. `None` when the value depends on one that isn't known.
+    fn const_value(&self, expr: &Expr, ty: Option<&Type>) -> Result<Option<Integer>, &'static str> {
+        Ok(match expr {
+            Expr::Num(n) => match ty {
+                Some(Type::Int { bits, signed }) => {
+                    Integer::with_width(n.to_i128(), *bits, *signed)
+                }
+                _ => Some(n.clone()),
+            },
+            Expr::Ident(name) => self.constants.get(name).cloned().flatten(),
+            Expr::Cast(operand, Type::Int { bits, signed }) => self
+                .const_value(operand, None)?
+                .map(|value| value.cast(*bits, *signed)),
+            Expr::Unary(UnaryOp::Negate, operand) => match self.const_value(operand, ty)? {
+                Some(value) => Some(value.checked_neg().ok_or("Integer overflow")?),
+                None => None,
+            },
+            Expr::Infix(left, op, right) => {
+                let (left, right) = if matches!(**left, Expr::Num(_)) {
+                    let right = self.const_value(right, ty)?;
+                    let left_type = right.as_ref().map(literal_type);
+                    (self.const_value(left, left_type.as_ref().or(ty))?, right)
+                } else {
+                    let left = self.const_value(left, ty)?;
+                    let right_type = left.as_ref().map(literal_type);
+                    (left, self.const_value(right, right_type.as_ref().or(ty))?)
+                };
+                let (Some(left), Some(right)) = (left, right) else {
+                    return Ok(None);
+                };
+                let result = match op {
+                    BinaryOp::Add => left.checked_add(right),
+                    BinaryOp::Subtract => left.checked_sub(right),
+                    BinaryOp::Multiply => left.checked_mul(right),
+                    BinaryOp::Divide if right.is_zero() => return Err("Division by zero"),
+                    BinaryOp::Divide => left.checked_div(right),
+                    _ => return Ok(None),
+                };
+                Some(result.ok_or("Integer overflow")?)
+            }
+            _ => None,
+        })
+    }
+
+    /// Whether `expr` can be computed at compile time: literals and `const`s combined with
+    /// operators and casts
+    fn is_constant(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Num(_) | Expr::Float(_) | Expr::Bool(_) | Expr::Char(_) | Expr::Str(_) => true,
+            Expr::Ident(name) => self.constants.contains_key(name),
+            Expr::Unary(_, operand) | Expr::Cast(operand, _) => self.is_constant(operand),
+            Expr::Infix(left, _, right) => self.is_constant(left) && self.is_constant(right),
+            _ => false,
+        }
+    }
+
+    fn check_function(&mut self, params: &[Param], return_type: Option<&Type>, body: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for param in params {
+            let ty = param.ty.clone().unwrap_or(Type::I32);
+            self.define(&param.ident, Some(ty), false);
+        }
+        self.return_type = Some(return_type.cloned().unwrap_or(Type::I32));
+        self.check_body(body);
+        self.return_type = None;
+        self.scopes.pop();
+    }
+
+    /// Checks the statements of a function or closure body, a trailing closure takes its
+    /// types from the return type. Yields the type of the trailing statement.
+    fn check_body(&mut self, body: &[Stmt]) -> Option<Type> {
+        let Some((tail, stmts)) = body.split_last() else {
+            return Some(Type::Unit);
+        };
+        for stmt in stmts {
+            self.check_stmt(stmt);
+        }
+        match tail {
+            Stmt::Expression(expr, span) => {
+                self.span = *span;
+                let return_type = self.return_type.clone();
+                self.expr_type_expecting(expr, return_type.as_ref())
+            }
+            tail => self.check_stmt(tail),
+        }
+    }
+
+    /// Checks a statement, yielding the type it evaluates to.
+    /// Statements that leave the block (`return`, `break`, `continue`) have no type.
+    fn check_stmt(&mut self, stmt: &Stmt) -> Option<Type> {
+        match stmt {
+            Stmt::FunctionDeclaration { ident, span, .. } => {
+                self.span = *span;
+                self.error(format!(
+                    "Nested function declarations are not supported: `{}`",
+                    ident
+                ));
+            }
+            Stmt::StructDeclaration { ident, span, .. } => {
+                self.span = *span;
+                self.error(format!(
+                    "Nested struct declarations are not supported: `{}`",
+                    ident
+                ));
+            }
+            Stmt::EnumDeclaration { ident, span, .. } => {
+                self.span = *span;
+                self.error(format!(
+                    "Nested enum declarations are not supported: `{}`",
+                    ident
+                ));
+            }
+            // modules are flattened by `parsers::modules` before type checking
+            Stmt::Module { span, .. } | Stmt::Use { span, .. } | Stmt::Impl { span, .. } => {
+                self.span = *span;
+                self.error(
+                    "`mod`, `use` and `impl` have to be resolved before type checking".to_string(),
+                );
+            }
+            Stmt::Assignment {
+                ident,
+                mutable,
+                ty,
+                expr,
+                span,
+            } => {
+                self.span = *span;
+                let value = self.expr_type_expecting(expr, ty.as_ref());
+                if let Some(ty) = ty {
+                    self.expect_known_type(ty);
+                }
+                if let (Some(declared), Some(value)) = (ty, &value) {
+                    if !accepts(declared, expr, value) {
+                        self.error(format!(
+                            "Mismatched types: `{}` is declared as {} but the value is {}",
+                            ident, declared, value
+                        ));
+                    }
+                }
+                self.define(ident, ty.clone().or(value), *mutable);
+            }
+            Stmt::Global { ident, span, .. } => {
+                self.span = *span;
+                self.error(format!(
+                    "`{}` has to be declared at the top level, not inside a function",
+                    ident
+                ));
+            }
+            Stmt::Destructure {
+                pattern,
+                ty,
+                expr,
+                span,
+            } => {
+                self.span = *span;
+                let value = self.expr_type_expecting(expr, ty.as_ref());
+                if let Some(ty) = ty {
+                    self.expect_known_type(ty);
+                }
+                if let (Some(declared), Some(value)) = (ty, &value) {
+                    if !accepts(declared, expr, value) {
+                        self.error(format!(
+                            "Mismatched types: the pattern is declared as {} but the value is {}",
+                            declared, value
+                        ));
+                    }
+                }
+                let ty = ty.clone().or(value);
+                let errors = self.errors.len();
+                let mut bindings = Vec::new();
+                self.check_pattern(pattern, ty.as_ref(), &mut bindings);
+                if let (true, Some(ty)) = (self.errors.len() == errors, &ty) {
+                    if let Some(missing) = missing_pattern(&[pattern], ty, &self.enums) {
+                        self.error(format!(
+                            "Refutable pattern in `let`: `{}` not covered",
+                            missing
+                        ));
+                    }
+                }
+                for (ident, ty) in bindings {
+                    self.define(&ident, ty, false);
+                }
+            }
+            Stmt::Reassignment {
+                ident,
+                op,
+                expr,
+                span,
+            } => {
+                self.span = *span;
+                let (declared, mutable) = match self.lookup(ident) {
+                    Some(binding) => (binding.ty.clone(), binding.mutable),
+                    None => {
+                        self.expr_type(expr);
+                        self.error(format!("Undefined variable: `{}`", ident));
+                        return Some(Type::Unit);
+                    }
+                };
+                let value = self.expr_type_expecting(expr, declared.as_ref());
+                if !mutable {
+                    self.error(format!(
+                        "Cannot assign twice to immutable variable `{}`",
+                        ident
+                    ));
+                } else if self.is_captured(ident) {
+                    self.error(format!(
+                        "Cannot assign to `{}`, closures capture variables by value",
+                        ident
+                    ));
+                }
+                match (op, declared, value) {
+                    (Some(op), Some(declared), Some(value))
+                        if !is_number(&declared) || !accepts(&declared, expr, &value) =>
+                    {
+                        self.error(format!(
+                            "Cannot apply `{}=` to {} and {}",
+                            op, declared, value
+                        ));
+                    }
+                    (None, Some(declared), Some(value)) if !accepts(&declared, expr, &value) => {
+                        self.error(format!(
+                            "Mismatched types: `{}` is {} but the value is {}",
+                            ident, declared, value
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            Stmt::FieldAssignment {
+                ident,
+                fields,
+                op,
+                expr,
+                span,
+            } => {
+                self.span = *span;
+                let value = self.expr_type(expr);
+                let target = format!("{}.{}", ident, fields.join("."));
+                let (mut declared, mutable) = match self.lookup(ident) {
+                    Some(binding) => (binding.ty.clone(), binding.mutable),
+                    None => {
+                        self.error(format!("Undefined variable: `{}`", ident));
+                        return Some(Type::Unit);
+                    }
+                };
+                if !mutable {
+                    self.error(format!(
+                        "Cannot assign to `{}`, `{}` is not declared as mutable",
+                        target, ident
+                    ));
+                } else if self.is_captured(ident) {
+                    self.error(format!(
+                        "Cannot assign to `{}`, closures capture variables by value",
+                        target
+                    ));
+                }
+                for field in fields {
+                    declared = declared.and_then(|ty| self.field_type(&ty, field));
+                }
+                match (op, declared, value) {
+                    (Some(op), Some(declared), Some(value))
+                        if !is_number(&declared) || !accepts(&declared, expr, &value) =>
+                    {
+                        self.error(format!(
+                            "Cannot apply `{}=` to {} and {}",
+                            op, declared, value
+                        ));
+                    }
+                    (None, Some(declared), Some(value)) if !accepts(&declared, expr, &value) => {
+                        self.error(format!(
+                            "Mismatched types: `{}` is {} but the value is {}",
+                            target, declared, value
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            Stmt::IndexAssignment {
+                ident,
+                indices,
+                op,
+                expr,
+                span,
+            } => {
+                self.span = *span;
+                let (mut declared, mutable) = match self.lookup(ident) {
+                    Some(binding) => (binding.ty.clone(), binding.mutable),
+                    None => {
+                        self.expr_type(expr);
+                        self.error(format!("Undefined variable: `{}`", ident));
+                        return Some(Type::Unit);
+                    }
+                };
+                if !mutable {
+                    self.error(format!(
+                        "Cannot assign to an element of `{}`, `{}` is not declared as mutable",
+                        ident, ident
+                    ));
+                } else if self.is_captured(ident) {
+                    self.error(format!(
+                        "Cannot assign to an element of `{}`, closures capture variables by value",
+                        ident
+                    ));
+                }
+                for index in indices {
+                    declared = self.index_type(declared, index);
+                }
+                let value = self.expr_type_expecting(expr, declared.as_ref());
+                match (op, declared, value) {
+                    (Some(op), Some(declared), Some(value))
+                        if !is_number(&declared) || !accepts(&declared, expr, &value) =>
+                    {
+                        self.error(format!(
+                            "Cannot apply `{}=` to {} and {}",
+                            op, declared, value
+                        ));
+                    }
+                    (None, Some(declared), Some(value)) if !accepts(&declared, expr, &value) => {
+                        self.error(format!(
+                            "Mismatched types: the elements of `{}` are {} but the value is {}",
+                            ident, declared, value
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            Stmt::Expression(expr, span) => {
+                self.span = *span;
+                return self.expr_type(expr);
+            }
+            Stmt::Return(expr, span) => {
+                self.span = *span;
+                let return_type = self.return_type.clone();
+                let value = self.expr_type_expecting(expr, return_type.as_ref());
+                if let (Some(expected), Some(value)) = (self.return_type.clone(), value) {
+                    if !accepts(&expected, expr, &value) {
+                        self.error(format!(
+                            "Mismatched types: expected a return value of {}, got {}",
+                            expected, value
+                        ));
+                    }
+                }
+                return None;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                span,
+            } => {
+                self.span = *span;
+                self.expect_condition(condition);
+                self.check_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch);
+                }
+            }
+            Stmt::While {
+                label,
+                condition,
+                body,
+                span,
+            } => {
+                self.span = *span;
+                self.expect_condition(condition);
+                self.loop_labels.push(label.clone());
+                self.check_block(body);
+                self.loop_labels.pop();
+            }
+            Stmt::For {
+                label,
+                ident,
+                start,
+                end,
+                body,
+                span,
+            } => {
+                self.span = *span;
+                let mut bound_types = vec![];
+                for bound in [start, end] {
+                    match self.expr_type(bound) {
+                        Some(ty) if !is_int(&ty) => {
+                            self.error(format!("Range bounds must be integers, got {}", ty))
+                        }
+                        ty => bound_types.push(ty),
+                    }
+                }
+                // the loop variable has the type of the bounds, an unsuffixed literal takes the other's
+                let ty = match &bound_types[..] {
+                    [Some(start_type), Some(end_type)] => {
+                        if accepts(end_type, start, start_type) {
+                            Some(end_type.clone())
+                        } else if accepts(start_type, end, end_type) {
+                            Some(start_type.clone())
+                        } else {
+                            self.error(format!(
+                                "Range bounds have different types: {} and {}, convert one with `as`",
+                                start_type, end_type
+                            ));
+                            None
+                        }
+                    }
+                    _ => None,
+                };
+                self.loop_labels.push(label.clone());
+                self.scopes.push(HashMap::new());
+                self.define(ident, ty, false);
+                self.check_block(body);
+                self.scopes.pop();
+                self.loop_labels.pop();
+            }
+            Stmt::Break(label, span) | Stmt::Continue(label, span) => {
+                self.span = *span;
+                let keyword = match stmt {
+                    Stmt::Break(..) => "break",
+                    _ => "continue",
+                };
+                match label {
+                    _ if self.loop_labels.is_empty() => {
+                        self.error(format!("`{}` outside of a loop", keyword))
+                    }
+                    Some(label) if !self.loop_labels.contains(&Some(label.clone())) => {
+                        self.error(format!("Undeclared loop label: '{}", label))
+                    }
+                    _ => {}
+                }
+                return None;
+            }
+        }
+        Some(Type::Unit)
+    }
+
+    /// Checks a nested block in its own scope, yielding the type of its trailing statement
+    fn check_block(&mut self, stmts: &[Stmt]) -> Option<Type> {
+        let outer_span = self.span;
+        self.scopes.push(HashMap::new());
+        let mut ty = Some(Type::Unit);
+        for stmt in stmts {
+            ty = self.check_stmt(stmt);
+        }
+        self.scopes.pop();
+        self.span = outer_span;
+        ty
+    }
+
+    fn expect_condition(&mut self, condition: &Expr) {
+        match self.expr_type(condition) {
+            Some(Type::Bool) | None => {}
+            Some(ty) => self.error(format!("Expected a bool condition, got {}", ty)),
+        }
+    }
+
+    /// Infers the type of an expression, `None` when it can't be known
+    fn expr_type(&mut self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Num(n) => Some(literal_type(n)),
+            Expr::Float(_) => Some(Type::F64),
+            Expr::Str(_) => Some(Type::Str),
+            Expr::Char(_) => Some(Type::Char),
+            Expr::Bool(_) => Some(Type::Bool),
+            Expr::Ident(ident) => match (self.lookup(ident), self.functions.get(ident)) {
+                (Some(binding), _) => binding.ty.clone(),
+                (None, Some(signature)) if !signature.type_params.is_empty() => {
+                    self.error(format!(
+                        "Generic function `{}` can't be used as a value",
+                        ident
+                    ));
+                    None
+                }
+                (None, Some(signature)) if !signature.variadic => Some(Type::Function(
+                    signature.params.clone(),
+                    Box::new(signature.return_type.clone()),
+                )),
+                (None, Some(_)) => {
+                    self.error(format!(
+                        "Variadic function `{}` can't be used as a value",
+                        ident
+                    ));
+                    None
+                }
+                (None, None) => {
+                    self.error(format!("Undefined variable: `{}`", ident));
+                    None
+                }
+            },
+            Expr::QualifiedIdent(idents) => {
+                let path = idents.join("::");
+                match path.as_str() {
+                    "std::args" => Some(Type::Ptr(Box::new(Type::Ptr(Box::new(Type::Int {
+                        bits: 8,
+                        signed: true,
+                    }))))),
+                    "std::printf" => Some(Type::I32),
+                    _ => {
+                        self.error(format!("Unknown path: `{}`", path));
+                        None
+                    }
+                }
+            }
+            Expr::Call(name, args) => self.call_type(name, args),
+            Expr::MacroCall(name, args) => self.macro_type(name, args),
+            Expr::Infix(left_expr, op, right_expr) => {
+                let left = self.expr_type(left_expr);
+                let right = self.expr_type(right_expr);
+                let (left, right) = match (left, right) {
+                    (Some(left), Some(right)) => (left, right),
+                    // the result of a comparison is known even when an operand isn't
+                    _ if op.is_comparison() || op.is_logical() => return Some(Type::Bool),
+                    _ => return None,
+                };
+                if op.is_logical() {
+                    if left != Type::Bool || right != Type::Bool {
+                        self.error(format!(
+                            "Cannot apply `{}` to {} and {}, expected bool operands",
+                            op, left, right
+                        ));
+                    }
+                    Some(Type::Bool)
+                } else if is_number(&left) && is_number(&right) {
+                    // an unsuffixed literal takes the type of the other operand
+                    let operand_type = if accepts(&right, left_expr, &left) {
+                        Some(right.clone())
+                    } else if accepts(&left, right_expr, &right) {
+                        Some(left.clone())
+                    } else {
+                        self.error(format!(
+                            "Mismatched types: {} {} {}, convert one side with `as`",
+                            left, op, right
+                        ));
+                        None
+                    };
+                    if op.is_comparison() {
+                        Some(Type::Bool)
+                    } else {
+                        operand_type
+                    }
+                } else if op.is_comparison() {
+                    if matches!(
+                        left,
+                        Type::Str
+                            | Type::Ptr(_)
+                            | Type::Unit
+                            | Type::Named(_)
+                            | Type::Function(..)
+                            | Type::Array(..)
+                            | Type::Vec(_)
+                            | Type::Slice(_)
+                    ) || left != right
+                    {
+                        self.error(format!("Cannot compare {} {} {}", left, op, right));
+                    }
+                    Some(Type::Bool)
+                } else {
+                    self.error(format!("Cannot apply `{}` to {} and {}", op, left, right));
+                    None
+                }
+            }
+            Expr::Unary(op, operand) => {
+                let operand = self.expr_type(operand);
+                match (op, operand) {
+                    (UnaryOp::Not, Some(Type::Bool)) | (UnaryOp::Not, None) => Some(Type::Bool),
+                    (
+                        UnaryOp::Negate,
+                        Some(ty @ (Type::Int { signed: true, .. } | Type::Float { .. })),
+                    ) => Some(ty),
+                    (UnaryOp::Negate, None) => None,
+                    (op, Some(ty)) => {
+                        self.error(format!("Cannot apply `{}` to {}", op, ty));
+                        None
+                    }
+                }
+            }
+            Expr::Cast(operand, ty) => {
+                let Some(operand) = self.expr_type(operand) else {
+                    return Some(ty.clone());
+                };
+                let valid = match (&operand, ty) {
+                    (Type::Int { .. } | Type::Bool | Type::Char, Type::Int { .. }) => true,
+                    (Type::Int { .. } | Type::Float { .. }, Type::Float { .. }) => true,
+                    (Type::Float { .. }, Type::Int { .. }) => true,
+                    (
+                        Type::Int {
+                            bits: 8,
+                            signed: false,
+                        },
+                        Type::Char,
+                    ) => true,
+                    (operand, ty) => operand == ty,
+                };
+                if !valid {
+                    self.error(format!("Cannot cast {} as {}", operand, ty));
+                }
+                Some(ty.clone())
+            }
+            Expr::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expect_condition(condition);
+                let then_type = self.check_block(then_branch);
+                let else_type = self.check_block(else_branch);
+                match (then_type, else_type) {
+                    (Some(then_type), Some(else_type)) => {
+                        if then_type != else_type {
+                            self.error(format!(
+                                "`if` and `else` have different types: {} and {}",
+                                then_type, else_type
+                            ));
+                        }
+                        Some(then_type)
+                    }
+                    // a branch that returns doesn't contribute a value
+                    (then_type, else_type) => then_type.or(else_type),
+                }
+            }
+            Expr::Array(elements) => self.array_literal_type(elements, None),
+            Expr::StructLiteral(name, fields) => {
+                let declared = self.structs.get(name).cloned();
+                if declared.is_none() {
+                    self.error(format!("Undefined struct: `{}`", name));
+                }
+                for (i, (field, expr)) in fields.iter().enumerate() {
+                    let value = self.expr_type(expr);
+                    if fields[..i].iter().any(|(other, _)| other == field) {
+                        self.error(format!("Field `{}` is specified more than once", field));
+                    }
+                    let Some(declared) = &declared else {
+                        continue;
+                    };
+                    match declared.iter().find(|declared| declared.ident == *field) {
+                        None => self.error(format!("Struct `{}` has no field `{}`", name, field)),
+                        Some(declared) => match value {
+                            Some(value) if !accepts(&declared.ty, expr, &value) => {
+                                self.error(format!(
+                                "Mismatched types: field `{}` of `{}` is {} but the value is {}",
+                                field, name, declared.ty, value
+                            ))
+                            }
+                            _ => {}
+                        },
+                    }
+                }
+                for missing in declared
+                    .iter()
+                    .flatten()
+                    .filter(|declared| !fields.iter().any(|(field, _)| *field == declared.ident))
+                {
+                    self.error(format!(
+                        "Missing field `{}` in `{}` literal",
+                        missing.ident, name
+                    ));
+                }
+                Some(Type::Named(name.clone()))
+            }
+            Expr::FieldAccess(operand, field) => {
+                let operand = self.expr_type(operand)?;
+                self.field_type(&operand, field)
+            }
+            Expr::EnumVariant { .. } if expr.is_vec_new() => {
+                self.error(
+                    "Cannot infer the element type of `Vec::new()`, annotate it as `Vec<T>`"
+                        .to_string(),
+                );
+                None
+            }
+            Expr::EnumVariant {
+                enum_name,
+                variant,
+                args,
+            } => self.variant_type(enum_name, variant, args),
+            Expr::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.method_type(receiver, method, args),
+            Expr::Match { scrutinee, arms } => self.match_type(scrutinee, arms),
+            Expr::Closure { .. } => self.expr_type_expecting(expr, None),
+            Expr::Tuple(elements) => {
+                let types = elements
+                    .iter()
+                    .map(|element| self.expr_type(element))
+                    .collect::<Vec<_>>();
+                types.into_iter().collect::<Option<_>>().map(Type::Tuple)
+            }
+            Expr::ArrayIndexing(array, index) => {
+                let array = self.expr_type(array);
+                self.index_type(array, index)
+            }
+            Expr::Slice(array, start, end) => {
+                let array = self.expr_type(array);
+                self.slice_type(array, start.as_deref(), end.as_deref())
+            }
+        }
+    }
+
+    /// Like `expr_type`, but a closure without annotations takes its parameter and return types
+    /// from the function type `expected` where it's used, and an array literal or `Vec::new()`
+    /// takes its element type from it
+    fn expr_type_expecting(&mut self, expr: &Expr, expected: Option<&Type>) -> Option<Type> {
+        let (params, return_type, body) = match (expr, expected) {
+            (
+                Expr::Closure {
+                    params,
+                    return_type,
+                    body,
+                },
+                _,
+            ) => (params, return_type, body),
+            (Expr::Array(elements), Some(Type::Array(element, _))) => {
+                return self.array_literal_type(elements, Some(element))
+            }
+            (Expr::EnumVariant { .. }, Some(ty @ Type::Vec(_))) if expr.is_vec_new() => {
+                return Some(ty.clone())
+            }
+            _ => return self.expr_type(expr),
+        };
+        let (expected_params, expected_return) = match expected {
+            Some(Type::Function(expected_params, expected_return))
+                if expected_params.len() == params.len() =>
+            {
+                (Some(expected_params), Some(&**expected_return))
+            }
+            _ => (None, None),
+        };
+        let param_types = params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| match (&param.ty, expected_params) {
+                (Some(ty), _) => ty.clone(),
+                (None, Some(expected_params)) => expected_params[i].clone(),
+                (None, None) => Type::I32,
+            })
+            .collect::<Vec<_>>();
+        for ty in params
+            .iter()
+            .filter_map(|param| param.ty.as_ref())
+            .chain(return_type)
+        {
+            self.expect_known_type(ty);
+        }
+        let return_type = return_type
+            .clone()
+            .or(expected_return.cloned())
+            .unwrap_or(Type::I32);
+
+        let outer_span = self.span;
+        let outer_return_type = self.return_type.replace(return_type.clone());
+        let outer_loop_labels = std::mem::take(&mut self.loop_labels);
+        self.closure_scopes.push(self.scopes.len());
+        self.scopes.push(HashMap::new());
+        for (param, ty) in params.iter().zip(&param_types) {
+            self.define(&param.ident, Some(ty.clone()), false);
+        }
+        let body_type = self.check_body(body);
+        match (body.last(), body_type) {
+            (Some(Stmt::Expression(tail, _)), Some(body_type))
+                if body_type != Type::Unit && !accepts(&return_type, tail, &body_type) =>
+            {
+                self.error(format!(
+                    "Mismatched types: the closure returns {} but its body is {}",
+                    return_type, body_type
+                ));
+            }
+            _ => {}
+        }
+        self.scopes.pop();
+        self.closure_scopes.pop();
+        self.loop_labels = outer_loop_labels;
+        self.return_type = outer_return_type;
+        self.span = outer_span;
+        Some(Type::Function(param_types, Box::new(return_type)))
+    }
+
+    /// Type of an array literal. Its elements have the element type `expected`, or else the type of
+    /// the first element that isn't an unsuffixed literal, so `[1, x]` holds `u8`s when `x` is a `u8`.
+    fn array_literal_type(&mut self, elements: &[Expr], expected: Option<&Type>) -> Option<Type> {
+        let types = elements
+            .iter()
+            .map(|element| self.expr_type_expecting(element, expected))
+            .collect::<Vec<_>>();
+        let element_type = expected.cloned().or_else(|| {
+            let mut known = elements.iter().zip(&types).filter_map(|(element, ty)| {
+                ty.as_ref()
+                    .map(|ty| (matches!(element, Expr::Num(_) | Expr::Float(_)), ty))
+            });
+            let first = known.clone().next();
+            known
+                .find(|(literal, _)| !literal)
+                .or(first)
+                .map(|(_, ty)| ty.clone())
+        });
+        let Some(element_type) = element_type else {
+            if elements.is_empty() {
+                self.error("Cannot infer the element type of an empty array".to_string());
+            }
+            return None;
+        };
+        for (i, (element, ty)) in elements.iter().zip(types).enumerate() {
+            match ty {
+                Some(ty) if !accepts(&element_type, element, &ty) => self.error(format!(
+                    "Mismatched types: element {} of the array is {}, expected {}",
+                    i + 1,
+                    ty,
+                    element_type
+                )),
+                _ => {}
+            }
+        }
+        Some(Type::Array(Box::new(element_type), elements.len()))
+    }
+
+    /// Type of an element of a value of type `array`, checking that it can be indexed with `index`
+    fn index_type(&mut self, array: Option<Type>, index: &Expr) -> Option<Type> {
+        match self.expr_type(index) {
+            Some(ty) if !is_int(&ty) => {
+                self.error(format!("Array index must be an integer, got {}", ty))
+            }
+            _ => {}
+        }
+        match array? {
+            Type::Array(element, _) | Type::Vec(element) | Type::Slice(element) => Some(*element),
+            // `std::args[1]`
+            Type::Ptr(_) => None,
+            ty => {
+                self.error(format!("Cannot index into a value of type {}", ty));
+                None
+            }
+        }
+    }
+
+    /// Type of `array[start..end]`, a slice of the elements of an array, `Vec` or slice, or of the
+    /// UTF-8 bytes of a string. Literal bounds are checked against each other and the length of an array.
+    fn slice_type(
+        &mut self,
+        array: Option<Type>,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> Option<Type> {
+        for bound in start.into_iter().chain(end) {
+            match self.expr_type(bound) {
+                Some(ty) if !is_int(&ty) => {
+                    self.error(format!("Slice bounds must be integers, got {}", ty))
+                }
+                _ => {}
+            }
+        }
+        let (element, len) = match array? {
+            Type::Array(element, len) => (*element, Some(len as i128)),
+            Type::Vec(element) | Type::Slice(element) => (*element, None),
+            Type::Str => (
+                Type::Int {
+                    bits: 8,
+                    signed: false,
+                },
+                None,
+            ),
+            ty => {
+                self.error(format!("Cannot slice a value of type {}", ty));
+                return None;
+            }
+        };
+        let literal = |bound: Option<&Expr>| match bound {
+            Some(Expr::Num(n)) => Some(n.to_i128()),
+            _ => None,
+        };
+        match (literal(start), literal(end).or(len)) {
+            (Some(start), Some(end)) if start > end => {
+                self.error(format!("Slice starts at {} but ends at {}", start, end))
+            }
+            _ => {}
+        }
+        match (literal(end), len) {
+            (Some(end), Some(len)) if end > len => self.error(format!(
+                "Slice end {} is out of range for an array of length {}",
+                end, len
+            )),
+            _ => {}
+        }
+        Some(Type::Slice(Box::new(element)))
+    }
+
+    /// Checks a call of a method of an `impl` block or of a builtin type, yielding its return type.
+    /// `push` and `pop` modify the `Vec` they're called on, which has to be a mutable variable.
+    fn method_type(&mut self, receiver: &Expr, method: &str, args: &[Expr]) -> Option<Type> {
+        let (params, return_type, modifies) = match (self.expr_type(receiver), method) {
+            (Some(Type::Named(name)), _)
+                if self
+                    .functions
+                    .contains_key(&format!("{}::{}", name, method)) =>
+            {
+                let function = format!("{}::{}", name, method);
+                let signature = &self.functions[&function];
+                match signature.params.split_first() {
+                    Some((Type::Named(receiver), params)) if *receiver == name => {
+                        (params.to_vec(), signature.return_type.clone(), false)
+                    }
+                    _ => {
+                        let return_type = signature.return_type.clone();
+                        for arg in args {
+                            self.expr_type(arg);
+                        }
+                        self.error(format!(
+                            "`{}` doesn't take `self`, call it as `{}(...)`",
+                            function, function
+                        ));
+                        return Some(return_type);
+                    }
+                }
+            }
+            (Some(Type::Vec(element)), "push") => (vec![*element], Type::Unit, true),
+            (Some(Type::Vec(element)), "pop") => (vec![], *element, true),
+            (Some(Type::Vec(_) | Type::Array(..) | Type::Slice(_)), "len") => (
+                vec![],
+                Type::Int {
+                    bits: 64,
+                    signed: false,
+                },
+                false,
+            ),
+            (receiver, _) => {
+                for arg in args {
+                    self.expr_type(arg);
+                }
+                if let Some(receiver) = receiver {
+                    self.error(format!("No method `{}` on {}", method, receiver));
+                }
+                return None;
+            }
+        };
+        if modifies {
+            match receiver {
+                Expr::Ident(ident) => match self.lookup(ident) {
+                    Some(binding) if !binding.mutable => self.error(format!(
+                        "Cannot call `{}` on `{}`, it is not declared as mutable",
+                        method, ident
+                    )),
+                    Some(_) if self.is_captured(ident) => self.error(format!(
+                        "Cannot call `{}` on `{}`, closures capture variables by value",
+                        method, ident
+                    )),
+                    _ => {}
+                },
+                _ => self.error(format!("`{}` can only be called on a variable", method)),
+            }
+        }
+        if args.len() != params.len() {
+            self.error(format!(
+                "`{}` takes {} argument(s) but {} were given",
+                method,
+                params.len(),
+                args.len()
+            ));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let ty = self.expr_type_expecting(arg, params.get(i));
+            match (params.get(i), ty) {
+                (Some(param), Some(ty)) if !accepts(param, arg, &ty) => self.error(format!(
+                    "Argument {} of `{}` expects {}, got {}",
+                    i + 1,
+                    method,
+                    param,
+                    ty
+                )),
+                _ => {}
+            }
+        }
+        Some(return_type)
+    }
+
+    /// `println!` yields `()` and `format!` a `str`. The format string has to be a literal with
+    /// a `{}` for each argument, and every argument a number, `bool`, `char` or `str`.
+    fn macro_type(&mut self, name: &str, args: &[Expr]) -> Option<Type> {
+        let return_type = match name {
+            "println" => Type::Unit,
+            "format" => Type::Str,
+            _ => {
+                for arg in args {
+                    self.expr_type(arg);
+                }
+                self.error(format!("Unknown macro `{}!`", name));
+                return None;
+            }
+        };
+        let args = match args.split_first() {
+            Some((Expr::Str(format), args)) => {
+                match parse_format_string(format) {
+                    Ok(pieces) => {
+                        let placeholders = pieces
+                            .iter()
+                            .filter(|piece| **piece == FormatPiece::Placeholder)
+                            .count();
+                        if placeholders != args.len() {
+                            self.error(format!(
+                                "`{}!` has {} placeholder(s) but {} argument(s) were given",
+                                name,
+                                placeholders,
+                                args.len()
+                            ));
+                        }
+                    }
+                    Err(err) => {
+                        self.error(format!("Invalid format string in `{}!`: {}", name, err))
+                    }
+                }
+                args
+            }
+            _ => {
+                self.error(format!(
+                    "`{}!` takes a string literal as its first argument",
+                    name
+                ));
+                args
+            }
+        };
+        for (i, arg) in args.iter().enumerate() {
+            match self.expr_type(arg) {
+                // `printf` has no conversion for 128 bit integers
+                Some(Type::Int { bits, .. }) if bits <= 64 => {}
+                Some(Type::Float { .. } | Type::Bool | Type::Char | Type::Str) | None => {}
+                Some(ty) => self.error(format!(
+                    "Argument {} of `{}!` is {}, which can't be formatted",
+                    i + 1,
+                    name,
+                    ty
+                )),
+            }
+        }
+        Some(return_type)
+    }
+
+    /// Checks the arity and argument types of a call, yielding the return type.
+    /// A variable holding a function shadows a declared function of the same name.
+    fn call_type(&mut self, name: &str, args: &[Expr]) -> Option<Type> {
+        let signature = match self.lookup(name).map(|binding| binding.ty.clone()) {
+            Some(Some(Type::Function(params, return_type))) => Signature {
+                type_params: vec![],
+                params,
+                return_type: *return_type,
+                variadic: false,
+            },
+            Some(ty) => {
+                for arg in args {
+                    self.expr_type(arg);
+                }
+                if let Some(ty) = ty {
+                    self.error(format!("Cannot call `{}` of type {}", name, ty));
+                }
+                return None;
+            }
+            None => match self.functions.get(name) {
+                Some(signature) => signature.clone(),
+                None => {
+                    for arg in args {
+                        self.expr_type(arg);
+                    }
+                    self.error(format!("Undefined function: `{}`", name));
+                    return None;
+                }
+            },
+        };
+        let (signature, arg_types) = if signature.type_params.is_empty() {
+            let arg_types: Vec<Option<Type>> = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| self.expr_type_expecting(arg, signature.params.get(i)))
+                .collect();
+            (signature, arg_types)
+        } else {
+            self.instantiate(name, signature, args)?
+        };
+        let arity_matches = if signature.variadic {
+            args.len() >= signature.params.len()
+        } else {
+            args.len() == signature.params.len()
+        };
+        let mut errors = Vec::new();
+        if !arity_matches {
+            errors.push(format!(
+                "`{}` takes {}{} argument(s) but {} were given",
+                name,
+                if signature.variadic { "at least " } else { "" },
+                signature.params.len(),
+                args.len()
+            ));
+        }
+        for (i, ((param, arg), arg_expr)) in signature
+            .params
+            .iter()
+            .zip(&arg_types)
+            .zip(args)
+            .enumerate()
+        {
+            if let Some(arg) = arg {
+                // booleans and chars widen to integer parameters, e.g. `printd(a < b)`
+                let widens = is_int(param) && matches!(arg, Type::Bool | Type::Char);
+                if !accepts(param, arg_expr, arg) && !widens {
+                    errors.push(format!(
+                        "Argument {} of `{}` expects {}, got {}",
+                        i + 1,
+                        name,
+                        param,
+                        arg
+                    ));
+                }
+            }
+        }
+        for error in errors {
+            self.error(error);
+        }
+        Some(signature.return_type)
+    }
+
+    /// Infers the type arguments of a call to a generic function from its arguments, yielding
+    /// the signature of the instance called and the argument types. Unsuffixed literals and
+    /// closures only bind what the other arguments leave open, so `max(x, 1)` is `max<u8>`
+    /// when `x` is a `u8`. The instance is queued to have its body checked.
+    fn instantiate(
+        &mut self,
+        name: &str,
+        signature: Signature,
+        args: &[Expr],
+    ) -> Option<(Signature, Vec<Option<Type>>)> {
+        let mut bindings = HashMap::new();
+        let mut arg_types = vec![None; args.len()];
+        let (late, early): (Vec<_>, Vec<_>) = (0..args.len()).partition(|&i| {
+            matches!(
+                args[i],
+                Expr::Num(_) | Expr::Float(_) | Expr::Closure { .. }
+            )
+        });
+        for i in early.into_iter().chain(late) {
+            let param = signature
+                .params
+                .get(i)
+                .map(|param| param.substitute(&bindings));
+            arg_types[i] = self.expr_type_expecting(&args[i], param.as_ref());
+            if let (Some(param), Some(arg)) = (signature.params.get(i), &arg_types[i]) {
+                bind_type_params(param, arg, &signature.type_params, &mut bindings);
+            }
+        }
+        let type_args = signature
+            .type_params
+            .iter()
+            .map(|type_param| bindings.get(type_param).cloned().ok_or(type_param))
+            .collect::<Result<Vec<_>, _>>();
+        let type_args = match type_args {
+            Ok(type_args) => type_args,
+            Err(type_param) => {
+                self.error(format!(
+                    "Cannot infer type parameter `{}` of `{}`",
+                    type_param, name
+                ));
+                return None;
+            }
+        };
+        let instance = instance_name(name, &type_args);
+        if !self.instances.contains(&instance) {
+            if self.instances.len() == MAX_INSTANCES {
+                self.error(format!(
+                    "Too many instances of generic functions, reached `{}`",
+                    instance
+                ));
+                return None;
+            }
+            self.instances.insert(instance.clone());
+            self.pending_instances
+                .push((name.to_string(), instance, bindings.clone()));
+        }
+        let signature = Signature {
+            type_params: vec![],
+            params: signature
+                .params
+                .iter()
+                .map(|param| param.substitute(&bindings))
+                .collect(),
+            return_type: signature.return_type.substitute(&bindings),
+            variadic: signature.variadic,
+        };
+        Some((signature, arg_types))
+    }
+
+    /// Checks the payload of an enum value against the variant's declaration
+    fn variant_type(&mut self, enum_name: &str, variant: &str, args: &[Expr]) -> Option<Type> {
+        let arg_types: Vec<Option<Type>> = args.iter().map(|arg| self.expr_type(arg)).collect();
+        let fields = self.variant_fields(enum_name, variant)?;
+        if fields.len() != args.len() {
+            self.error(format!(
+                "`{}::{}` takes {} value(s) but {} were given",
+                enum_name,
+                variant,
+                fields.len(),
+                args.len()
+            ));
+        }
+        for (i, ((field, arg), arg_expr)) in fields.iter().zip(&arg_types).zip(args).enumerate() {
+            match arg {
+                Some(arg) if !accepts(field, arg_expr, arg) => self.error(format!(
+                    "Value {} of `{}::{}` expects {}, got {}",
+                    i + 1,
+                    enum_name,
+                    variant,
+                    field,
+                    arg
+                )),
+                _ => {}
+            }
+        }
+        Some(Type::Named(enum_name.to_string()))
+    }
+
+    /// Checks every arm of a `match` and that together they cover every value of the scrutinee.
+    /// Arms with a guard don't count towards exhaustiveness, the guard may not hold.
+    fn match_type(&mut self, scrutinee: &Expr, arms: &[MatchArm]) -> Option<Type> {
+        let scrutinee = self.expr_type(scrutinee);
+        let outer_span = self.span;
+        let mut patterns_valid = true;
+        let mut match_type = None;
+        for arm in arms {
+            let errors = self.errors.len();
+            let mut bindings = Vec::new();
+            self.check_pattern(&arm.pattern, scrutinee.as_ref(), &mut bindings);
+            patterns_valid &= self.errors.len() == errors;
+            self.scopes.push(HashMap::new());
+            for (ident, ty) in bindings {
+                self.define(&ident, ty, false);
+            }
+            if let Some(guard) = &arm.guard {
+                self.expect_condition(guard);
+            }
+            let arm_type = self.check_block(&arm.body);
+            self.scopes.pop();
+            self.span = outer_span;
+            match (&match_type, arm_type) {
+                (Some(expected), Some(arm_type)) if *expected != arm_type => {
+                    self.error(format!(
+                        "`match` arms have different types: {} and {}",
+                        expected, arm_type
+                    ));
+                }
+                // an arm that returns doesn't contribute a value
+                (None, arm_type) => match_type = arm_type,
+                _ => {}
+            }
+        }
+        if let (true, Some(scrutinee)) = (patterns_valid, &scrutinee) {
+            let unguarded = arms
+                .iter()
+                .filter(|arm| arm.guard.is_none())
+                .map(|arm| &arm.pattern)
+                .collect::<Vec<_>>();
+            if let Some(missing) = missing_pattern(&unguarded, scrutinee, &self.enums) {
+                self.error(format!("Non-exhaustive match: `{}` not covered", missing));
+            }
+        }
+        match_type
+    }
+
+    /// Checks that `pattern` can match a value of type `ty`, collecting the types of its bindings
+    fn check_pattern(
+        &mut self,
+        pattern: &Pattern,
+        ty: Option<&Type>,
+        bindings: &mut Vec<(String, Option<Type>)>,
+    ) {
+        match pattern {
+            Pattern::Wildcard => {}
+            Pattern::Binding(ident) => {
+                if bindings.iter().any(|(other, _)| other == ident) {
+                    self.error(format!(
+                        "`{}` is bound more than once in the same pattern",
+                        ident
+                    ));
+                }
+                bindings.push((ident.clone(), ty.cloned()));
+            }
+            Pattern::Literal(literal) => {
+                let literal_type = self.expr_type(literal);
+                if let (Some(ty), Some(literal_type)) = (ty, literal_type) {
+                    if !accepts(ty, literal, &literal_type) {
+                        self.error(format!(
+                            "Mismatched types: the pattern is {} but the matched value is {}",
+                            literal_type, ty
+                        ));
+                    }
+                }
+            }
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let Some(declared) = self.variant_fields(enum_name, variant) else {
+                    return;
+                };
+                if let Some(ty) = ty.filter(|ty| **ty != Type::Named(enum_name.clone())) {
+                    self.error(format!(
+                        "Mismatched types: the pattern is {} but the matched value is {}",
+                        enum_name, ty
+                    ));
+                    return;
+                }
+                if declared.len() != fields.len() {
+                    self.error(format!(
+                        "`{}::{}` has {} field(s) but the pattern has {}",
+                        enum_name,
+                        variant,
+                        declared.len(),
+                        fields.len()
+                    ));
+                }
+                for (field, ty) in fields.iter().zip(&declared) {
+                    self.check_pattern(field, Some(ty), bindings);
+                }
+            }
+            Pattern::Tuple(elements) => {
+                let types = match ty {
+                    Some(Type::Tuple(types)) if types.len() == elements.len() => Some(types),
+                    Some(ty) => {
+                        self.error(format!(
+                            "Mismatched types: the pattern is a tuple of {} element(s) but the matched value is {}",
+                            elements.len(),
+                            ty
+                        ));
+                        None
+                    }
+                    None => None,
+                };
+                for (i, element) in elements.iter().enumerate() {
+                    let ty = types.map(|types| &types[i]);
+                    self.check_pattern(element, ty, bindings);
+                }
+            }
+        }
+    }
+
+    /// Payload types of `enum_name::variant`, reporting enums and variants that don't exist
+    fn variant_fields(&mut self, enum_name: &str, variant: &str) -> Option<Vec<Type>> {
+        let Some(variants) = self.enums.get(enum_name) else {
+            self.error(format!("Undefined enum: `{}`", enum_name));
+            return None;
+        };
+        let fields = variants
+            .iter()
+            .find(|declared| declared.ident == variant)
+            .map(|declared| declared.fields.clone());
+        if fields.is_none() {
+            self.error(format!("Enum `{}` has no variant `{}`", enum_name, variant));
+        }
+        fields
+    }
+
+    /// Type of `field` on a value of type `ty`, reporting fields that don't exist
+    fn field_type(&mut self, ty: &Type, field: &str) -> Option<Type> {
+        if let Type::Tuple(types) = ty {
+            let element = field.parse::<usize>().ok().and_then(|i| types.get(i));
+            if element.is_none() {
+                self.error(format!("Tuple {} has no field `{}`", ty, field));
+            }
+            return element.cloned();
+        }
+        let Some(name) = (match ty {
+            Type::Named(name) if !self.enums.contains_key(name) => Some(name),
+            _ => None,
+        }) else {
+            self.error(format!("Cannot access field `{}` on {}", field, ty));
+            return None;
+        };
+        let declared = self
+            .structs
+            .get(name)?
+            .iter()
+            .find(|declared| declared.ident == field)
+            .map(|declared| declared.ty.clone());
+        if declared.is_none() {
+            self.error(format!("Struct `{}` has no field `{}`", name, field));
+        }
+        declared
+    }
+
+    fn is_declared_type(&self, name: &str) -> bool {
+        self.structs.contains_key(name) || self.enums.contains_key(name)
+    }
+
+    /// Reports annotations naming a struct or enum that isn't declared
+    fn expect_known_type(&mut self, ty: &Type) {
+        match ty {
+            Type::Named(name)
+                if !self.is_declared_type(name) && !self.type_params.contains(name) =>
+            {
+                self.error(format!("Unknown type `{}`", name))
+            }
+            Type::Ptr(ty) | Type::Array(ty, _) | Type::Vec(ty) | Type::Slice(ty) => {
+                self.expect_known_type(ty)
+            }
+            Type::Function(params, return_type) => {
+                for ty in params.iter().chain([&**return_type]) {
+                    self.expect_known_type(ty);
+                }
+            }
+            Type::Tuple(types) => types.iter().for_each(|ty| self.expect_known_type(ty)),
+            _ => {}
+        }
+    }
+
+    fn define(&mut self, ident: &str, ty: Option<Type>, mutable: bool) {
+        self.scopes
+            .last_mut()
+            .expect("the global scope is never popped")
+            .insert(ident.to_string(), Binding { ty, mutable });
+    }
+
+    fn lookup(&self, ident: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(ident))
+    }
+
+    /// Whether `ident` is a local from outside the closure being checked
+    fn is_captured(&self, ident: &str) -> bool {
+        let Some(&closure_scope) = self.closure_scopes.last() else {
+            return false;
+        };
+        match self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(ident))
+        {
+            Some(depth) => depth > 0 && depth < closure_scope,
+            None => false,
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        let message = match &self.instance {
+            Some(instance) => format!("{}, in `{}`", message, instance),
+            None => message,
+        };
+        self.errors.push(TypeError {
+            message,
+            span: self.span,
+        });
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_int(ty: &Type) -> bool {
+    matches!(ty, Type::Int { .. })
+}
+
+fn is_number(ty: &Type) -> bool {
+    matches!(ty, Type::Int { .. } | Type::Float { .. })
+}
+
+/// Whether `expr` of type `actual` can be used where `expected` is. Numbers of different types
+/// need an `as` cast, except for unsuffixed literals, which take any type of their kind they fit in.
+fn accepts(expected: &Type, expr: &Expr, actual: &Type) -> bool {
+    match (expected, expr) {
+        (Type::Int { bits, signed }, Expr::Num(n)) => {
+            Integer::with_width(n.to_i128(), *bits, *signed).is_some()
+        }
+        (Type::Float { .. }, Expr::Float(_)) => true,
+        (Type::Tuple(expected), Expr::Tuple(elements)) => match actual {
+            Type::Tuple(actual) if expected.len() == elements.len() => expected
+                .iter()
+                .zip(elements)
+                .zip(actual)
+                .all(|((expected, element), actual)| accepts(expected, element, actual)),
+            _ => false,
+        },
+        _ => expected == actual,
+    }
+}
+
+/// Unsuffixed literals are `i32`, unless they only fit in `i64` or `i128`
+fn literal_type(n: &Integer) -> Type {
+    let (bits, signed) = n.width();
+    Type::Int { bits, signed }
+}
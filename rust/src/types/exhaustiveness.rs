@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::llvm::ast::{Expr, Pattern, Type, Variant};
+
+/// A pattern reduced to what matters for exhaustiveness: bindings are wildcards
+/// and literals of types with infinitely many values never cover their type.
+#[derive(Debug, Clone, PartialEq)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(String, String), // enum name and variant
+    Bool(bool),
+    Tuple,   // the only constructor of a tuple type
+    Literal, // a number, char or string, never equal to a constructor we enumerate
+}
+
+impl From<&Pattern> for Pat {
+    fn from(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_) => Pat::Wild,
+            Pattern::Literal(Expr::Bool(b)) => Pat::Ctor(Ctor::Bool(*b), vec![]),
+            Pattern::Literal(_) => Pat::Ctor(Ctor::Literal, vec![]),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => Pat::Ctor(
+                Ctor::Variant(enum_name.clone(), variant.clone()),
+                fields.iter().map(Pat::from).collect(),
+            ),
+            Pattern::Tuple(elements) => {
+                Pat::Ctor(Ctor::Tuple, elements.iter().map(Pat::from).collect())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Pat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pat::Wild | Pat::Ctor(Ctor::Literal, _) => write!(f, "_"),
+            Pat::Ctor(Ctor::Bool(b), _) => write!(f, "{}", b),
+            Pat::Ctor(Ctor::Variant(enum_name, variant), fields) => {
+                write!(f, "{}::{}", enum_name, variant)?;
+                if !fields.is_empty() {
+                    let fields = fields.iter().map(Pat::to_string).collect::<Vec<_>>();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+            Pat::Ctor(Ctor::Tuple, elements) if elements.len() == 1 => {
+                write!(f, "({},)", elements[0])
+            }
+            Pat::Ctor(Ctor::Tuple, elements) => {
+                let elements = elements.iter().map(Pat::to_string).collect::<Vec<_>>();
+                write!(f, "({})", elements.join(", "))
+            }
+        }
+    }
+}
+
+/// Finds a value of type `ty` that none of `patterns` match, formatted as a pattern like
+/// `Shape::Rect(_, _)`, or `None` when the patterns are exhaustive.
+/// The patterns are expected to have been type checked against `ty` already.
+pub fn missing_pattern(
+    patterns: &[&Pattern],
+    ty: &Type,
+    enums: &HashMap<String, Vec<Variant>>,
+) -> Option<String> {
+    let rows = patterns
+        .iter()
+        .map(|pattern| vec![Pat::from(*pattern)])
+        .collect::<Vec<_>>();
+    let witness = missing(&rows, std::slice::from_ref(ty), enums)?;
+    Some(witness[0].to_string())
+}
+
+/// Every value of a type with finitely many constructors, with the types of their fields
+fn constructors(
+    ty: &Type,
+    enums: &HashMap<String, Vec<Variant>>,
+) -> Option<Vec<(Ctor, Vec<Type>)>> {
+    match ty {
+        Type::Bool => Some(vec![
+            (Ctor::Bool(true), vec![]),
+            (Ctor::Bool(false), vec![]),
+        ]),
+        Type::Named(name) => enums.get(name).map(|variants| {
+            variants
+                .iter()
+                .map(|variant| {
+                    let ctor = Ctor::Variant(name.clone(), variant.ident.clone());
+                    (ctor, variant.fields.clone())
+                })
+                .collect()
+        }),
+        Type::Tuple(types) => Some(vec![(Ctor::Tuple, types.clone())]),
+        _ => None,
+    }
+}
+
+/// Searches for a row of values of `types` that no row of `rows` matches, the usefulness
+/// check from Maranget's "Warnings for pattern matching". Each row matches the columns
+/// left to right, the first column is split by constructor and the search recurses into
+/// the rows that could still match it.
+fn missing(
+    rows: &[Vec<Pat>],
+    types: &[Type],
+    enums: &HashMap<String, Vec<Variant>>,
+) -> Option<Vec<Pat>> {
+    let Some((ty, rest_types)) = types.split_first() else {
+        // no columns left, any remaining row matches
+        return if rows.is_empty() { Some(vec![]) } else { None };
+    };
+    match constructors(ty, enums) {
+        Some(ctors) => ctors.into_iter().find_map(|(ctor, field_types)| {
+            let arity = field_types.len();
+            let specialized = rows
+                .iter()
+                .filter_map(|row| match &row[0] {
+                    Pat::Wild => Some(
+                        std::iter::repeat_n(Pat::Wild, arity)
+                            .chain(row[1..].iter().cloned())
+                            .collect(),
+                    ),
+                    Pat::Ctor(head, fields) if *head == ctor => {
+                        Some(fields.iter().chain(&row[1..]).cloned().collect())
+                    }
+                    Pat::Ctor(..) => None,
+                })
+                .collect::<Vec<Vec<Pat>>>();
+            let types = field_types
+                .into_iter()
+                .chain(rest_types.iter().cloned())
+                .collect::<Vec<_>>();
+            let mut witness = missing(&specialized, &types, enums)?;
+            let rest = witness.split_off(arity);
+            Some(
+                std::iter::once(Pat::Ctor(ctor, witness))
+                    .chain(rest)
+                    .collect(),
+            )
+        }),
+        None => {
+            // literals can't cover a type with infinitely many values, only wildcards can
+            let defaults = rows
+                .iter()
+                .filter(|row| row[0] == Pat::Wild)
+                .map(|row| row[1..].to_vec())
+                .collect::<Vec<_>>();
+            let witness = missing(&defaults, rest_types, enums)?;
+            Some(std::iter::once(Pat::Wild).chain(witness).collect())
+        }
+    }
+}
@@ -0,0 +1,608 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parsers::{modules::load_source, parse_program},
+        types::checker::{check_modules, check_program},
+    };
+
+    /// Type checks `src`, returning the reported errors one per line
+    fn check(src: &str) -> anyhow::Result<Vec<String>> {
+        let ast = parse_program(src)?;
+        Ok(match check_program(&ast, src) {
+            Ok(()) => vec![],
+            Err(err) => err.to_string().lines().skip(1).map(String::from).collect(),
+        })
+    }
+
+    #[test]
+    fn test_well_typed_program() -> anyhow::Result<()> {
+        let src = r#"
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+fn greet(name: str) -> () {
+    print(name);
+}
+
+fn main() {
+    let mut total: i64 = add(1, 2);
+    total += 3;
+    let big = total > 5 && !false;
+    if big {
+        greet("hi");
+    }
+    for i in 0..3 {
+        printd(i < 2);
+    }
+    let arg = std::args[1];
+    printf(arg);
+    return 0;
+}
+"#;
+        assert_eq!(check(src)?, Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_every_error_with_its_location() -> anyhow::Result<()> {
+        let src = r#"
+fn add(a: i64, b: i64) -> i64 {
+    a + b
+}
+
+fn main() {
+    let x: str = 5;
+    add(1);
+    add("one", 2);
+    let y = 1;
+    y = 2;
+    if y { printd(y); }
+    undefined(3);
+    return "done";
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "7:5: Mismatched types: `x` is declared as str but the value is i32",
+                "8:5: `add` takes 2 argument(s) but 1 were given",
+                "9:5: Argument 1 of `add` expects i64, got str",
+                "11:5: Cannot assign twice to immutable variable `y`",
+                "12:5: Expected a bool condition, got i32",
+                "13:5: Undefined function: `undefined`",
+                "14:5: Mismatched types: expected a return value of i32, got str",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_function_names() -> anyhow::Result<()> {
+        let src = r#"
+fn exit(code: i32) -> i32 { code }
+fn strlen(s: str) -> u64 { 0 }
+fn print(s: str) -> () { }
+fn panic() -> i32 { 1 }
+fn malloc(size: u64) -> u64 { size }
+fn main() {
+    panic();
+}
+fn memcpy() -> i32 { 0 }
+"#;
+        // the runtime's own helpers have reserved names, a user `panic` doesn't clash with them
+        assert_eq!(
+            check(src)?,
+            vec![
+                "2:1: `exit` is reserved for the runtime",
+                "3:1: `strlen` is reserved for the runtime",
+                "4:1: Function `print` is defined more than once",
+                "6:1: `malloc` is reserved for the runtime",
+                "10:1: `memcpy` is reserved for the runtime",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_operator_types() -> anyhow::Result<()> {
+        assert_eq!(
+            check(r#"fn f() { let a = "s" + 1; let b = 1 && true; let c = -true; "a" < "b"; }"#)?,
+            vec![
+                "1:10: Cannot apply `+` to str and i32",
+                "1:27: Cannot apply `&&` to i32 and bool, expected bool operands",
+                "1:46: Cannot apply `-` to bool",
+                "1:61: Cannot compare str < str",
+            ]
+        );
+        assert_eq!(
+            check("fn f() { let v = if true { 1 } else { 'c' }; }")?,
+            vec!["1:10: `if` and `else` have different types: i32 and char"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_integer_types_need_casts() -> anyhow::Result<()> {
+        assert_eq!(
+            check(r#"fn f(a: u8, b: i64) { a + b; a + 1; a + 300; b as u8 + a; -a; true as i32; "s" as i32; let c: u8 = 256; }"#)?,
+            vec![
+                "1:23: Mismatched types: u8 + i64, convert one side with `as`",
+                "1:37: Mismatched types: u8 + i32, convert one side with `as`",
+                "1:59: Cannot apply `-` to u8",
+                "1:76: Cannot cast str as i32",
+                "1:88: Mismatched types: `c` is declared as u8 but the value is i32",
+            ]
+        );
+        assert_eq!(check("fn f(n: u16) -> u16 { let half = n / 2u16; if half > 10 { return half; } 7 }")?, Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_types() -> anyhow::Result<()> {
+        assert_eq!(
+            check("fn f(x: f32, n: i32) -> f64 { let a = x * 2.0; let b = x + n; let c = n as f64 / 2.0; let d: f32 = 1; printf64(c); -x; x as f64 }")?,
+            vec![
+                "1:48: Mismatched types: f32 + i32, convert one side with `as`",
+                "1:87: Mismatched types: `d` is declared as f32 but the value is i32",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_loops_and_scopes() -> anyhow::Result<()> {
+        assert_eq!(
+            check("fn f() { break; 'outer: while true { continue 'inner; } if true { let inner = 1; } inner; }")?,
+            vec![
+                "1:10: `break` outside of a loop",
+                "1:38: Undeclared loop label: 'inner",
+                "1:84: Undefined variable: `inner`",
+            ]
+        );
+        // the loop variable has the type of the bounds, an unsuffixed literal takes the other's
+        let src = r#"
+fn f(n: u64) {
+    for i in 0u8..10u8 { let b: u8 = i; }
+    for i in 0..n { let x: u64 = i; let y: i32 = i; }
+    for i in 0u8..n { }
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "4:37: Mismatched types: `y` is declared as i32 but the value is u64",
+                "5:5: Range bounds have different types: u8 and u64, convert one with `as`",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_struct_types() -> anyhow::Result<()> {
+        let src = r#"
+struct Point { x: i32, y: u8 }
+fn origin() -> Point { Point { x: 0, y: 0 } }
+fn f(p: Point) -> i32 {
+    let mut q = origin();
+    q.y = p.y + 1;
+    q.x = p.y;
+    p.x = 3;
+    let r = Point { x: 1, z: 2, x: 3 };
+    let s: Shape = p;
+    p == q;
+    p.w + q.x.y
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "7:5: Mismatched types: `q.x` is i32 but the value is u8",
+                "8:5: Cannot assign to `p.x`, `p` is not declared as mutable",
+                "9:5: Struct `Point` has no field `z`",
+                "9:5: Field `x` is specified more than once",
+                "9:5: Missing field `y` in `Point` literal",
+                "10:5: Unknown type `Shape`",
+                "10:5: Mismatched types: `s` is declared as Shape but the value is Point",
+                "11:5: Cannot compare Point == Point",
+                "12:5: Struct `Point` has no field `w`",
+                "12:5: Cannot access field `y` on i32",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_enums_and_match() -> anyhow::Result<()> {
+        let src = r#"
+enum Shape { Circle(i32), Rect(i32, i32), Empty }
+fn area(s: Shape) -> i32 {
+    match s { Shape::Circle(r) => r * r, Shape::Rect(w, h) => w * h, Shape::Empty => 0 }
+}
+fn f(s: Shape, flag: bool) -> i32 {
+    let a = Shape::Circle(true);
+    let b = Shape::Rect(1);
+    let c = Shape::Square(1);
+    match s { Shape::Circle(_) => 1, Shape::Rect(w, _) if w > 0 => 2, Shape::Empty => 3 };
+    match flag { true => 1 };
+    match s { Shape::Circle(r) => r, _ => 'c' };
+    match s { Shape::Rect(w, w) => w, Shape::Circle(1) => 0, Shape::Circle("one") => 0, _ => 1 };
+    match 5 { 1 => 1 };
+    match s { x if x => 1, Shape::Empty(1) => 2, _ => 3 }
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "7:5: Value 1 of `Shape::Circle` expects i32, got bool",
+                "8:5: `Shape::Rect` takes 2 value(s) but 1 were given",
+                "9:5: Enum `Shape` has no variant `Square`",
+                "10:5: Non-exhaustive match: `Shape::Rect(_, _)` not covered",
+                "11:5: Non-exhaustive match: `false` not covered",
+                "12:5: `match` arms have different types: i32 and char",
+                "13:5: `w` is bound more than once in the same pattern",
+                "13:5: Mismatched types: the pattern is str but the matched value is i32",
+                "14:5: Non-exhaustive match: `_` not covered",
+                "15:5: Expected a bool condition, got Shape",
+                "15:5: `Shape::Empty` has 0 field(s) but the pattern has 1",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_closures() -> anyhow::Result<()> {
+        let src = r#"
+fn apply(f: fn(i32) -> i32, x: i32) -> i32 { f(x) }
+fn adder(n: i32) -> fn(i32) -> i32 { |x| x + n }
+fn f(flag: bool) -> i32 {
+    let mut total = 0;
+    let inc = |x| x + 1;
+    let is = |x: i32| -> bool { x > 0 };
+    let a = apply(inc, 1) + apply(|x| x * 2, 2);
+    let b: fn(bool) -> i32 = |x| x + 1;
+    let c = apply(is, 1);
+    let d = flag(1);
+    let e = |x| { total = x; };
+    let g = printf;
+    let h: fn() -> bool = || 1;
+    undefined(inc);
+    inc == inc
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "9:34: Cannot apply `+` to bool and i32",
+                "10:5: Argument 1 of `apply` expects fn(i32) -> i32, got fn(i32) -> bool",
+                "11:5: Cannot call `flag` of type bool",
+                "12:19: Cannot assign to `total`, closures capture variables by value",
+                "13:5: Variadic function `printf` can't be used as a value",
+                "14:30: Mismatched types: the closure returns bool but its body is i32",
+                "15:5: Undefined function: `undefined`",
+                "16:5: Cannot compare fn(i32) -> i32 == fn(i32) -> i32",
+            ]
+        );
+        // globals aren't captured, a `static mut` can be assigned in a closure
+        let src = r#"
+static mut HITS: i32 = 0;
+fn main() {
+    let hit = || { HITS += 1; HITS };
+    hit();
+}
+"#;
+        assert_eq!(check(src)?, Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_module_types() -> anyhow::Result<()> {
+        let program = load_source(
+            r#"
+mod shapes {
+    pub struct Point { x: i32, y: i32 }
+    pub fn norm(p: Point) -> i32 { p.x + p.y }
+    pub fn broken() { let b: bool = 1; }
+}
+use shapes::Point;
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let n: bool = shapes::norm(p);
+    shapes::norm(1);
+}
+"#,
+            std::path::Path::new("."),
+        )?;
+        let errors = match check_modules(&program) {
+            Ok(()) => vec![],
+            Err(err) => err.to_string().lines().skip(1).map(String::from).collect(),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                "5:23: Mismatched types: `b` is declared as bool but the value is i32",
+                "10:5: Mismatched types: `n` is declared as bool but the value is i32",
+                "11:5: Argument 1 of `shapes::norm` expects shapes::Point, got i32",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generic_functions() -> anyhow::Result<()> {
+        let src = r#"
+fn max<T>(a: T, b: T) -> T { if a > b { a } else { b } }
+fn id<T>(x: T) -> T { x }
+fn make<T>() -> i32 { 0 }
+fn wrap<T>(x: T, y: Thing) -> T { x }
+struct P { x: i32 }
+fn main() {
+    let b: u8 = 3;
+    let c: u8 = max(b, 4);
+    let d: bool = id(1);
+    max(true, 1);
+    max(P { x: 1 }, P { x: 2 });
+    make();
+    let f = id;
+    let g: u8 = id(id(b));
+}
+"#;
+        // instances are checked after the rest of the program, errors in them name the instance
+        assert_eq!(
+            check(src)?,
+            vec![
+                "5:1: Unknown type `Thing`",
+                "10:5: Mismatched types: `d` is declared as bool but the value is i32",
+                "11:5: Argument 2 of `max` expects bool, got i32",
+                "13:5: Cannot infer type parameter `T` of `make`",
+                "14:5: Generic function `id` can't be used as a value",
+                "2:30: Cannot compare P > P, in `max<P>`",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tuples() -> anyhow::Result<()> {
+        let src = r#"
+fn divmod(a: i32, b: i32) -> (i32, i32) { (a / b, a - a / b * b) }
+fn main() {
+    let (q, r) = divmod(7, 2);
+    let sum: i32 = q + r;
+    let t: (u8, bool) = (1, true);
+    let x = t.2;
+    let (a, b, c) = divmod(1, 1);
+    let (0, d) = divmod(1, 1);
+    let (e, f): (i32, i32) = (1, 2, 3);
+    let w: (i32, i32) = (1, 'c');
+    q = 3;
+    match t { (_, true) => 1, (0, false) => 2 };
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "7:5: Tuple (u8, bool) has no field `2`",
+                "8:5: Mismatched types: the pattern is a tuple of 3 element(s) but the matched value is (i32, i32)",
+                "9:5: Refutable pattern in `let`: `(_, _)` not covered",
+                "10:5: Mismatched types: the pattern is declared as (i32, i32) but the value is (i32, i32, i32)",
+                "11:5: Mismatched types: `w` is declared as (i32, i32) but the value is (i32, char)",
+                "12:5: Cannot assign twice to immutable variable `q`",
+                "13:5: Non-exhaustive match: `(_, false)` not covered",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays_and_vecs() -> anyhow::Result<()> {
+        let src = r#"
+fn main() {
+    let mut a: [u8; 3] = [1, 2, 3];
+    a[0] = 4;
+    let mut v: Vec<i32> = Vec::new();
+    v.push(1);
+    let n: u64 = v.len() + a.len();
+    let b = [1, true];
+    let c = [];
+    let mut d = Vec::new();
+    let frozen: Vec<i32> = Vec::new();
+    frozen.push(1);
+    v.push(true);
+    v.shrink();
+    let i = 5;
+    i[0];
+    a[true];
+    a[1] = 'c';
+    frozen[0] = 1;
+    let e: i32 = v.pop();
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "8:5: Mismatched types: element 1 of the array is i32, expected bool",
+                "9:5: Cannot infer the element type of an empty array",
+                "10:5: Cannot infer the element type of `Vec::new()`, annotate it as `Vec<T>`",
+                "12:5: Cannot call `push` on `frozen`, it is not declared as mutable",
+                "13:5: Argument 1 of `push` expects i32, got bool",
+                "14:5: No method `shrink` on Vec<i32>",
+                "16:5: Cannot index into a value of type i32",
+                "17:5: Array index must be an integer, got bool",
+                "18:5: Mismatched types: the elements of `a` are u8 but the value is char",
+                "19:5: Cannot assign to an element of `frozen`, `frozen` is not declared as mutable",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_slices() -> anyhow::Result<()> {
+        let src = r#"
+fn sum(xs: &[i32]) -> i32 { xs[0] }
+fn main() {
+    let a: [i32; 4] = [1, 2, 3, 4];
+    let total: i32 = sum(a[1..3]);
+    let n: u64 = a[..].len();
+    let s = "hello";
+    let word: &[u8] = s[1..];
+    let letters: &[char] = s[0..2];
+    sum(a);
+    let b: &[u8] = a[0..2];
+    a[3..1];
+    a[0..5];
+    a[true..];
+    let x = 5;
+    x[0..1];
+    a[..2] == a[2..];
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "9:5: Mismatched types: `letters` is declared as &[char] but the value is &[u8]",
+                "10:5: Argument 1 of `sum` expects &[i32], got [i32; 4]",
+                "11:5: Mismatched types: `b` is declared as &[u8] but the value is &[i32]",
+                "12:5: Slice starts at 3 but ends at 1",
+                "13:5: Slice end 5 is out of range for an array of length 4",
+                "14:5: Slice bounds must be integers, got bool",
+                "16:5: Cannot slice a value of type i32",
+                "17:5: Cannot compare &[i32] == &[i32]",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_macros() -> anyhow::Result<()> {
+        let src = r#"
+fn main() {
+    let x = 5;
+    println!("x = {}, y = {}", x, 2.5);
+    let s: str = format!("{}{}", 'a', true);
+    println!("{} {}", x);
+    println!("{}", x, x);
+    let n: i32 = format!("{}", x);
+    println!("{}", [1, 2]);
+    println!(s);
+    println!("{x}", x);
+    dbg!(x);
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "6:5: `println!` has 2 placeholder(s) but 1 argument(s) were given",
+                "7:5: `println!` has 1 placeholder(s) but 2 argument(s) were given",
+                "8:5: Mismatched types: `n` is declared as i32 but the value is str",
+                "9:5: Argument 1 of `println!` is [i32; 2], which can't be formatted",
+                "10:5: `println!` takes a string literal as its first argument",
+                "11:5: Invalid format string in `println!`: only `{}` placeholders are supported, write `{{` for a literal `{`",
+                "12:5: Unknown macro `dbg!`",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_globals() -> anyhow::Result<()> {
+        let src = r#"
+fn double(x: i32) -> i32 { x * LIMIT }
+const LIMIT: i32 = 10;
+const DOUBLE: i64 = LIMIT as i64 * 2;
+static mut COUNTER: u8 = 0;
+static NAME: str = "red";
+const WRONG: bool = 1;
+const CALLED: i32 = double(2);
+static mut COUNTS: [i32; 2] = [0, 0];
+static LATER: u8 = COUNTER;
+fn main() {
+    COUNTER += 1;
+    let n: u8 = COUNTER;
+    LIMIT = 3;
+    NAME = "blue";
+    const INNER: i32 = 1;
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "7:1: Mismatched types: `WRONG` is declared as bool but the value is i32",
+                "8:1: The value of `CALLED` has to be known at compile time",
+                "9:1: `COUNTS` can't be [i32; 2], a const or static has to be a number, bool, char or str",
+                "10:1: The value of `LATER` has to be known at compile time",
+                "14:5: Cannot assign twice to immutable variable `LIMIT`",
+                "15:5: Cannot assign twice to immutable variable `NAME`",
+                "16:5: `INNER` has to be declared at the top level, not inside a function",
+            ]
+        );
+        // `const`s can refer to the ones declared after them, integer values are computed without wrapping
+        let src = r#"
+const A: i32 = B * 2;
+const B: i32 = 3;
+const WIDE: i64 = A as i64 * 4294967296;
+const MAX: i32 = 2147483647 + 1;
+const HALF: i32 = B / (A - 6);
+const SMALL: u8 = 200;
+const MIN: i8 = SMALL as i8 - 72;
+const NEG: i8 = -MIN;
+const TRUNCATED: u8 = (B * 100) as u8;
+const C: i32 = D;
+const D: i32 = C;
+fn main() {
+    let x: i64 = WIDE;
+}
+"#;
+        assert_eq!(
+            check(src)?,
+            vec![
+                "5:1: Integer overflow in the value of `MAX`",
+                "6:1: Division by zero in the value of `HALF`",
+                "9:1: Integer overflow in the value of `NEG`",
+                "12:1: Undefined variable: `C`",
+                "12:1: The value of `D` has to be known at compile time",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_methods() -> anyhow::Result<()> {
+        let program = load_source(
+            r#"
+struct Point { x: i32, y: i32 }
+// methods can be called before their `impl` block
+fn total(p: Point) -> i32 { p.len() + Point::new(0, 0).len() }
+impl Point {
+    fn new(x: i32, y: i32) -> Point { Point { x: x, y: y } }
+    fn len(self) -> i32 { self.x + self.y }
+}
+fn main() {
+    let p = Point::new(1, 2);
+    let n: i32 = p.len();
+    let b: bool = p.len();
+    p.len(1);
+    p.area();
+    p.new(1, 2);
+    Point::new("one", 2);
+}
+"#,
+            std::path::Path::new("."),
+        )?;
+        let errors = match check_modules(&program) {
+            Ok(()) => vec![],
+            Err(err) => err.to_string().lines().skip(1).map(String::from).collect(),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                "12:5: Mismatched types: `b` is declared as bool but the value is i32",
+                "13:5: `len` takes 0 argument(s) but 1 were given",
+                "14:5: No method `area` on Point",
+                "15:5: `Point::new` doesn't take `self`, call it as `Point::new(...)`",
+                "16:5: Argument 1 of `Point::new` expects i32, got str",
+            ]
+        );
+        Ok(())
+    }
+}
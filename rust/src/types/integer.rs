@@ -1,117 +1,155 @@
-use std::ops::{Sub, Mul, Div, Add};
+use std::ops::{Sub, Mul, Div, Add, Neg};
 
-const DEBUG_MODE: bool = true; // set to false for release mode
-
-#[derive(Debug, PartialEq, Clone, Eq)]
+/// A sized integer value. Arithmetic stays in the width of its operands and
+/// reports overflow instead of silently wrapping.
+#[derive(Debug, Clone, Eq)]
 pub enum Integer {
     Int8(i8),
+    Int16(i16),
     Int(i32),
     Int64(i64),
     Int128(i128),
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
 }
 
-pub fn parse_large_integer(num_str: &str) -> Result<i128, &'static str> {
-    let mut result = 0i128;
-
-    for (_idx, ch) in num_str.chars().enumerate() {
-        let digit = (ch as i128) - ('0' as i128);
-        if digit < 0 || digit > 9 {
-            return Err("Invalid character in number string");
+impl Integer {
+    fn promote(&self) -> i128 {
+        match *self {
+            Integer::Int8(a) => a as i128,
+            Integer::Int16(a) => a as i128,
+            Integer::Int(a) => a as i128,
+            Integer::Int64(a) => a as i128,
+            Integer::Int128(a) => a,
+            Integer::UInt8(a) => a as i128,
+            Integer::UInt16(a) => a as i128,
+            Integer::UInt32(a) => a as i128,
+            Integer::UInt64(a) => a as i128,
         }
-
-        result = checked_mul(result, 10);
-        result = checked_add(result, digit);
     }
 
-    Ok(result)
-}
-
-
-fn checked_add(a: i128, b: i128) -> i128 {
-    let (result, overflowed) = a.overflowing_add(b);
-    if  DEBUG_MODE && overflowed {
-        panic!("Integer overflow detected in addition");
+    /// Bit width and signedness of the value's type
+    pub fn width(&self) -> (u32, bool) {
+        match self {
+            Integer::Int8(_) => (8, true),
+            Integer::Int16(_) => (16, true),
+            Integer::Int(_) => (32, true),
+            Integer::Int64(_) => (64, true),
+            Integer::Int128(_) => (128, true),
+            Integer::UInt8(_) => (8, false),
+            Integer::UInt16(_) => (16, false),
+            Integer::UInt32(_) => (32, false),
+            Integer::UInt64(_) => (64, false),
+        }
     }
-    result
-}
 
-fn checked_mul(a: i128, b: i128) -> i128 {
-    let (result, overflowed) = a.overflowing_mul(b);
-    if  DEBUG_MODE && overflowed {
-        panic!("Integer overflow detected in multiplication");
+    /// `value` as an integer of the given width, `None` if it doesn't fit
+    pub fn with_width(value: i128, bits: u32, signed: bool) -> Option<Integer> {
+        match (bits, signed) {
+            (8, true) => i8::try_from(value).ok().map(Integer::Int8),
+            (16, true) => i16::try_from(value).ok().map(Integer::Int16),
+            (32, true) => i32::try_from(value).ok().map(Integer::Int),
+            (64, true) => i64::try_from(value).ok().map(Integer::Int64),
+            (128, true) => Some(Integer::Int128(value)),
+            (8, false) => u8::try_from(value).ok().map(Integer::UInt8),
+            (16, false) => u16::try_from(value).ok().map(Integer::UInt16),
+            (32, false) => u32::try_from(value).ok().map(Integer::UInt32),
+            (64, false) => u64::try_from(value).ok().map(Integer::UInt64),
+            _ => None,
+        }
     }
-    result
-}
 
-impl Integer {
-    fn promote(self) -> (Integer, i128) {
-        match self {
-            Integer::Int8(a) => (Integer::Int8(a), a as i128),
-            Integer::Int(a) => (Integer::Int(a), a as i128),
-            Integer::Int64(a) => (Integer::Int64(a), a as i128),
-            Integer::Int128(a) => (Integer::Int128(a), a),
+    /// Truncating / extending conversion with the semantics of `as`
+    pub fn cast(&self, bits: u32, signed: bool) -> Integer {
+        let value = self.promote();
+        match (bits, signed) {
+            (8, true) => Integer::Int8(value as i8),
+            (16, true) => Integer::Int16(value as i16),
+            (32, true) => Integer::Int(value as i32),
+            (64, true) => Integer::Int64(value as i64),
+            (8, false) => Integer::UInt8(value as u8),
+            (16, false) => Integer::UInt16(value as u16),
+            (32, false) => Integer::UInt32(value as u32),
+            (64, false) => Integer::UInt64(value as u64),
+            _ => Integer::Int128(value),
         }
     }
 
-    fn demote(val: i128) -> Integer {
-        if let Some(result) = i8::try_from(val).ok() {
-            Integer::Int8(result)
-        } else if let Some(result) = i32::try_from(val).ok() {
-            Integer::Int(result)
-        } else if let Some(result) = i64::try_from(val).ok() {
-            Integer::Int64(result)
+    /// Converts a float with the semantics of `as`: the fraction is dropped, out of range values
+    /// saturate at the bounds of the type and NaN becomes 0
+    pub fn from_f64(value: f64, bits: u32, signed: bool) -> Integer {
+        let (min, max) = match (bits, signed) {
+            (128, true) => (i128::MIN, i128::MAX),
+            (bits, true) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+            (bits, false) => (0, (1 << bits) - 1),
+        };
+        let value = (value as i128).clamp(min, max);
+        Integer::with_width(value, bits, signed).expect("value was clamped to the bounds of its type")
+    }
+
+    /// Operands of the same type keep it, otherwise the wider one wins (signed on a tie)
+    fn result_width(&self, other: &Integer) -> (u32, bool) {
+        let (left, right) = (self.width(), other.width());
+        if left == right {
+            left
+        } else if left.0 != right.0 {
+            left.max(right)
         } else {
-            Integer::Int128(val)
+            (left.0, true)
         }
     }
 
-    fn add(self, other: Integer) -> Integer {
-        let (_s1, val1) = self.promote();
-        let (_s2, val2) = other.promote();
-        let result = Self::checked_add(val1, val2);  // Use the checked_add method here
-        Integer::demote(result)
+    fn checked(self, rhs: Integer, op: fn(i128, i128) -> Option<i128>) -> Option<Integer> {
+        let (bits, signed) = self.result_width(&rhs);
+        op(self.promote(), rhs.promote()).and_then(|result| Integer::with_width(result, bits, signed))
     }
 
-    fn checked_add(a: i128, b: i128) -> i128 {
-        let (result, overflowed) = a.overflowing_add(b);
-        if DEBUG_MODE && overflowed {
-            panic!("Integer overflow detected");
-        }
-        result
+    pub fn checked_add(self, rhs: Integer) -> Option<Integer> {
+        self.checked(rhs, i128::checked_add)
     }
 
-    
-    
-}
+    pub fn checked_sub(self, rhs: Integer) -> Option<Integer> {
+        self.checked(rhs, i128::checked_sub)
+    }
 
-impl Add for Integer {
-    type Output = Integer;
+    pub fn checked_mul(self, rhs: Integer) -> Option<Integer> {
+        self.checked(rhs, i128::checked_mul)
+    }
 
-    fn add(self, other: Self) -> Self::Output {
-        self.add(other)
+    /// `None` on division by zero as well as overflow
+    pub fn checked_div(self, rhs: Integer) -> Option<Integer> {
+        self.checked(rhs, i128::checked_div)
     }
-}
 
-impl From<i32> for Integer {
-    fn from(item: i32) -> Self {
-        Integer::Int(item)
+    pub fn checked_neg(self) -> Option<Integer> {
+        let (bits, signed) = self.width();
+        self.promote().checked_neg().and_then(|result| Integer::with_width(result, bits, signed))
     }
-}
 
+    pub fn to_i128(&self) -> i128 {
+        self.promote()
+    }
 
+    pub fn is_zero(&self) -> bool {
+        self.promote() == 0
+    }
+}
 
+impl Add for Integer {
+    type Output = Integer;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.checked_add(rhs).expect("Integer overflow detected in addition")
+    }
+}
 
 impl Sub for Integer {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Integer::Int8(l), Integer::Int8(r)) => Integer::Int8(l - r),
-            (Integer::Int(l), Integer::Int(r)) => Integer::Int(l - r),
-            (Integer::Int64(l), Integer::Int64(r)) => Integer::Int64(l - r),
-            (Integer::Int128(l), Integer::Int128(r)) => Integer::Int128(l - r),
-            _ => panic!("Subtraction mismatched types"),
-        }
+        self.checked_sub(rhs).expect("Integer overflow detected in subtraction")
     }
 }
 
@@ -119,13 +157,7 @@ impl Mul for Integer {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Integer::Int8(l), Integer::Int8(r)) => Integer::Int8(l * r),
-            (Integer::Int(l), Integer::Int(r)) => Integer::Int(l * r),
-            (Integer::Int64(l), Integer::Int64(r)) => Integer::Int64(l * r),
-            (Integer::Int128(l), Integer::Int128(r)) => Integer::Int128(l * r),
-            _ => panic!("Multiplication mismatched types"),
-        }
+        self.checked_mul(rhs).expect("Integer overflow detected in multiplication")
     }
 }
 
@@ -133,28 +165,58 @@ impl Div for Integer {
     type Output = Self;
 
     fn div(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Integer::Int8(l), Integer::Int8(r)) => Integer::Int8(l / r),
-            (Integer::Int(l), Integer::Int(r)) => Integer::Int(l / r),
-            (Integer::Int64(l), Integer::Int64(r)) => Integer::Int64(l / r),
-            (Integer::Int128(l), Integer::Int128(r)) => Integer::Int128(l / r),
-            _ => panic!("Division mismatched types"),
+        self.checked_div(rhs).expect("Integer division by zero or overflow")
+    }
+}
+
+impl Neg for Integer {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        self.checked_neg().expect("Integer overflow detected in negation")
+    }
+}
+
+impl From<i32> for Integer {
+    fn from(item: i32) -> Self {
+        Integer::Int(item)
+    }
+}
+
+/// Unsuffixed literals are `i32` unless they need `i64` or `i128`
+impl From<i128> for Integer {
+    fn from(item: i128) -> Self {
+        if let Ok(result) = i32::try_from(item) {
+            Integer::Int(result)
+        } else if let Ok(result) = i64::try_from(item) {
+            Integer::Int64(result)
+        } else {
+            Integer::Int128(item)
         }
     }
 }
 
+// integers compare by value, `Int8(5)` and `Int(5)` are the same number
+impl PartialEq for Integer {
+    fn eq(&self, other: &Self) -> bool {
+        self.promote() == other.promote()
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.promote().cmp(&other.promote())
+    }
+}
 
 impl std::fmt::Display for Integer {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Integer::Int8(n) => write!(f, "{}", n),
-            Integer::Int(n) => write!(f, "{}", n),
-            Integer::Int64(n) => write!(f, "{}", n),
-            Integer::Int128(n) => write!(f, "{}", n),
-        }
+        write!(f, "{}", self.promote())
     }
 }
-
-pub fn try_parse_number<T: std::str::FromStr>(s: &str) -> Option<T> {
-    s.parse::<T>().ok()
-}
\ No newline at end of file
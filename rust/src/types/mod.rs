@@ -1 +1,4 @@
-pub mod integer;
\ No newline at end of file
+pub mod checker;
+pub mod exhaustiveness;
+mod full_test;
+pub mod integer;